@@ -0,0 +1,345 @@
+//! Client library for the hopr module of a `hopr-polkadot` node.
+//!
+//! `HoprClient` signs hopr calls as extrinsics of the `hopr-polkadot` runtime and submits them
+//! over the WebSocket RPC of a node. `submit_and_watch` follows the extrinsic through the
+//! transaction pool until it is included in a block and returns its outcome together with the
+//! hopr events that it deposited.
+//!
+//! ```no_run
+//! use hopr_client::{hopr, HoprClient};
+//! use primitives::{sr25519, Pair};
+//!
+//! let alice = sr25519::Pair::from_string("//Alice", None).unwrap();
+//! let bob = sr25519::Pair::from_string("//Bob", None).unwrap();
+//! let mut client = HoprClient::connect("ws://127.0.0.1:9944", alice).unwrap();
+//! let included = client.submit_and_watch(hopr::Call::initiate_settlement(bob.public())).unwrap();
+//! assert!(included.success);
+//! ```
+
+mod rpc;
+
+pub use hopr_polkadot_runtime::hopr;
+pub use rpc::{Transport, WebSocket};
+
+use hopr_polkadot_runtime::{AccountId, Balance, Call, Event, Hash, Nonce, Runtime, UncheckedExtrinsic};
+use parity_codec::{Compact, Decode, Encode};
+use primitives::{blake2_256, sr25519, twox_128, Pair};
+use rpc::Rpc;
+use runtime_primitives::generic::Era;
+use serde_json::{json, Value};
+use std::fmt;
+use system::{EventRecord, Phase};
+
+/// A call of the hopr module of the `hopr-polkadot` runtime.
+pub type HoprCall = hopr::Call<Runtime>;
+
+/// An event of the hopr module of the `hopr-polkadot` runtime.
+pub type HoprEvent = hopr::RawEvent<AccountId, Hash, Balance>;
+
+#[derive(Debug)]
+pub enum Error {
+	/// The connection to the node failed or was closed.
+	Connection(String),
+	/// The node rejected a request or sent an unexpected response.
+	Rpc(String),
+	/// The transaction pool dropped the extrinsic, found it invalid or replaced it.
+	Rejected(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Connection(e) => write!(f, "Connection error: {}", e),
+			Error::Rpc(e) => write!(f, "RPC error: {}", e),
+			Error::Rejected(e) => write!(f, "Extrinsic rejected: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// An extrinsic that was included in a block.
+#[derive(Debug)]
+pub struct Included {
+	pub block_hash: Hash,
+	/// Position of the extrinsic in the block.
+	pub index: u32,
+	/// Whether the call succeeded. A failed call still pays its fees and increments the nonce.
+	///
+	/// The runtime only deposits `ExtrinsicFailed` without the reason, which is only logged by the
+	/// node that executed the block.
+	pub success: bool,
+	/// The hopr events that the extrinsic deposited.
+	pub events: Vec<HoprEvent>,
+}
+
+/// Signs and submits hopr calls for one account.
+pub struct HoprClient<T: Transport = WebSocket> {
+	rpc: Rpc<T>,
+	key: sr25519::Pair,
+	genesis_hash: Hash,
+	/// Nonce of the next extrinsic, if more recent than the one the node knows.
+	next_nonce: Option<Nonce>,
+}
+
+impl HoprClient {
+	/// Connects to the WebSocket RPC at `url` and signs extrinsics with `key`.
+	pub fn connect(url: &str, key: sr25519::Pair) -> Result<Self, Error> {
+		Self::new(WebSocket::connect(url)?, key)
+	}
+}
+
+impl<T: Transport> HoprClient<T> {
+	/// Talks to the node over `transport` and signs extrinsics with `key`.
+	pub fn new(transport: T, key: sr25519::Pair) -> Result<Self, Error> {
+		let mut rpc = Rpc::new(transport);
+		let genesis_hash = decode_hash(&rpc.request("chain_getBlockHash", json!([0]))?)?;
+
+		Ok(HoprClient {
+			rpc,
+			key,
+			genesis_hash,
+			next_nonce: None,
+		})
+	}
+
+	pub fn account(&self) -> AccountId {
+		self.key.public()
+	}
+
+	/// Index of the next extrinsic of the account at the best block of the node.
+	pub fn account_nonce(&mut self) -> Result<Nonce, Error> {
+		let result = self.rpc.request("state_call", json!([
+			"AccountNonceApi_account_nonce",
+			to_hex(&self.account().encode()),
+		]))?;
+		Nonce::decode(&mut &decode_bytes(&result)?[..])
+			.ok_or_else(|| Error::Rpc(format!("Invalid nonce {}", result)))
+	}
+
+	/// Signs `call` with the given nonce.
+	pub fn sign(&self, call: HoprCall, nonce: Nonce) -> UncheckedExtrinsic {
+		let era = Era::immortal();
+		let raw_payload = (Compact(nonce), Call::Hopr(call), era, self.genesis_hash);
+		let signature = raw_payload.using_encoded(|payload| if payload.len() > 256 {
+			self.key.sign(&blake2_256(payload)[..])
+		} else {
+			self.key.sign(payload)
+		});
+
+		UncheckedExtrinsic::new_signed(nonce, raw_payload.1, self.account().into(), signature, era)
+	}
+
+	/// Signs `call` with the next nonce and submits it to the transaction pool of the node.
+	///
+	/// Returns the hash of the extrinsic.
+	pub fn submit(&mut self, call: HoprCall) -> Result<Hash, Error> {
+		let xt = self.sign_next(call)?;
+		let result = self.rpc.request("author_submitExtrinsic", json!([to_hex(&xt.encode())]))?;
+		self.next_nonce = xt.signed.as_ref().map(|(_, _, Compact(nonce), _)| nonce + 1);
+		decode_hash(&result)
+	}
+
+	/// Same as `submit`, then waits until the extrinsic is included in a block.
+	///
+	/// Fails with `Error::Rejected` if the transaction pool drops the extrinsic, finds it invalid
+	/// or replaces it by another extrinsic with the same nonce.
+	pub fn submit_and_watch(&mut self, call: HoprCall) -> Result<Included, Error> {
+		let xt = self.sign_next(call)?;
+		let encoded = to_hex(&xt.encode());
+		let subscription = self.rpc.request("author_submitAndWatchExtrinsic", json!([encoded]))?;
+		self.next_nonce = xt.signed.as_ref().map(|(_, _, Compact(nonce), _)| nonce + 1);
+
+		let block_hash = loop {
+			// The pool reports `ready` and `{"broadcast": peers}` while the extrinsic waits, then
+			// either `{"finalized": block}` once it is included in a block or one of the statuses
+			// that end the subscription without including it.
+			let status = self.rpc.notification(&subscription)?;
+			let rejection = match &status {
+				Value::String(status) if status == "ready" => continue,
+				Value::Object(status) if status.contains_key("broadcast") => continue,
+				Value::Object(status) if status.contains_key("finalized") => break decode_hash(&status["finalized"])?,
+				Value::Object(status) if status.contains_key("usurped") => {
+					Error::Rejected(format!("Replaced by extrinsic {} with the same nonce", status["usurped"]))
+				},
+				Value::String(status) if status == "dropped" => Error::Rejected("Dropped from the transaction pool".into()),
+				Value::String(status) if status == "invalid" => Error::Rejected("Invalid at the best block".into()),
+				_ => Error::Rpc(format!("Unexpected extrinsic status {}", status)),
+			};
+			// The nonce was not used, so it is read from the node again.
+			self.next_nonce = None;
+			return Err(rejection);
+		};
+		let _ = self.rpc.request("author_unwatchExtrinsic", json!([subscription]));
+
+		self.outcome(block_hash, &encoded)
+	}
+
+	fn sign_next(&mut self, call: HoprCall) -> Result<UncheckedExtrinsic, Error> {
+		let nonce = self.account_nonce()?.max(self.next_nonce.unwrap_or(0));
+		Ok(self.sign(call, nonce))
+	}
+
+	/// Finds the extrinsic `encoded` in the block and collects its events.
+	fn outcome(&mut self, block_hash: Hash, encoded: &str) -> Result<Included, Error> {
+		let block = self.rpc.request("chain_getBlock", json!([to_hex(&block_hash)]))?;
+		let index = block["block"]["extrinsics"].as_array()
+			.and_then(|extrinsics| extrinsics.iter().position(|xt| xt.as_str() == Some(encoded)))
+			.ok_or_else(|| Error::Rpc(format!("Extrinsic is missing in block {:?}", block_hash)))? as u32;
+
+		// Plain storage values are stored under the twox_128 hash of their key.
+		let key = twox_128(b"System Events");
+		let records = self.rpc.request("state_getStorage", json!([to_hex(&key), to_hex(&block_hash)]))?;
+		let records = Vec::<EventRecord<Event>>::decode(&mut &decode_bytes(&records)?[..])
+			.ok_or_else(|| Error::Rpc(format!("Invalid events in block {:?}", block_hash)))?;
+
+		let mut included = Included { block_hash, index, success: false, events: Vec::new() };
+		for record in records {
+			if record.phase != Phase::ApplyExtrinsic(index) {
+				continue;
+			}
+			match record.event {
+				Event::system(system::Event::ExtrinsicSuccess) => included.success = true,
+				Event::hopr(event) => included.events.push(event),
+				_ => {},
+			}
+		}
+		Ok(included)
+	}
+}
+
+fn to_hex<T: AsRef<[u8]>>(bytes: T) -> String {
+	format!("0x{}", hex::encode(bytes.as_ref()))
+}
+
+fn decode_bytes(value: &Value) -> Result<Vec<u8>, Error> {
+	value.as_str()
+		.and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+		.ok_or_else(|| Error::Rpc(format!("Expected hex string, got {}", value)))
+}
+
+fn decode_hash(value: &Value) -> Result<Hash, Error> {
+	let bytes = decode_bytes(value)?;
+	if bytes.len() != 32 {
+		return Err(Error::Rpc(format!("Expected hash, got {}", value)));
+	}
+	Ok(Hash::from_slice(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::VecDeque;
+
+	const SUBSCRIPTION: u64 = 7;
+
+	/// Answers the requests of the client like a node whose pool reports `statuses` for the
+	/// submitted extrinsic, which is included as the second extrinsic of a block with `events`.
+	#[derive(Default)]
+	struct MockNode {
+		statuses: Vec<Value>,
+		events: Vec<EventRecord<Event>>,
+		submitted: Value,
+		messages: VecDeque<String>,
+	}
+
+	impl Transport for MockNode {
+		fn send(&mut self, message: String) -> Result<(), Error> {
+			let request: Value = serde_json::from_str(&message).expect("requests are valid JSON; qed");
+			let method = request["method"].as_str().expect("requests have a method; qed");
+			let result = match method {
+				"chain_getBlockHash" => json!(to_hex(Hash::repeat_byte(0x01))),
+				"state_call" => json!(to_hex(Nonce::from(3u64).encode())),
+				"author_submitAndWatchExtrinsic" => {
+					self.submitted = request["params"][0].clone();
+					json!(SUBSCRIPTION)
+				},
+				"author_unwatchExtrinsic" => json!(true),
+				"chain_getBlock" => json!({ "block": { "extrinsics": ["0x00", self.submitted] } }),
+				"state_getStorage" => json!(to_hex(self.events.encode())),
+				_ => panic!("Unexpected request {}", method),
+			};
+			self.messages.push_back(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string());
+
+			if method == "author_submitAndWatchExtrinsic" {
+				for status in self.statuses.drain(..) {
+					self.messages.push_back(json!({
+						"jsonrpc": "2.0",
+						"method": "author_extrinsicUpdate",
+						"params": { "subscription": SUBSCRIPTION, "result": status }
+					}).to_string());
+				}
+			}
+			Ok(())
+		}
+
+		fn receive(&mut self) -> Result<String, Error> {
+			self.messages.pop_front().ok_or_else(|| Error::Connection("Connection closed by the node".into()))
+		}
+	}
+
+	fn client(statuses: Vec<Value>, events: Vec<EventRecord<Event>>) -> HoprClient<MockNode> {
+		let key = sr25519::Pair::from_string("//Alice", None).expect("static values are valid; qed");
+		HoprClient::new(MockNode { statuses, events, ..Default::default() }, key).unwrap()
+	}
+
+	fn record(index: u32, event: Event) -> EventRecord<Event> {
+		EventRecord { phase: Phase::ApplyExtrinsic(index), event }
+	}
+
+	fn call() -> HoprCall {
+		let bob = sr25519::Pair::from_string("//Bob", None).expect("static values are valid; qed");
+		hopr::Call::initiate_settlement(bob.public())
+	}
+
+	#[test]
+	fn watches_extrinsic_until_included() {
+		let block_hash = Hash::repeat_byte(0xbb);
+		let settlement = hopr::RawEvent::InitiatedSettlement(Hash::repeat_byte(0xcc), 10);
+		let mut client = client(
+			vec![json!("ready"), json!({ "broadcast": ["QmPeer"] }), json!({ "finalized": to_hex(block_hash) })],
+			vec![
+				record(0, Event::hopr(hopr::RawEvent::InitiatedSettlement(Hash::repeat_byte(0xaa), 5))),
+				record(1, Event::hopr(settlement.clone())),
+				record(1, Event::system(system::Event::ExtrinsicSuccess)),
+			],
+		);
+
+		let included = client.submit_and_watch(call()).unwrap();
+		assert_eq!(included.block_hash, block_hash);
+		assert_eq!(included.index, 1);
+		assert!(included.success);
+		assert_eq!(included.events, vec![settlement]);
+		assert_eq!(client.next_nonce, Some(4));
+	}
+
+	#[test]
+	fn reports_failed_call() {
+		let mut client = client(
+			vec![json!({ "finalized": to_hex(Hash::repeat_byte(0xbb)) })],
+			vec![record(1, Event::system(system::Event::ExtrinsicFailed))],
+		);
+
+		let included = client.submit_and_watch(call()).unwrap();
+		assert!(!included.success);
+		assert!(included.events.is_empty());
+	}
+
+	#[test]
+	fn reports_rejected_extrinsics() {
+		for status in &[json!("dropped"), json!("invalid"), json!({ "usurped": to_hex(Hash::repeat_byte(0xdd)) })] {
+			let mut client = client(vec![json!("ready"), status.clone()], Vec::new());
+			match client.submit_and_watch(call()) {
+				Err(Error::Rejected(_)) => {},
+				other => panic!("{} was not reported as rejection: {:?}", status, other),
+			}
+			assert_eq!(client.next_nonce, None);
+		}
+
+		let mut client = client(vec![json!("unknown")], Vec::new());
+		match client.submit_and_watch(call()) {
+			Err(Error::Rpc(_)) => {},
+			other => panic!("Unexpected status was accepted: {:?}", other),
+		}
+	}
+}
@@ -0,0 +1,114 @@
+//! Blocking JSON-RPC over the WebSocket endpoint of a node.
+
+use crate::Error;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::net::TcpStream;
+use websocket::sync::Client;
+use websocket::{ClientBuilder, OwnedMessage};
+
+/// Carries the JSON-RPC messages to and from a node.
+pub trait Transport {
+	/// Sends one message.
+	fn send(&mut self, message: String) -> Result<(), Error>;
+	/// Waits for the next message.
+	fn receive(&mut self) -> Result<String, Error>;
+}
+
+/// A WebSocket connection to a node.
+pub struct WebSocket {
+	client: Client<TcpStream>,
+}
+
+impl WebSocket {
+	/// Connects to `url`, e.g. `ws://127.0.0.1:9944`.
+	pub fn connect(url: &str) -> Result<Self, Error> {
+		let client = ClientBuilder::new(url)
+			.map_err(|e| Error::Connection(format!("Invalid url {}: {}", url, e)))?
+			.connect_insecure()
+			.map_err(|e| Error::Connection(format!("Error connecting to {}: {}", url, e)))?;
+
+		Ok(WebSocket { client })
+	}
+}
+
+impl Transport for WebSocket {
+	fn send(&mut self, message: String) -> Result<(), Error> {
+		self.client.send_message(&OwnedMessage::Text(message))
+			.map_err(|e| Error::Connection(format!("{}", e)))
+	}
+
+	fn receive(&mut self) -> Result<String, Error> {
+		loop {
+			match self.client.recv_message().map_err(|e| Error::Connection(format!("{}", e)))? {
+				OwnedMessage::Text(text) => return Ok(text),
+				OwnedMessage::Ping(data) => self.client.send_message(&OwnedMessage::Pong(data))
+					.map_err(|e| Error::Connection(format!("{}", e)))?,
+				OwnedMessage::Close(_) => return Err(Error::Connection("Connection closed by the node".into())),
+				OwnedMessage::Binary(_) | OwnedMessage::Pong(_) => {},
+			}
+		}
+	}
+}
+
+/// A JSON-RPC connection that sends one request at a time.
+///
+/// Notifications of subscriptions that arrive while waiting for a response are kept until they
+/// are asked for.
+pub struct Rpc<T: Transport> {
+	transport: T,
+	next_id: u64,
+	notifications: VecDeque<Value>,
+}
+
+impl<T: Transport> Rpc<T> {
+	pub fn new(transport: T) -> Self {
+		Rpc {
+			transport,
+			next_id: 0,
+			notifications: VecDeque::new(),
+		}
+	}
+
+	/// Calls `method` and waits for its result.
+	pub fn request(&mut self, method: &str, params: Value) -> Result<Value, Error> {
+		self.next_id += 1;
+		let id = self.next_id;
+		let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+		self.transport.send(request.to_string())?;
+
+		loop {
+			let mut message = self.receive()?;
+			if message.get("id").and_then(Value::as_u64) != Some(id) {
+				self.notifications.push_back(message);
+				continue;
+			}
+			if let Some(error) = message.get("error") {
+				return Err(Error::Rpc(format!("{} failed: {}", method, error)));
+			}
+			return Ok(message.get_mut("result").map(Value::take).unwrap_or(Value::Null));
+		}
+	}
+
+	/// Waits for the next notification of the subscription `id` and returns its result.
+	pub fn notification(&mut self, id: &Value) -> Result<Value, Error> {
+		let is_match = |message: &Value| message["params"]["subscription"] == *id;
+
+		if let Some(position) = self.notifications.iter().position(is_match) {
+			let mut message = self.notifications.remove(position).expect("position was just found; qed");
+			return Ok(message["params"]["result"].take());
+		}
+		loop {
+			let mut message = self.receive()?;
+			if is_match(&message) {
+				return Ok(message["params"]["result"].take());
+			}
+			self.notifications.push_back(message);
+		}
+	}
+
+	fn receive(&mut self) -> Result<Value, Error> {
+		let text = self.transport.receive()?;
+		serde_json::from_str(&text).map_err(|e| Error::Rpc(format!("Invalid message {}: {}", text, e)))
+	}
+}
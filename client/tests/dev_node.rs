@@ -0,0 +1,57 @@
+//! Tests against a running dev node.
+//!
+//! Start a fresh node with `hopr-polkadot purge-chain --dev && hopr-polkadot --dev` and run them with
+//! `cargo test -- --ignored --test-threads 1` in `./client`. `HOPR_WS_URL` overrides the
+//! WebSocket endpoint of the node.
+
+use hopr_client::{hopr, HoprClient};
+use hopr_polkadot_runtime::hopr::ChannelBalance;
+use primitives::{sr25519, Pair, H256};
+use runtime_primitives::traits::{BlakeTwo256, Hash as HashT};
+
+fn key(name: &str) -> sr25519::Pair {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed")
+}
+
+fn connect(name: &str) -> HoprClient {
+	let url = std::env::var("HOPR_WS_URL").unwrap_or_else(|_| "ws://127.0.0.1:9944".into());
+	HoprClient::connect(&url, key(name)).expect("Error connecting to the dev node")
+}
+
+fn init(client: &mut HoprClient) {
+	let pubkey = H256::from_slice(client.account().as_ref());
+	let secret = BlakeTwo256::hash(BlakeTwo256::hash(client.account().as_ref()).as_ref());
+	let included = client.submit_and_watch(hopr::Call::init(pubkey, secret)).expect("Error submitting init");
+	assert!(included.success);
+}
+
+#[test]
+#[ignore]
+fn opens_channel_with_events() {
+	let (mut alice, mut bob) = (connect("Alice"), connect("Bob"));
+	init(&mut alice);
+	init(&mut bob);
+
+	let channel_balance = ChannelBalance { balance: 200, balance_a: 100 };
+	let signature = key("Bob").sign(&hopr_primitives::funding_message(&channel_balance));
+	let included = alice.submit_and_watch(hopr::Call::create_funded(key("Bob").public(), signature, 100))
+		.expect("Error submitting create_funded");
+
+	assert!(included.success);
+	let channel_id = hopr_primitives::channel_id::<BlakeTwo256, _>(&alice.account(), &bob.account());
+	assert!(included.events.contains(&hopr::RawEvent::Opened(channel_id, 200, 100)));
+}
+
+#[test]
+#[ignore]
+fn reports_failed_call() {
+	let mut charlie = connect("Charlie");
+
+	let nonce = charlie.account_nonce().expect("Error reading nonce");
+	let included = charlie.submit_and_watch(hopr::Call::withdraw(key("Dave").public()))
+		.expect("Error submitting withdraw");
+
+	assert!(!included.success);
+	assert!(included.events.is_empty());
+	assert_eq!(charlie.account_nonce().expect("Error reading nonce"), nonce + 1);
+}
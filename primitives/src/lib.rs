@@ -0,0 +1,346 @@
+//! Primitive types of the hopr module. They are shared between the runtime and off-chain
+//! clients, so that both agree on encodings, channel ids, challenges and winning tickets.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use parity_codec::{Decode, Encode};
+use rstd::prelude::*;
+use runtime_primitives::traits::{CheckedSub, Hash};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+/// Tag that is part of the message signed to agree on a backup state of a channel.
+pub const RECOVERY_TAG: &str = "restore_transaction";
+
+/// Tag that is part of the message signed to acknowledge a packet.
+pub const ACKNOWLEDGEMENT_TAG: &str = "acknowledgement";
+
+/// Tag that is part of the message signed to lock a conditional payment.
+pub const HASH_LOCK_TAG: &str = "hash_lock";
+
+#[derive(Clone, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase", deny_unknown_fields))]
+pub struct ChannelBalance<Balance> {
+	/// Total amount of funds locked in the channel.
+	pub balance: Balance,
+	/// Part of `balance` that belongs to party A.
+	pub balance_a: Balance,
+}
+
+impl<Balance> ChannelBalance<Balance> {
+	pub fn new(balance: Balance, balance_a: Balance) -> Self {
+		ChannelBalance {
+			balance,
+			balance_a,
+		}
+	}
+}
+
+impl<Balance: CheckedSub> ChannelBalance<Balance> {
+	/// Part of `balance` that belongs to party B, or `None` if `balance_a` exceeds `balance`.
+	pub fn balance_b(&self) -> Option<Balance> {
+		self.balance.checked_sub(&self.balance_a)
+	}
+}
+
+#[derive(Clone, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub enum Channel<Balance, Moment> {
+	Uninitialized,
+	Funded(ChannelBalance<Balance>),
+	Active(ChannelBalance<Balance>),
+	PendingSettlement(ChannelBalance<Balance>, Moment),
+}
+
+impl<Balance, Moment> Default for Channel<Balance, Moment> {
+	fn default() -> Self {
+		Self::Uninitialized
+	}
+}
+
+/// A ticket that pays `amount` with probability `win_prob` if it is redeemed at the latest in the
+/// block `expiry`.
+///
+/// The ticket commits to `next_hop`, the hash of the public key of the downstream node, so that only
+/// the node that the issuer forwards the packet to can acknowledge it.
+///
+/// Once the block `expiry` is final, none of the tickets of an issuer that expired until then can
+/// be redeemed anymore, which bounds its liability to the tickets that expire later.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase", deny_unknown_fields))]
+pub struct LotteryTicket<Hash, Balance, BlockNumber> {
+	challenge: Hash,
+	next_hop: Hash,
+	on_chain_secret: Hash,
+	amount: Balance,
+	win_prob: Hash,
+	expiry: BlockNumber,
+}
+
+impl<Hash: AsRef<[u8]> + Encode, Balance: Encode, BlockNumber: Encode + PartialOrd> LotteryTicket<Hash, Balance, BlockNumber> {
+	/// Creates a ticket that pays `amount` with winning probability `win_prob` until the block
+	/// `expiry` once the downstream node with the hashed public key `next_hop` acknowledges it.
+	pub fn new(challenge: Hash, next_hop: Hash, on_chain_secret: Hash, amount: Balance, win_prob: Hash, expiry: BlockNumber) -> Self {
+		LotteryTicket {
+			challenge,
+			next_hop,
+			on_chain_secret,
+			amount,
+			win_prob,
+			expiry,
+		}
+	}
+
+	pub fn challenge(&self) -> &Hash {
+		&self.challenge
+	}
+
+	/// Hash of the public key of the downstream node that has to acknowledge the ticket.
+	pub fn next_hop(&self) -> &Hash {
+		&self.next_hop
+	}
+
+	pub fn on_chain_secret(&self) -> &Hash {
+		&self.on_chain_secret
+	}
+
+	pub fn amount(&self) -> &Balance {
+		&self.amount
+	}
+
+	pub fn win_prob(&self) -> &Hash {
+		&self.win_prob
+	}
+
+	/// The last block that the ticket can be redeemed in.
+	pub fn expiry(&self) -> &BlockNumber {
+		&self.expiry
+	}
+
+	/// Whether the ticket can no longer be redeemed in the block `block_number`.
+	pub fn is_expired(&self, block_number: &BlockNumber) -> bool {
+		*block_number > self.expiry
+	}
+
+	/// A ticket is a win if its hash is smaller than `win_prob`.
+	pub fn is_win<H: runtime_primitives::traits::Hash<Output = Hash>>(&self) -> bool {
+		*(self.using_encoded(H::hash).as_ref()) < *(self.win_prob.as_ref())
+	}
+}
+
+/// Acknowledgement of the next downstream node that it received the packet of a ticket.
+///
+/// It reveals the key half `s_b` of the downstream node, which the relay needs together with its
+/// own key half `s_a` to redeem the ticket.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Acknowledgement<Hash> {
+	challenge: Hash,
+	key_half: Hash,
+}
+
+impl<Hash: AsRef<[u8]> + Encode + PartialEq> Acknowledgement<Hash> {
+	/// Acknowledges the packet of the ticket with `challenge` by revealing `key_half`.
+	pub fn new(challenge: Hash, key_half: Hash) -> Self {
+		Acknowledgement {
+			challenge,
+			key_half,
+		}
+	}
+
+	pub fn challenge(&self) -> &Hash {
+		&self.challenge
+	}
+
+	/// The key half `s_b` of the downstream node.
+	pub fn key_half(&self) -> &Hash {
+		&self.key_half
+	}
+
+	/// Whether the key half completes the challenge together with `s_a`.
+	pub fn matches<H: runtime_primitives::traits::Hash<Output = Hash>>(&self, s_a: &[u8]) -> bool {
+		challenge::<H>(s_a, self.key_half.as_ref()) == self.challenge
+	}
+}
+
+/// Conditional payment of `amount` to the counterparty in the channel `channel_id`.
+///
+/// The counterparty gets the payment if it reveals a pre-image of `hash_lock` until the block
+/// `deadline`, otherwise the payment is refunded. Locks with the same `hash_lock` along a path,
+/// with deadlines that decrease towards the recipient, make a multi-hop payment atomic.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct HashLock<Hash, Balance, BlockNumber> {
+	pub channel_id: Hash,
+	pub hash_lock: Hash,
+	pub amount: Balance,
+	pub deadline: BlockNumber,
+}
+
+/// Computes the challenge `H(H(s_a), H(s_b))` of a ticket from both key halves.
+pub fn challenge<H: Hash>(s_a: &[u8], s_b: &[u8]) -> H::Output {
+	(H::hash(s_a), H::hash(s_b)).using_encoded(H::hash)
+}
+
+/// Computes the `next_hop` of a ticket that is acknowledged by the node with public key `pubkey`.
+pub fn next_hop<H: Hash>(pubkey: &[u8]) -> H::Output {
+	H::hash(pubkey)
+}
+
+/// Whether `value` is among the values `H(pre_image), H(H(pre_image)), ..` of the hash chain of
+/// `pre_image` up to and including `secret`, or `None` if `secret` is not among the first
+/// `max_depth` of them.
+///
+/// The values are checked while walking the chain, so that nothing is allocated.
+pub fn hash_chain_contains<H: Hash>(pre_image: &H::Output, secret: &H::Output, value: &H::Output, max_depth: u32) -> Option<bool> {
+	let mut contains = false;
+	let mut current = *pre_image;
+	for _ in 0..max_depth {
+		current = H::hash(current.as_ref());
+		contains |= current == *value;
+		if current == *secret {
+			return Some(contains);
+		}
+	}
+	None
+}
+
+/// Party A of a channel is the party with the smaller account id.
+pub fn is_party_a<AccountId: Ord>(a: &AccountId, b: &AccountId) -> bool {
+	a < b
+}
+
+/// Gives the payment channels a meaningful ID that is the same for both parties.
+pub fn channel_id<H: Hash, AccountId: Encode + Ord>(a: &AccountId, b: &AccountId) -> H::Output {
+	if is_party_a(a, b) {
+		(a, b).using_encoded(H::hash)
+	} else {
+		(b, a).using_encoded(H::hash)
+	}
+}
+
+/// Message that the counterparty signs to agree on funding a channel with `channel_balance`.
+pub fn funding_message<Balance: Encode>(channel_balance: &ChannelBalance<Balance>) -> Vec<u8> {
+	// Same encoding as `Channel::Funded(channel_balance)`, which does not depend on `Moment`.
+	(1u8, channel_balance).encode()
+}
+
+/// Message that the counterparty signs to agree on `channel_balance` as backup state of a channel.
+pub fn recovery_message<Hash: Encode, Balance: Encode>(channel_id: &Hash, channel_balance: &ChannelBalance<Balance>) -> Vec<u8> {
+	(RECOVERY_TAG, channel_id, channel_balance).encode()
+}
+
+/// Message that the downstream node signs to acknowledge a packet.
+pub fn acknowledgement_message<Hash: Encode>(acknowledgement: &Acknowledgement<Hash>) -> Vec<u8> {
+	(ACKNOWLEDGEMENT_TAG, acknowledgement).encode()
+}
+
+/// Message that the payer signs to lock a conditional payment.
+pub fn hash_lock_message<Hash: Encode, Balance: Encode, BlockNumber: Encode>(lock: &HashLock<Hash, Balance, BlockNumber>) -> Vec<u8> {
+	(HASH_LOCK_TAG, lock).encode()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use primitives::{Blake2Hasher, Hasher, H256};
+	use runtime_primitives::traits::BlakeTwo256;
+
+	#[test]
+	fn channel_id_is_symmetric() {
+		let a = H256::repeat_byte(1);
+		let b = H256::repeat_byte(2);
+
+		assert!(is_party_a(&a, &b));
+		assert!(!is_party_a(&b, &a));
+		assert_eq!(channel_id::<BlakeTwo256, _>(&a, &b), channel_id::<BlakeTwo256, _>(&b, &a));
+		assert_eq!(channel_id::<BlakeTwo256, _>(&a, &b), (a, b).using_encoded(BlakeTwo256::hash));
+	}
+
+	#[test]
+	fn challenge_hashes_both_key_halves() {
+		let s_a = [3u8; 32];
+		let s_b = [4u8; 32];
+
+		let expected = (
+			<Blake2Hasher as Hasher>::hash(&s_a),
+			<Blake2Hasher as Hasher>::hash(&s_b),
+		).using_encoded(<Blake2Hasher as Hasher>::hash);
+
+		assert_eq!(challenge::<BlakeTwo256>(&s_a, &s_b), expected);
+	}
+
+	#[test]
+	fn hash_chain_stops_at_max_depth() {
+		let pre_image = H256::repeat_byte(5);
+		let first = BlakeTwo256::hash(pre_image.as_ref());
+		let second = BlakeTwo256::hash(first.as_ref());
+
+		assert_eq!(hash_chain_contains::<BlakeTwo256>(&pre_image, &first, &first, 1), Some(true));
+		assert_eq!(hash_chain_contains::<BlakeTwo256>(&pre_image, &second, &first, 2), Some(true));
+		assert_eq!(hash_chain_contains::<BlakeTwo256>(&pre_image, &second, &second, 2), Some(true));
+		assert_eq!(hash_chain_contains::<BlakeTwo256>(&pre_image, &second, &pre_image, 2), Some(false));
+		assert_eq!(hash_chain_contains::<BlakeTwo256>(&pre_image, &second, &second, 1), None);
+		assert_eq!(hash_chain_contains::<BlakeTwo256>(&pre_image, &pre_image, &pre_image, 2), None);
+	}
+
+	#[test]
+	fn funding_message_matches_channel_encoding() {
+		let channel_balance = ChannelBalance {
+			balance: 2u128,
+			balance_a: 1u128,
+		};
+
+		assert_eq!(
+			funding_message(&channel_balance),
+			(Channel::Funded(channel_balance.clone()) as Channel<u128, u64>).encode()
+		);
+	}
+
+	#[test]
+	fn channel_balance_of_party_b() {
+		assert_eq!(ChannelBalance::new(10u128, 3).balance_b(), Some(7));
+		assert_eq!(ChannelBalance::new(10u128, 10).balance_b(), Some(0));
+		assert_eq!(ChannelBalance::new(10u128, 11).balance_b(), None);
+	}
+
+	#[test]
+	fn ticket_json_round_trip() {
+		let ticket = LotteryTicket::new(H256::repeat_byte(1), H256::repeat_byte(6), H256::repeat_byte(2), 3u128, H256::repeat_byte(4), 5u64);
+		let json = serde_json::to_value(&ticket).unwrap();
+
+		assert_eq!(json["nextHop"], serde_json::to_value(H256::repeat_byte(6)).unwrap());
+		assert_eq!(json["onChainSecret"], serde_json::to_value(H256::repeat_byte(2)).unwrap());
+		assert_eq!(serde_json::from_value::<LotteryTicket<H256, u128, u64>>(json).unwrap(), ticket);
+	}
+
+	#[test]
+	fn acknowledgement_matches_first_key_half() {
+		let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+		let acknowledgement = Acknowledgement::new(challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()), s_b);
+
+		assert!(acknowledgement.matches::<BlakeTwo256>(s_a.as_ref()));
+		assert!(!acknowledgement.matches::<BlakeTwo256>(s_b.as_ref()));
+	}
+
+	#[test]
+	fn ticket_wins_below_win_prob() {
+		let ticket = LotteryTicket::new(H256::zero(), H256::zero(), H256::zero(), 1u128, H256::repeat_byte(0xff), 1u64);
+		assert!(ticket.is_win::<BlakeTwo256>());
+
+		let ticket = LotteryTicket::new(H256::zero(), H256::zero(), H256::zero(), 1u128, H256::zero(), 1u64);
+		assert!(!ticket.is_win::<BlakeTwo256>());
+	}
+
+	#[test]
+	fn ticket_expires_after_expiry() {
+		let ticket = LotteryTicket::new(H256::zero(), H256::zero(), H256::zero(), 1u128, H256::zero(), 10u64);
+
+		assert!(!ticket.is_expired(&10));
+		assert!(ticket.is_expired(&11));
+	}
+}
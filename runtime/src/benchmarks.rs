@@ -0,0 +1,475 @@
+//! Benchmarks of the dispatchable functions of the hopr module.
+//!
+//! Every benchmark prepares the storage for the most expensive path of one call, dispatches the
+//! call through the runtime and measures the time it takes as well as the storage it reads and
+//! changes.
+//! Only the dispatch is timed, the setup of every repetition starts from a fresh genesis state.
+
+use crate::{
+	hopr, AccountId, Balance, BalancesConfig, BlockNumber, BuildStorage, Call, GenesisConfig, Hash, Hopr,
+	HoprConfig, IndicesConfig, Moment, Origin, Runtime, System, TimestampConfig, PENDING_WINDOW,
+};
+use hopr_primitives::{Acknowledgement, ChannelBalance, HashLock, LotteryTicket};
+use parity_codec::Encode;
+use primitives::{sr25519, Blake2Hasher, Pair, H256};
+use runtime_io::{with_externalities, TestExternalities};
+use runtime_primitives::traits::{BlakeTwo256, Hash as HashT};
+use state_machine::{offchain, ChildStorageKey, Externalities};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use support::dispatch::Dispatchable;
+
+/// Names of all benchmarks, one per dispatchable function of the hopr module.
+pub const BENCHMARKS: &[&str] = &[
+	"init",
+	"set_secret",
+	"create",
+	"set_active",
+	"create_funded",
+	"redeem_ticket",
+	"redeem_tickets",
+	"initiate_recovery",
+	"initiate_settlement",
+	"withdraw",
+	"announce",
+	"lock_payment",
+	"claim_payment",
+	"refund_payment",
+];
+
+/// Free balance of both parties.
+const ENDOWMENT: Balance = 1 << 60;
+
+/// Funds that each party locks in a channel.
+const FUNDS: Balance = 1 << 40;
+
+/// Time after the end of every pending window that is set up by the benchmarks.
+const AFTER_PENDING_WINDOW: Moment = 1_000 * PENDING_WINDOW;
+
+/// Measurements of one benchmark.
+#[derive(Clone, Debug)]
+pub struct BenchmarkResult {
+	pub name: &'static str,
+	pub repetitions: u32,
+	/// Fastest, average and slowest execution of the call.
+	pub min: Duration,
+	pub mean: Duration,
+	pub max: Duration,
+	/// Storage reads of the call, every read of the same key counts.
+	pub reads: usize,
+	/// Storage keys that the call inserted or changed.
+	pub writes: usize,
+	/// Storage keys that the call removed.
+	pub removals: usize,
+	/// Encoded size of the values that the call wrote.
+	pub bytes_written: usize,
+}
+
+/// Runs the benchmark `name` `repetitions` times.
+pub fn run(name: &str, repetitions: u32) -> Result<BenchmarkResult, String> {
+	let name = *BENCHMARKS.iter().find(|benchmark| **benchmark == name)
+		.ok_or_else(|| format!("Unknown benchmark `{}`, expected one of {:?}.", name, BENCHMARKS))?;
+	if repetitions == 0 {
+		return Err("Benchmarks must run at least once.".into());
+	}
+
+	let genesis = genesis_storage()?;
+	let mut times = Vec::with_capacity(repetitions as usize);
+	let mut reads = 0;
+	let mut changes = (0, 0, 0);
+
+	for _ in 0..repetitions {
+		let mut ext = TestExternalities::<Blake2Hasher>::from(genesis.clone());
+		let (sender, call) = with_externalities(&mut ext, || setup(name))?;
+		let before: HashMap<Vec<u8>, Vec<u8>> = ext.iter_pairs_in_order().collect();
+
+		let mut counting = CountingExternalities::new(&mut ext);
+		let (time, result) = with_externalities(&mut counting, || {
+			let start = Instant::now();
+			let result = call.dispatch(Origin::signed(sender));
+			(start.elapsed(), result)
+		});
+		result.map_err(|e| format!("Benchmark `{}` failed: {}", name, e))?;
+		reads = counting.reads.get();
+
+		times.push(time);
+		changes = storage_changes(&before, ext.iter_pairs_in_order().collect());
+	}
+
+	let total = times.iter().fold(Duration::from_secs(0), |total, time| total + *time);
+	let (writes, removals, bytes_written) = changes;
+
+	Ok(BenchmarkResult {
+		name,
+		repetitions,
+		min: *times.iter().min().expect("runs at least once; qed"),
+		mean: total / repetitions,
+		max: *times.iter().max().expect("runs at least once; qed"),
+		reads,
+		writes,
+		removals,
+		bytes_written,
+	})
+}
+
+/// Runs every benchmark `repetitions` times.
+pub fn run_all(repetitions: u32) -> Result<Vec<BenchmarkResult>, String> {
+	BENCHMARKS.iter().map(|name| run(name, repetitions)).collect()
+}
+
+fn genesis_storage() -> Result<HashMap<Vec<u8>, Vec<u8>>, String> {
+	let accounts = vec![key("Alice").public(), key("Bob").public()];
+
+	let config = GenesisConfig {
+		consensus: None,
+		system: None,
+		timestamp: Some(TimestampConfig {
+			minimum_period: 5,
+		}),
+		indices: Some(IndicesConfig {
+			ids: accounts.clone(),
+		}),
+		balances: Some(BalancesConfig {
+			transaction_base_fee: 1,
+			transaction_byte_fee: 0,
+			existential_deposit: 500,
+			transfer_fee: 0,
+			creation_fee: 0,
+			balances: accounts.into_iter().map(|account| (account, ENDOWMENT)).collect(),
+			vesting: vec![],
+		}),
+		sudo: None,
+		hopr: Some(HoprConfig {
+			pending_window: PENDING_WINDOW,
+			max_pre_image_depth: hopr::MAX_PRE_IMAGE_DEPTH_LIMIT,
+			states: vec![],
+		}),
+		bridge: None,
+		registry: None,
+	};
+
+	Ok(config.build_storage()?.0)
+}
+
+/// Prepares the worst case of the benchmark `name` and returns the call to measure together
+/// with its sender.
+///
+/// Alice sends every call, Bob is the counterparty.
+fn setup(name: &str) -> Result<(AccountId, Call), String> {
+	let alice = key("Alice");
+	let bob = key("Bob");
+	let sender = alice.public();
+	let counterparty = bob.public();
+
+	let call = match name {
+		"init" => hopr::Call::init(pubkey(&alice), secret(&alice)),
+		"set_secret" => {
+			init(&alice)?;
+			hopr::Call::set_secret(BlakeTwo256::hash(secret(&alice).as_ref()))
+		},
+		"create" => {
+			// Adding funds to a funded channel reads and updates its balance.
+			init(&alice)?;
+			init(&bob)?;
+			Hopr::create(Origin::signed(counterparty.clone()), FUNDS, sender.clone())?;
+			hopr::Call::create(FUNDS, counterparty.clone())
+		},
+		"set_active" => {
+			init(&alice)?;
+			init(&bob)?;
+			Hopr::create(Origin::signed(sender.clone()), FUNDS, counterparty.clone())?;
+
+			let balance_a = if hopr_primitives::is_party_a(&sender, &counterparty) { FUNDS } else { 0 };
+			let channel_balance = ChannelBalance {
+				balance: FUNDS,
+				balance_a,
+			};
+			let signature = bob.sign(&hopr_primitives::funding_message(&channel_balance));
+			hopr::Call::set_active(counterparty.clone(), signature)
+		},
+		"create_funded" => {
+			init(&alice)?;
+			init(&bob)?;
+			let signature = bob.sign(&hopr_primitives::funding_message(&funded_balance()));
+			hopr::Call::create_funded(counterparty.clone(), signature, FUNDS)
+		},
+		"redeem_ticket" => {
+			// Redeeming during the pending window also pushes back the settlement. The pre-image
+			// lies as deep as possible below the on-chain secret, with the ticket issued right above
+			// it, so that the whole hash chain is walked and searched.
+			let depth = hopr::MAX_PRE_IMAGE_DEPTH_LIMIT as usize;
+			let chain = hash_chain(pre_image(&alice), depth);
+			Hopr::init(Origin::signed(sender.clone()), pubkey(&alice), chain[depth])?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+			Hopr::initiate_settlement(Origin::signed(counterparty.clone()), sender.clone())?;
+
+			// Charlie is the next node on the path of the packet.
+			let charlie = key("Charlie");
+			init(&charlie)?;
+
+			let redemption = redemption(&bob, &charlie, chain[1], chain[0]);
+			hopr::Call::redeem_ticket(
+				redemption.signature,
+				redemption.counterparty,
+				redemption.on_chain_secret,
+				redemption.pre_image,
+				redemption.s_a,
+				redemption.acknowledgement,
+				redemption.acknowledgement_signature,
+				redemption.downstream,
+				redemption.amount,
+				redemption.win_prob,
+				redemption.expiry,
+			)
+		},
+		"redeem_tickets" => {
+			// As many tickets as possible, which all push back the settlement of the same channel and
+			// reveal pre-images as deep as possible.
+			let charlie = key("Charlie");
+			init(&bob)?;
+			init(&charlie)?;
+
+			let depth = hopr::MAX_PRE_IMAGE_DEPTH_LIMIT as usize;
+			let chain = hash_chain(pre_image(&alice), hopr::MAX_REDEEMED_TICKETS * depth);
+			Hopr::init(Origin::signed(sender.clone()), pubkey(&alice), chain[hopr::MAX_REDEEMED_TICKETS * depth])?;
+			create_funded(&alice, &bob)?;
+			Hopr::initiate_settlement(Origin::signed(counterparty.clone()), sender.clone())?;
+
+			let tickets = (0..hopr::MAX_REDEEMED_TICKETS).rev()
+				.map(|k| redemption(&bob, &charlie, chain[k * depth + 1], chain[k * depth]))
+				.collect();
+			hopr::Call::redeem_tickets(tickets)
+		},
+		"initiate_recovery" => {
+			init(&alice)?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+
+			let channel_id = hopr_primitives::channel_id::<BlakeTwo256, _>(&sender, &counterparty);
+			let signature = bob.sign(&hopr_primitives::recovery_message(&channel_id, &funded_balance()));
+			hopr::Call::initiate_recovery(counterparty.clone(), signature, funded_balance())
+		},
+		"initiate_settlement" => {
+			init(&alice)?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+			hopr::Call::initiate_settlement(counterparty.clone())
+		},
+		"withdraw" => {
+			// Both parties get funds back.
+			init(&alice)?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+			Hopr::initiate_settlement(Origin::signed(sender.clone()), counterparty.clone())?;
+			timestamp::Call::<Runtime>::set(AFTER_PENDING_WINDOW).dispatch(Origin::INHERENT)?;
+			hopr::Call::withdraw(counterparty.clone())
+		},
+		"announce" => {
+			// The first announcement of an account also adds it to the announced nodes, and
+			// announcements of the maximum size take longest to check and write.
+			init(&alice)?;
+			hopr::Call::announce(
+				vec![0u8; hopr::MAX_PEER_ID_LEN],
+				vec![vec![0u8; hopr::MAX_MULTIADDR_LEN]; hopr::MAX_MULTIADDRS],
+			)
+		},
+		"lock_payment" => {
+			init(&alice)?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+			let lock = hash_lock(&bob, &alice, 0);
+			let signature = bob.sign(&hopr_primitives::hash_lock_message(&lock));
+			hopr::Call::lock_payment(counterparty.clone(), lock, signature)
+		},
+		"claim_payment" => {
+			// Claiming in an open channel also adds the payment to the part of the payee.
+			init(&alice)?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+			let lock_id = lock_payment(&bob, &alice, 0)?;
+			hopr::Call::claim_payment(lock_id, pre_image(&bob))
+		},
+		"refund_payment" => {
+			init(&alice)?;
+			init(&bob)?;
+			create_funded(&alice, &bob)?;
+			let lock_id = lock_payment(&alice, &bob, 0)?;
+			System::set_block_number(1);
+			hopr::Call::refund_payment(lock_id)
+		},
+		_ => return Err(format!("Unknown benchmark `{}`.", name)),
+	};
+
+	Ok((sender, Call::Hopr(call)))
+}
+
+/// Externalities that count the storage reads of the call and pass everything else through.
+struct CountingExternalities<'a> {
+	inner: &'a mut TestExternalities<Blake2Hasher>,
+	reads: Cell<usize>,
+}
+
+impl<'a> CountingExternalities<'a> {
+	fn new(inner: &'a mut TestExternalities<Blake2Hasher>) -> Self {
+		CountingExternalities { inner, reads: Cell::new(0) }
+	}
+
+	fn count_read(&self) {
+		self.reads.set(self.reads.get() + 1);
+	}
+}
+
+impl<'a> Externalities<Blake2Hasher> for CountingExternalities<'a> {
+	fn storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.count_read();
+		self.inner.storage(key)
+	}
+
+	fn child_storage(&self, storage_key: ChildStorageKey<Blake2Hasher>, key: &[u8]) -> Option<Vec<u8>> {
+		self.count_read();
+		self.inner.child_storage(storage_key, key)
+	}
+
+	fn kill_child_storage(&mut self, storage_key: ChildStorageKey<Blake2Hasher>) {
+		self.inner.kill_child_storage(storage_key)
+	}
+
+	fn clear_prefix(&mut self, prefix: &[u8]) {
+		self.inner.clear_prefix(prefix)
+	}
+
+	fn place_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.inner.place_storage(key, value)
+	}
+
+	fn place_child_storage(&mut self, storage_key: ChildStorageKey<Blake2Hasher>, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.inner.place_child_storage(storage_key, key, value)
+	}
+
+	fn chain_id(&self) -> u64 {
+		self.inner.chain_id()
+	}
+
+	fn storage_root(&mut self) -> H256 {
+		self.inner.storage_root()
+	}
+
+	fn child_storage_root(&mut self, storage_key: ChildStorageKey<Blake2Hasher>) -> Vec<u8> {
+		self.inner.child_storage_root(storage_key)
+	}
+
+	fn storage_changes_root(&mut self, parent: H256, parent_num: u64) -> Option<H256> {
+		self.inner.storage_changes_root(parent, parent_num)
+	}
+
+	fn offchain(&mut self) -> Option<&mut dyn offchain::Externalities> {
+		self.inner.offchain()
+	}
+}
+
+/// Returns the number of inserted or changed keys, of removed keys and of written bytes.
+fn storage_changes(before: &HashMap<Vec<u8>, Vec<u8>>, after: HashMap<Vec<u8>, Vec<u8>>) -> (usize, usize, usize) {
+	let removals = before.keys().filter(|key| !after.contains_key(*key)).count();
+	let written: Vec<&Vec<u8>> = after.iter()
+		.filter(|(key, value)| before.get(*key) != Some(*value))
+		.map(|(_, value)| value)
+		.collect();
+
+	(written.len(), removals, written.iter().map(|value| value.len()).sum())
+}
+
+fn key(name: &str) -> sr25519::Pair {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed")
+}
+
+fn pubkey(pair: &sr25519::Pair) -> H256 {
+	H256::from_slice(pair.public().as_ref())
+}
+
+/// The pre-image of the initial on-chain secret of `pair`.
+fn pre_image(pair: &sr25519::Pair) -> Hash {
+	BlakeTwo256::hash(pair.public().as_ref())
+}
+
+fn secret(pair: &sr25519::Pair) -> Hash {
+	BlakeTwo256::hash(pre_image(pair).as_ref())
+}
+
+/// The hash chain of `pre_image` with `len` hashes, where `chain[k + 1]` is the hash of
+/// `chain[k]`.
+fn hash_chain(pre_image: Hash, len: usize) -> Vec<Hash> {
+	let mut chain = vec![pre_image];
+	for k in 0..len {
+		chain.push(BlakeTwo256::hash(chain[k].as_ref()));
+	}
+	chain
+}
+
+fn init(pair: &sr25519::Pair) -> Result<(), String> {
+	Hopr::init(Origin::signed(pair.public()), pubkey(pair), secret(pair)).map_err(Into::into)
+}
+
+/// Balance of the channels that `create_funded` opens.
+fn funded_balance() -> ChannelBalance<Balance> {
+	ChannelBalance {
+		balance: 2 * FUNDS,
+		balance_a: FUNDS,
+	}
+}
+
+/// Redemption of a winning ticket worth 1 that `issuer` issued for `on_chain_secret` and that
+/// `downstream` acknowledged.
+fn redemption(issuer: &sr25519::Pair, downstream: &sr25519::Pair, on_chain_secret: Hash, pre_image: Hash) -> hopr::Redemption<Runtime> {
+	let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+	let win_prob = H256::repeat_byte(0xff);
+	let expiry: BlockNumber = 1;
+	let ticket = LotteryTicket::new(
+		hopr::challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()),
+		hopr_primitives::next_hop::<BlakeTwo256>(downstream.public().as_ref()),
+		on_chain_secret,
+		1 as Balance,
+		win_prob,
+		expiry,
+	);
+	let acknowledgement = Acknowledgement::new(*ticket.challenge(), s_b);
+
+	hopr::TicketRedemption {
+		signature: issuer.sign(&ticket.encode()),
+		counterparty: issuer.public(),
+		on_chain_secret,
+		pre_image,
+		s_a,
+		acknowledgement_signature: downstream.sign(&hopr_primitives::acknowledgement_message(&acknowledgement)),
+		acknowledgement,
+		downstream: downstream.public(),
+		amount: 1,
+		win_prob,
+		expiry,
+	}
+}
+
+/// Payment of 1 from `payer` to `payee` that is locked with the hash of `pre_image(payer)`.
+fn hash_lock(payer: &sr25519::Pair, payee: &sr25519::Pair, deadline: BlockNumber) -> HashLock<Hash, Balance, BlockNumber> {
+	HashLock {
+		channel_id: hopr_primitives::channel_id::<BlakeTwo256, _>(&payer.public(), &payee.public()),
+		hash_lock: BlakeTwo256::hash(pre_image(payer).as_ref()),
+		amount: 1,
+		deadline,
+	}
+}
+
+/// Locks the payment of `hash_lock` in the channel of both parties and returns its id.
+fn lock_payment(payer: &sr25519::Pair, payee: &sr25519::Pair, deadline: BlockNumber) -> Result<Hash, String> {
+	let signature = payer.sign(&hopr_primitives::hash_lock_message(&hash_lock(payer, payee, deadline)));
+	Hopr::lock_payment(Origin::signed(payee.public()), payer.public(), hash_lock(payer, payee, deadline), signature.clone())?;
+	Ok(BlakeTwo256::hash(signature.as_ref()))
+}
+
+/// Opens an active channel that is funded by both parties.
+fn create_funded(sender: &sr25519::Pair, counterparty: &sr25519::Pair) -> Result<(), String> {
+	let signature = counterparty.sign(&hopr_primitives::funding_message(&funded_balance()));
+	Hopr::create_funded(Origin::signed(sender.public()), counterparty.public(), signature, FUNDS)
+		.map_err(Into::into)
+}
@@ -0,0 +1,386 @@
+//! Bridge of the HOPR token on Ethereum.
+//!
+//! Locking HOPR on Ethereum mints the same amount of the native currency, once a quorum of
+//! registered relayers attested to the lock event. The minted funds are wrapped HOPR and can fund
+//! hopr channels right away. Burning wrapped HOPR records a withdrawal, which the relayers
+//! unlock on Ethereum. An account can only burn as much as the bridge minted to it, so that funds
+//! that never came from Ethereum, such as the endowments at genesis, cannot be unlocked there.
+
+use parity_codec::{Decode, Encode};
+use primitives::{H160, H256};
+use rstd::prelude::*;
+use runtime_primitives::traits::{As, Hash, Saturating};
+use support::{
+	decl_event, decl_module, decl_storage, dispatch::Result, ensure,
+	traits::{Currency, ReservableCurrency},
+	StorageMap, StorageValue,
+};
+use system::{ensure_root, ensure_signed};
+
+#[cfg(test)]
+mod mock;
+
+/// A lock of HOPR tokens on Ethereum, identified by its transaction and log index.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Lock<AccountId, Balance> {
+	pub tx_hash: H256,
+	pub log_index: u32,
+	/// Account that the wrapped tokens are minted to.
+	pub recipient: AccountId,
+	pub amount: Balance,
+}
+
+/// Wrapped tokens that were burned to be unlocked on Ethereum.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Withdrawal<AccountId, Balance, BlockNumber> {
+	pub account: AccountId,
+	/// Ethereum address that the tokens are unlocked to.
+	pub eth_recipient: H160,
+	pub amount: Balance,
+	pub block: BlockNumber,
+}
+
+pub type LockId<T> = <T as system::Trait>::Hash;
+
+pub trait Trait: system::Trait + balances::Trait {
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as bridge {
+		Relayers get(relayers) config(): Vec<T::AccountId>;
+		/// Number of relayers that have to attest to a lock before it is minted.
+		Threshold get(threshold) config(): u32;
+		/// Relayers that attested to a lock, by hash of the lock.
+		Attestations get(attestations): map LockId<T> => Vec<T::AccountId>;
+		/// Lock events on Ethereum that were minted, by transaction hash and log index.
+		Minted get(is_minted): map (H256, u32) => bool;
+		/// Wrapped tokens that the bridge minted to an account and that it did not burn yet.
+		Bridged get(bridged): map T::AccountId => T::Balance;
+		Withdrawals get(withdrawals): map u64 => Option<Withdrawal<T::AccountId, T::Balance, T::BlockNumber>>;
+		WithdrawalCount get(withdrawal_count): u64;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event<T>() = default;
+
+		/// Attests to a lock on Ethereum. Mints the locked amount once `threshold` relayers
+		/// attested to the same lock. Attestations of removed relayers no longer count.
+		pub fn attest_lock(origin, lock: Lock<T::AccountId, T::Balance>) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let relayers = Self::relayers();
+			ensure!(relayers.contains(&sender), "Sender is not a relayer.");
+			ensure!(Self::threshold() > 0, "Threshold of the bridge is not set.");
+			ensure!(lock.amount > <T::Balance as As<u64>>::sa(0), "Locked amount must be strictly greater than zero.");
+			ensure!(!Self::is_minted((lock.tx_hash, lock.log_index)), "Lock was already minted.");
+
+			let lock_id = T::Hashing::hash_of(&lock);
+			let mut attestations = Self::attestations(lock_id);
+			ensure!(!attestations.contains(&sender), "Relayer already attested to this lock.");
+			// The attestations cannot be enumerated when a relayer is removed, so they are dropped here.
+			attestations.retain(|relayer| relayers.contains(relayer));
+
+			attestations.push(sender.clone());
+			Self::deposit_event(RawEvent::Attested(lock_id, sender));
+
+			if (attestations.len() as u32) < Self::threshold() {
+				<Attestations<T>>::insert(lock_id, attestations);
+				return Ok(());
+			}
+
+			<Attestations<T>>::remove(lock_id);
+			<Minted<T>>::insert((lock.tx_hash, lock.log_index), true);
+			<Bridged<T>>::mutate(&lock.recipient, |bridged| *bridged = bridged.saturating_add(lock.amount));
+			let _ = <balances::Module<T> as Currency<<T as system::Trait>::AccountId>>::deposit_creating(&lock.recipient, lock.amount);
+
+			Self::deposit_event(RawEvent::Minted(lock_id, lock.recipient, lock.amount));
+
+			Ok(())
+		}
+
+		/// Burns `amount` of wrapped tokens of the sender, to be unlocked to `eth_recipient`.
+		///
+		/// The amount must not exceed what the bridge minted to the sender and it did not burn yet.
+		pub fn burn(origin, amount: T::Balance, eth_recipient: H160) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(amount > <T::Balance as As<u64>>::sa(0), "Amount must be strictly greater than zero.");
+			let bridged = Self::bridged(&sender);
+			ensure!(amount <= bridged, "Amount exceeds the wrapped tokens that the bridge minted to the sender.");
+
+			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(&sender, amount)?;
+			let _ = <balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::slash_reserved(&sender, amount);
+			<Bridged<T>>::insert(&sender, bridged - amount);
+
+			let id = Self::withdrawal_count();
+			<Withdrawals<T>>::insert(id, Withdrawal {
+				account: sender.clone(),
+				eth_recipient,
+				amount,
+				block: <system::Module<T>>::block_number(),
+			});
+			<WithdrawalCount<T>>::put(id + 1);
+
+			Self::deposit_event(RawEvent::Burned(id, sender, eth_recipient, amount));
+
+			Ok(())
+		}
+
+		pub fn add_relayer(origin, relayer: T::AccountId) -> Result {
+			ensure_root(origin)?;
+
+			let mut relayers = Self::relayers();
+			ensure!(!relayers.contains(&relayer), "Account is a relayer already.");
+			relayers.push(relayer.clone());
+			<Relayers<T>>::put(relayers);
+
+			Self::deposit_event(RawEvent::RelayerAdded(relayer));
+
+			Ok(())
+		}
+
+		pub fn remove_relayer(origin, relayer: T::AccountId) -> Result {
+			ensure_root(origin)?;
+
+			let mut relayers = Self::relayers();
+			ensure!(relayers.contains(&relayer), "Account is not a relayer.");
+			ensure!(relayers.len() as u32 > Self::threshold(), "Removing the relayer would make the quorum unreachable.");
+			relayers.retain(|r| *r != relayer);
+			<Relayers<T>>::put(relayers);
+
+			Self::deposit_event(RawEvent::RelayerRemoved(relayer));
+
+			Ok(())
+		}
+
+		pub fn set_threshold(origin, threshold: u32) -> Result {
+			ensure_root(origin)?;
+
+			ensure!(threshold > 0, "Threshold must be strictly greater than zero.");
+			ensure!(threshold as usize <= Self::relayers().len(), "Threshold must not exceed the number of relayers.");
+			<Threshold<T>>::put(threshold);
+
+			Self::deposit_event(RawEvent::ThresholdSet(threshold));
+
+			Ok(())
+		}
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where
+		<T as system::Trait>::AccountId,
+		<T as system::Trait>::Hash,
+		<T as balances::Trait>::Balance {
+		/// Relayer attested to the lock with the given hash.
+		Attested(Hash, AccountId),
+		/// Wrapped tokens were minted for the lock with the given hash.
+		Minted(Hash, AccountId, Balance),
+		/// Wrapped tokens were burned, given as withdrawal id, account, Ethereum recipient and amount.
+		Burned(u64, AccountId, H160, Balance),
+		RelayerAdded(AccountId),
+		RelayerRemoved(AccountId),
+		ThresholdSet(u32),
+	}
+);
+
+#[cfg(test)]
+mod tests {
+	use super::mock::*;
+	use super::*;
+	use crate::hopr::{Channel, ChannelBalance};
+	use primitives::H256;
+	use runtime_io::with_externalities;
+	use runtime_primitives::traits::BlakeTwo256;
+	use support::{assert_noop, assert_ok};
+
+	fn lock(recipient: &str, amount: u128) -> Lock<AccountId, u128> {
+		Lock {
+			tx_hash: H256::repeat_byte(1),
+			log_index: 0,
+			recipient: account(recipient),
+			amount,
+		}
+	}
+
+	/// Mints `amount` to `name` through the bridge, from a lock event that differs by amount.
+	fn bridge_in(name: &str, amount: u128) {
+		let lock = Lock { log_index: amount as u32, ..lock(name, amount) };
+		assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock.clone()));
+		assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer2")), lock));
+	}
+
+	#[test]
+	fn mints_after_quorum() {
+		with_externalities(&mut new_test_ext(), || {
+			let lock = lock("Alice", 100);
+
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock.clone()));
+			assert_eq!(Balances::free_balance(&account("Alice")), 0);
+
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer2")), lock.clone()));
+			assert_eq!(Balances::free_balance(&account("Alice")), 100);
+			assert_eq!(Balances::total_issuance(), 100);
+			assert!(Bridge::is_minted((lock.tx_hash, lock.log_index)));
+			assert_eq!(Bridge::bridged(&account("Alice")), 100);
+
+			let lock_id = <BridgeTest as system::Trait>::Hashing::hash_of(&lock);
+			assert_eq!(bridge_events().last(), Some(&RawEvent::Minted(lock_id, account("Alice"), 100)));
+			assert!(Bridge::attestations(lock_id).is_empty());
+		});
+	}
+
+	#[test]
+	fn rejects_invalid_attestations() {
+		with_externalities(&mut new_test_ext(), || {
+			let lock = lock("Alice", 100);
+
+			assert_noop!(Bridge::attest_lock(Origin::signed(account("Alice")), lock.clone()), "Sender is not a relayer.");
+			assert_noop!(
+				Bridge::attest_lock(Origin::signed(account("Relayer1")), Lock { amount: 0, ..lock.clone() }),
+				"Locked amount must be strictly greater than zero."
+			);
+
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock.clone()));
+			assert_noop!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock.clone()), "Relayer already attested to this lock.");
+		});
+	}
+
+	#[test]
+	fn mints_every_lock_once() {
+		with_externalities(&mut new_test_ext(), || {
+			let lock = lock("Alice", 100);
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock.clone()));
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer2")), lock.clone()));
+
+			assert_noop!(Bridge::attest_lock(Origin::signed(account("Relayer3")), lock.clone()), "Lock was already minted.");
+
+			// A different recipient for the same lock event must not be minted either.
+			let forged = Lock { recipient: account("Bob"), ..lock };
+			assert_noop!(Bridge::attest_lock(Origin::signed(account("Relayer1")), forged), "Lock was already minted.");
+		});
+	}
+
+	#[test]
+	fn conflicting_attestations_do_not_add_up() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock("Alice", 100)));
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer2")), lock("Alice", 200)));
+
+			assert_eq!(Balances::free_balance(&account("Alice")), 0);
+		});
+	}
+
+	#[test]
+	fn burn_records_withdrawal() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			bridge_in("Alice", 100);
+			let eth_recipient = H160::repeat_byte(2);
+
+			assert_ok!(Bridge::burn(Origin::signed(account("Alice")), 40, eth_recipient));
+
+			assert_eq!(Balances::free_balance(&account("Alice")), 60);
+			assert_eq!(Balances::reserved_balance(&account("Alice")), 0);
+			assert_eq!(Balances::total_issuance(), 60);
+			assert_eq!(Bridge::bridged(&account("Alice")), 60);
+			assert_eq!(Bridge::withdrawals(0), Some(Withdrawal {
+				account: account("Alice"),
+				eth_recipient,
+				amount: 40,
+				block: 1,
+			}));
+			assert_eq!(Bridge::withdrawal_count(), 1);
+			assert_eq!(bridge_events().last(), Some(&RawEvent::Burned(0, account("Alice"), eth_recipient, 40)));
+		});
+	}
+
+	#[test]
+	fn burns_only_bridged_funds() {
+		with_externalities(&mut new_test_ext(), || {
+			let eth_recipient = H160::repeat_byte(2);
+
+			// Endowed funds never came from Ethereum.
+			mint("Alice", 1000);
+			assert_noop!(
+				Bridge::burn(Origin::signed(account("Alice")), 1, eth_recipient),
+				"Amount exceeds the wrapped tokens that the bridge minted to the sender."
+			);
+
+			bridge_in("Alice", 100);
+			assert_noop!(
+				Bridge::burn(Origin::signed(account("Alice")), 101, eth_recipient),
+				"Amount exceeds the wrapped tokens that the bridge minted to the sender."
+			);
+			assert_ok!(Bridge::burn(Origin::signed(account("Alice")), 100, eth_recipient));
+			assert_noop!(
+				Bridge::burn(Origin::signed(account("Alice")), 1, eth_recipient),
+				"Amount exceeds the wrapped tokens that the bridge minted to the sender."
+			);
+			assert_eq!(Balances::free_balance(&account("Alice")), 1000);
+
+			// Wrapped tokens that were spent cannot be burned either.
+			bridge_in("Bob", 50);
+			let _ = <Balances as Currency<AccountId>>::slash(&account("Bob"), 50);
+			assert_noop!(Bridge::burn(Origin::signed(account("Bob")), 50, eth_recipient), "not enough free funds");
+		});
+	}
+
+	#[test]
+	fn minted_funds_fund_channels() {
+		with_externalities(&mut new_test_ext(), || {
+			mint("Alice", 100);
+			for name in &["Alice", "Bob"] {
+				let pubkey = H256::from_slice(account(name).as_ref());
+				assert_ok!(Hopr::init(Origin::signed(account(name)), pubkey, H256::repeat_byte(3)));
+			}
+
+			assert_ok!(Hopr::create(Origin::signed(account("Alice")), 100, account("Bob")));
+
+			let channel_id = hopr_primitives::channel_id::<BlakeTwo256, _>(&account("Alice"), &account("Bob"));
+			let balance_a = if hopr_primitives::is_party_a(&account("Alice"), &account("Bob")) { 100 } else { 0 };
+			assert_eq!(Hopr::channels(channel_id), Channel::Funded(ChannelBalance { balance: 100, balance_a }));
+			assert_eq!(Balances::reserved_balance(&account("Alice")), 100);
+		});
+	}
+
+	#[test]
+	fn root_manages_relayers() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_noop!(Bridge::add_relayer(Origin::signed(account("Relayer1")), account("Bob")), "bad origin: expected to be a root origin");
+
+			assert_ok!(Bridge::add_relayer(Origin::ROOT, account("Bob")));
+			assert_noop!(Bridge::add_relayer(Origin::ROOT, account("Bob")), "Account is a relayer already.");
+			assert_ok!(Bridge::set_threshold(Origin::ROOT, 4));
+			assert_noop!(Bridge::set_threshold(Origin::ROOT, 5), "Threshold must not exceed the number of relayers.");
+			assert_noop!(Bridge::set_threshold(Origin::ROOT, 0), "Threshold must be strictly greater than zero.");
+
+			assert_noop!(Bridge::remove_relayer(Origin::ROOT, account("Bob")), "Removing the relayer would make the quorum unreachable.");
+			assert_ok!(Bridge::set_threshold(Origin::ROOT, 2));
+			assert_ok!(Bridge::remove_relayer(Origin::ROOT, account("Bob")));
+			assert_eq!(Bridge::relayers(), vec![account("Relayer1"), account("Relayer2"), account("Relayer3")]);
+		});
+	}
+
+	#[test]
+	fn attestations_of_removed_relayers_do_not_count() {
+		with_externalities(&mut new_test_ext(), || {
+			let lock = lock("Alice", 100);
+			let lock_id = <BridgeTest as system::Trait>::Hashing::hash_of(&lock);
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer1")), lock.clone()));
+			assert_ok!(Bridge::remove_relayer(Origin::ROOT, account("Relayer1")));
+
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer2")), lock.clone()));
+			assert_eq!(Balances::free_balance(&account("Alice")), 0);
+			assert_eq!(Bridge::attestations(lock_id), vec![account("Relayer2")]);
+
+			assert_ok!(Bridge::attest_lock(Origin::signed(account("Relayer3")), lock.clone()));
+			assert_eq!(Balances::free_balance(&account("Alice")), 100);
+		});
+	}
+}
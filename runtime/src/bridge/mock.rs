@@ -0,0 +1,124 @@
+//! Mock runtime for the tests of the bridge module.
+//!
+//! It includes the hopr module, so that the tests can fund channels with minted tokens. The
+//! relayers `Relayer1` to `Relayer3` are registered with a threshold of 2 and nobody has funds.
+
+use super::*;
+
+use crate::hopr;
+use primitives::{sr25519, Blake2Hasher, Pair, H256};
+use runtime_io::TestExternalities;
+use runtime_primitives::{
+	testing::{Digest, DigestItem, Header},
+	traits::{BlakeTwo256, IdentityLookup, Verify},
+	BuildStorage,
+};
+use support::{impl_outer_event, impl_outer_origin};
+
+pub type AccountId = <sr25519::Signature as Verify>::Signer;
+
+impl_outer_origin! {
+	pub enum Origin for BridgeTest {}
+}
+
+mod bridge {
+	pub use crate::bridge::Event;
+}
+
+impl_outer_event! {
+	pub enum TestEvent for BridgeTest {
+		balances<T>,
+		hopr<T>,
+		bridge<T>,
+	}
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct BridgeTest;
+impl system::Trait for BridgeTest {
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type Digest = Digest;
+	type AccountId = AccountId;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = TestEvent;
+	type Log = DigestItem;
+}
+
+impl timestamp::Trait for BridgeTest {
+	type Moment = u64;
+	type OnTimestampSet = ();
+}
+
+impl balances::Trait for BridgeTest {
+	type Balance = u128;
+	type OnFreeBalanceZero = ();
+	type OnNewAccount = ();
+	type Event = TestEvent;
+
+	type TransactionPayment = ();
+	type DustRemoval = ();
+	type TransferPayment = ();
+}
+
+impl hopr::Trait for BridgeTest {
+	type Event = TestEvent;
+}
+
+impl Trait for BridgeTest {
+	type Event = TestEvent;
+}
+
+pub type Bridge = Module<BridgeTest>;
+pub type Hopr = hopr::Module<BridgeTest>;
+pub type Balances = balances::Module<BridgeTest>;
+pub type System = system::Module<BridgeTest>;
+
+pub fn new_test_ext() -> TestExternalities<Blake2Hasher> {
+	let mut t = system::GenesisConfig::<BridgeTest>::default().build_storage().unwrap().0;
+	t.extend(
+		balances::GenesisConfig::<BridgeTest> {
+			transaction_base_fee: 0,
+			transaction_byte_fee: 0,
+			existential_deposit: 0,
+			transfer_fee: 0,
+			creation_fee: 0,
+			balances: vec![],
+			vesting: vec![],
+		}
+		.build_storage()
+		.unwrap()
+		.0,
+	);
+	t.extend(
+		GenesisConfig::<BridgeTest> {
+			relayers: vec![account("Relayer1"), account("Relayer2"), account("Relayer3")],
+			threshold: 2,
+		}
+		.build_storage()
+		.unwrap()
+		.0,
+	);
+	t.into()
+}
+
+pub fn account(name: &str) -> AccountId {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed").public()
+}
+
+/// Mints `amount` to `name` outside of the bridge, like an endowment at genesis.
+pub fn mint(name: &str, amount: u128) {
+	let _ = <Balances as Currency<AccountId>>::deposit_creating(&account(name), amount);
+}
+
+/// The events of the bridge module that were deposited so far.
+pub fn bridge_events() -> Vec<RawEvent<AccountId, H256, u128>> {
+	System::events().into_iter().filter_map(|record| match record.event {
+		TestEvent::bridge(event) => Some(event),
+		_ => None,
+	}).collect()
+}
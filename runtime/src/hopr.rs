@@ -1,9 +1,12 @@
 use parity_codec::{Decode, Encode};
+use rstd::prelude::*;
 use primitives::{
 	sr25519::{Public, Signature},
 	H256,
 };
 use runtime_primitives::traits::{As, CheckedAdd, CheckedSub, Hash, Verify};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
 /// A runtime module template with necessary imports
 
 /// Feel free to remove or edit this file as needed.
@@ -13,51 +16,261 @@ use runtime_primitives::traits::{As, CheckedAdd, CheckedSub, Hash, Verify};
 /// For more guidance on Substrate modules, see the example module
 /// https://github.com/paritytech/substrate/blob/master/srml/example/src/lib.rs
 use support::{
-	decl_event, decl_module, decl_storage, dispatch::Result, ensure, traits::ReservableCurrency,
-	StorageMap,
+	decl_event, decl_module, decl_storage, dispatch::Result, ensure,
+	traits::{Currency, ReservableCurrency}, StorageMap, StorageValue,
 };
 
 use system::ensure_signed;
 
+pub use hopr_primitives::{challenge, Acknowledgement, Channel, ChannelBalance, HashLock, LotteryTicket};
+
 /// Length of the pending_window in seconds
-const PENDING_WINDOW: u64 = 1 * 10;
+pub const PENDING_WINDOW: u64 = 1 * 10;
+
+/// Default number of hashes that a revealed pre-image may lie below the on-chain secret.
+pub const MAX_PRE_IMAGE_DEPTH: u32 = 16;
+
+/// Upper bound of the configured `MaxPreImageDepth`, which bounds the hashes that a single ticket
+/// makes the runtime compute.
+pub const MAX_PRE_IMAGE_DEPTH_LIMIT: u32 = 64;
+
+/// Maximum number of tickets that `redeem_tickets` redeems at once.
+pub const MAX_REDEEMED_TICKETS: usize = 64;
+
+/// Maximum number of multiaddrs that a node can announce.
+pub const MAX_MULTIADDRS: usize = 8;
+
+/// Maximum length of an encoded multiaddr in bytes.
+pub const MAX_MULTIADDR_LEN: usize = 128;
+
+/// Maximum length of an encoded libp2p peer id in bytes.
+pub const MAX_PEER_ID_LEN: usize = 64;
 
-#[derive(Clone, PartialEq, Encode, Decode)]
+/// Reasons for which a call of the hopr module fails.
+///
+/// Dispatch errors of this Substrate version are plain strings that the metadata does not know
+/// about, so a call fails with the fixed message of the variant, and `HoprApi::errors` lists the
+/// code of every variant together with its message. Codes are never reused or changed.
+///
+/// The code is not observable on-chain: a failed extrinsic only deposits `ExtrinsicFailed` and the
+/// message is printed to the log of the node that imports the block. Depositing an event or storing
+/// the code on failure is not possible either, as this version keeps the writes of failed calls.
+#[derive(Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub struct ChannelBalance<Balance> {
-	balance: Balance,
-	balance_a: Balance,
+pub enum Error {
+	SameAccount = 0,
+	ZeroAmount = 1,
+	UnknownState = 2,
+	StateAlreadySet = 3,
+	SameSecret = 4,
+	InsufficientFunds = 5,
+	CounterpartyInsufficientFunds = 6,
+	ChannelDoesNotExist = 7,
+	WrongChannelState = 8,
+	ChannelExists = 9,
+	ChannelBalanceMismatch = 10,
+	InvalidSignature = 11,
+	NonceUsed = 12,
+	PendingWindowElapsed = 13,
+	PendingWindowNotElapsed = 14,
+	BalanceExceeded = 15,
+	Overflow = 16,
+	NotPreImage = 17,
+	OnChainSecretMismatch = 18,
+	UnknownDownstream = 19,
+	OwnAcknowledgement = 20,
+	AcknowledgementMismatch = 21,
+	InvalidAcknowledgementSignature = 22,
+	TicketExpired = 23,
+	NotAWin = 24,
+	NoTickets = 25,
+	TooManyTickets = 26,
+	InvalidPeerId = 27,
+	InvalidMultiaddrCount = 28,
+	InvalidMultiaddr = 29,
+	DeadlinePassed = 30,
+	WrongHashLockChannel = 31,
+	UnknownLockedPayment = 32,
+	NotPayee = 33,
+	NotPayer = 34,
+	ClaimAfterDeadline = 35,
+	RefundBeforeDeadline = 36,
+	NotHashLockPreImage = 37,
+	NotImplemented = 38,
 }
 
-#[derive(Clone, PartialEq, Encode, Decode)]
-#[cfg_attr(feature = "std", derive(Debug))]
-pub enum Channel<Balance, Moment> {
-	Uninitialized,
-	Funded(ChannelBalance<Balance>),
-	Active(ChannelBalance<Balance>),
-	PendingSettlement(ChannelBalance<Balance>, Moment),
+impl Error {
+	/// Every error, ordered by code.
+	pub const ALL: &'static [Error] = &[
+		Error::SameAccount,
+		Error::ZeroAmount,
+		Error::UnknownState,
+		Error::StateAlreadySet,
+		Error::SameSecret,
+		Error::InsufficientFunds,
+		Error::CounterpartyInsufficientFunds,
+		Error::ChannelDoesNotExist,
+		Error::WrongChannelState,
+		Error::ChannelExists,
+		Error::ChannelBalanceMismatch,
+		Error::InvalidSignature,
+		Error::NonceUsed,
+		Error::PendingWindowElapsed,
+		Error::PendingWindowNotElapsed,
+		Error::BalanceExceeded,
+		Error::Overflow,
+		Error::NotPreImage,
+		Error::OnChainSecretMismatch,
+		Error::UnknownDownstream,
+		Error::OwnAcknowledgement,
+		Error::AcknowledgementMismatch,
+		Error::InvalidAcknowledgementSignature,
+		Error::TicketExpired,
+		Error::NotAWin,
+		Error::NoTickets,
+		Error::TooManyTickets,
+		Error::InvalidPeerId,
+		Error::InvalidMultiaddrCount,
+		Error::InvalidMultiaddr,
+		Error::DeadlinePassed,
+		Error::WrongHashLockChannel,
+		Error::UnknownLockedPayment,
+		Error::NotPayee,
+		Error::NotPayer,
+		Error::ClaimAfterDeadline,
+		Error::RefundBeforeDeadline,
+		Error::NotHashLockPreImage,
+		Error::NotImplemented,
+	];
+
+	/// Stable code of the error.
+	pub fn code(self) -> u8 {
+		self as u8
+	}
+
+	/// Message that a call fails with because of this error.
+	pub fn message(self) -> &'static str {
+		match self {
+			Error::SameAccount => "Sender and counterparty must not be the same.",
+			Error::ZeroAmount => "Amount must be strictly greater than zero.",
+			Error::UnknownState => "Party must have called init() before.",
+			Error::StateAlreadySet => "State must be set at most once.",
+			Error::SameSecret => "New and old hash must not be the same.",
+			Error::InsufficientFunds => "Sender does not have enough funds.",
+			Error::CounterpartyInsufficientFunds => "Counterparty does not have enough funds.",
+			Error::ChannelDoesNotExist => "Channel does not exist.",
+			Error::WrongChannelState => "Channel state does not fit.",
+			Error::ChannelExists => "Channel must not be created twice.",
+			Error::ChannelBalanceMismatch => "Channel must be in the agreed state.",
+			Error::InvalidSignature => "Signature must be valid.",
+			Error::NonceUsed => "Nonce was already used.",
+			Error::PendingWindowElapsed => "Pending window of the channel must not have ended.",
+			Error::PendingWindowNotElapsed => "Pending window of the channel must have ended.",
+			Error::BalanceExceeded => "Funds must not exceed channel balance.",
+			Error::Overflow => "Integer error.",
+			Error::NotPreImage => "Given value is not a pre-image of the stored on-chain secret.",
+			Error::OnChainSecretMismatch => "Ticket must be issued for an on-chain secret between the stored one and the pre-image.",
+			Error::UnknownDownstream => "Downstream node must have called init() before.",
+			Error::OwnAcknowledgement => "Sender must not acknowledge its own packet.",
+			Error::AcknowledgementMismatch => "Acknowledgement must match the key half s_a.",
+			Error::InvalidAcknowledgementSignature => "Acknowledgement signature must be valid.",
+			Error::TicketExpired => "Ticket must not have expired.",
+			Error::NotAWin => "Ticket must be a win.",
+			Error::NoTickets => "At least one ticket must be given.",
+			Error::TooManyTickets => "Too many tickets.",
+			Error::InvalidPeerId => "Invalid peer id length.",
+			Error::InvalidMultiaddrCount => "Invalid number of multiaddrs.",
+			Error::InvalidMultiaddr => "Invalid multiaddr length.",
+			Error::DeadlinePassed => "Deadline of the hash lock must not have passed.",
+			Error::WrongHashLockChannel => "Hash lock must be issued for the channel.",
+			Error::UnknownLockedPayment => "Locked payment does not exist.",
+			Error::NotPayee => "Only the payee can claim a locked payment.",
+			Error::NotPayer => "Only the payer can refund a locked payment.",
+			Error::ClaimAfterDeadline => "Locked payment must be claimed before its deadline.",
+			Error::RefundBeforeDeadline => "Locked payment can only be refunded after its deadline.",
+			Error::NotHashLockPreImage => "Given value is not a pre-image of the hash lock.",
+			Error::NotImplemented => "Not implemented.",
+		}
+	}
 }
 
-impl<Balance, Moment> Default for Channel<Balance, Moment> {
-	fn default() -> Self {
-		Self::Uninitialized
+impl From<Error> for &'static str {
+	fn from(error: Error) -> &'static str {
+		error.message()
 	}
 }
 
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(deny_unknown_fields))]
 pub struct State<Hash, Public> {
 	secret: Hash,
 	pubkey: Public,
 }
 
+impl<Hash, Public> State<Hash, Public> {
+	/// State of an account with the on-chain secret `secret` that signs tickets with `pubkey`.
+	pub fn new(secret: Hash, pubkey: Public) -> Self {
+		State {
+			secret,
+			pubkey,
+		}
+	}
+
+	/// The current on-chain secret.
+	pub fn secret(&self) -> &Hash {
+		&self.secret
+	}
+
+	/// The key that tickets of the account are signed with.
+	pub fn pubkey(&self) -> &Public {
+		&self.pubkey
+	}
+}
+
+/// Where a HOPR node can be reached, as announced by its account.
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub struct LotteryTicket<Hash, Balance> {
-	challenge: Hash,
-	on_chain_secret: Hash,
-	amount: Balance,
-	win_prob: Hash,
+pub struct Announcement {
+	/// The libp2p peer id of the node in its binary encoding.
+	pub peer_id: Vec<u8>,
+	/// Multiaddrs of the node in their binary encoding.
+	pub multiaddrs: Vec<Vec<u8>>,
+}
+
+/// A ticket together with everything that is needed to redeem it, as given to `redeem_ticket`.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TicketRedemption<AccountId, Hash, Balance, BlockNumber> {
+	/// Signature of the issuer.
+	pub signature: Signature,
+	/// Issuer of the ticket.
+	pub counterparty: AccountId,
+	/// On-chain secret that the ticket was issued for.
+	pub on_chain_secret: Hash,
+	/// Pre-image that replaces the stored on-chain secret. It may lie up to `MaxPreImageDepth`
+	/// hashes below it, as long as `on_chain_secret` lies between both.
+	pub pre_image: Hash,
+	pub s_a: Hash,
+	pub acknowledgement: Acknowledgement<Hash>,
+	pub acknowledgement_signature: Signature,
+	/// Next node on the path of the packet, which signed the acknowledgement.
+	pub downstream: AccountId,
+	pub amount: Balance,
+	pub win_prob: Hash,
+	pub expiry: BlockNumber,
+}
+
+/// Conditional payment that is locked in a channel until the payee claims it or the payer gets
+/// it refunded.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct LockedPayment<AccountId, Hash, Balance, BlockNumber> {
+	pub payer: AccountId,
+	pub payee: AccountId,
+	pub hash_lock: Hash,
+	pub amount: Balance,
+	pub deadline: BlockNumber,
 }
 
 // #[derive(Encode, Decode, Default, Clone, PartialEq)]
@@ -69,6 +282,12 @@ pub struct LotteryTicket<Hash, Balance> {
 
 pub type ChannelId<T> = <T as system::Trait>::Hash;
 pub type PreImage<T> = <T as system::Trait>::Hash;
+pub type Redemption<T> = TicketRedemption<
+	<T as system::Trait>::AccountId,
+	<T as system::Trait>::Hash,
+	<T as balances::Trait>::Balance,
+	<T as system::Trait>::BlockNumber,
+>;
 
 /// The module's configuration trait.
 pub trait Trait: system::Trait + timestamp::Trait + balances::Trait {
@@ -79,9 +298,27 @@ pub trait Trait: system::Trait + timestamp::Trait + balances::Trait {
 decl_storage! {
 	trait Store for Module<T: Trait> as hopr {
 		Channels get(channels): map ChannelId<T> => Channel<T::Balance, T::Moment>;
-		States get(state): map T::AccountId => State<T::Hash, Public>;
+		/// Ids of the channels that are not withdrawn yet, in the order they were created.
+		ChannelIds get(channel_ids): Vec<ChannelId<T>>;
+		States get(state) build(|config: &GenesisConfig<T>| {
+			config.states.iter().map(|(account, pubkey, secret)| {
+				(account.clone(), State::new(secret.clone(), Public::from_raw(*pubkey.as_fixed_bytes())))
+			}).collect::<Vec<_>>()
+		}): map T::AccountId => State<T::Hash, Public>;
 		Nonces get(nonce_exists): map T::Hash => bool;
-		PendingWindow get(pending_window): u64 = PENDING_WINDOW;
+		PendingWindow get(pending_window) config(): u64 = PENDING_WINDOW;
+		/// How many hashes a pre-image that redeems a ticket may lie below the on-chain secret, read
+		/// through `max_pre_image_depth` which caps it at `MAX_PRE_IMAGE_DEPTH_LIMIT`.
+		MaxPreImageDepth get(configured_pre_image_depth) config(max_pre_image_depth): u32 = MAX_PRE_IMAGE_DEPTH;
+		Announcements get(announcement): map T::AccountId => Option<Announcement>;
+		/// Accounts that announced their node, in the order of their first announcement.
+		AnnouncedNodes get(announced_nodes): Vec<T::AccountId>;
+		/// Conditional payments by the hash of the signature of the payer.
+		LockedPayments get(locked_payment): map T::Hash => Option<LockedPayment<T::AccountId, T::Hash, T::Balance, T::BlockNumber>>;
+	}
+	add_extra_genesis {
+		/// Accounts that are registered at genesis, given as `(account, pubkey, on-chain secret)`.
+		config(states): Vec<(T::AccountId, H256, T::Hash)>;
 	}
 }
 
@@ -91,6 +328,7 @@ decl_storage! {
 decl_module! {
 	/// Module that process Hopr payments
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		// Calls are encoded by their position, so new calls go to the end and bump `spec_version`.
 		fn deposit_event<T>() = default;
 
 		/// Initialises a payment channel between two parties.
@@ -98,13 +336,13 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
-			ensure!(funds > <T::Balance as As<u64>>::sa(0), "Funds must be strictly greater than zero.");
+			ensure!(sender != counterparty, Error::SameAccount.into());
+			ensure!(funds > <T::Balance as As<u64>>::sa(0), Error::ZeroAmount.into());
 
-			ensure!(<States<T>>::exists(&sender), "Party must have called init() before.");
-			ensure!(<States<T>>::exists(&counterparty), "Party must have called init() before.");
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+			ensure!(<States<T>>::exists(&counterparty), Error::UnknownState.into());
 
-			ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(&sender, funds), "User has not enough funds.");
+			ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(&sender, funds), Error::InsufficientFunds.into());
 
 			let channel_id = Self::get_id(&sender, &counterparty);
 
@@ -125,20 +363,23 @@ decl_module! {
 				Channel::Funded(channel_balance) => {
 					if Self::is_party_a(&sender, &counterparty) {
 						ChannelBalance {
-							balance: channel_balance.balance.checked_add(&funds).ok_or("integer error")?,
-							balance_a: channel_balance.balance_a.checked_add(&funds).ok_or("integer error")?,
+							balance: channel_balance.balance.checked_add(&funds).ok_or(Error::Overflow)?,
+							balance_a: channel_balance.balance_a.checked_add(&funds).ok_or(Error::Overflow)?,
 						}
 					} else {
 						ChannelBalance {
-							balance: channel_balance.balance.checked_add(&funds).ok_or("integer error")?,
+							balance: channel_balance.balance.checked_add(&funds).ok_or(Error::Overflow)?,
 							balance_a: channel_balance.balance_a,
 						}
 					}
 				},
-				_ => return Err("Channel must not be created twice."),
+				_ => return Err(Error::ChannelExists.into()),
 			};
 
 			// ==== State change ================================
+			if !<Channels<T>>::exists(&channel_id) {
+				<ChannelIds<T>>::mutate(|ids| ids.push(channel_id));
+			}
 			<Channels<T>>::insert(channel_id, Channel::Funded(channel_balance.clone()));
 			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(&sender, funds)?;
 
@@ -152,23 +393,23 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
+			ensure!(sender != counterparty, Error::SameAccount.into());
 
-			ensure!(<States<T>>::exists(&sender), "Party must have called init() before.");
-			ensure!(<States<T>>::exists(&counterparty), "Party must have called init() before.");
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+			ensure!(<States<T>>::exists(&counterparty), Error::UnknownState.into());
 
 			let channel_id = Self::get_id(&sender, &counterparty);
-			ensure!(<Channels<T>>::exists(&channel_id), "Channel does not exist and/or its state does not fit.");
+			ensure!(<Channels<T>>::exists(&channel_id), Error::ChannelDoesNotExist.into());
 
 			let channel_balance = match Self::channels(&channel_id) {
 				Channel::Funded(channel_balance) => channel_balance,
-				_ => return Err("Channel does not exist and/or its state does not fit.")
+				_ => return Err(Error::WrongChannelState.into()),
 			};
 
 			let counterparty_pubkey = Self::state(counterparty).pubkey;
 			// println!("{:?}", counterparty_pubkey);
 			// println!("{:?}", (Channel::Funded(channel_balance.clone()) as Channel<T::Balance, T::Moment>).encode().as_slice());
-			ensure!(Signature::verify(&signature, (Channel::Funded(channel_balance.clone()) as Channel<T::Balance, T::Moment>).encode().as_slice(), &counterparty_pubkey), "Invalid signature.");
+			ensure!(Signature::verify(&signature, hopr_primitives::funding_message(&channel_balance).as_slice(), &counterparty_pubkey), Error::InvalidSignature.into());
 
 			// ==== State change ================================
 			Self::test_and_set_nonce(<T as system::Trait>::Hashing::hash(signature.as_ref()))?;
@@ -186,13 +427,13 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
-			ensure!(funds > <T::Balance as As<u64>>::sa(0), "Funds must be strictly greater than zero.");
+			ensure!(sender != counterparty, Error::SameAccount.into());
+			ensure!(funds > <T::Balance as As<u64>>::sa(0), Error::ZeroAmount.into());
 
-			ensure!(<States<T>>::exists(&counterparty), "We do not know the public key of the counterparty.");
+			ensure!(<States<T>>::exists(&counterparty), Error::UnknownState.into());
 
 			let channel_balance: ChannelBalance<T::Balance> = ChannelBalance {
-				balance: funds.checked_add(&funds).ok_or("integer error")?,
+				balance: funds.checked_add(&funds).ok_or(Error::Overflow)?,
 				balance_a: funds,
 			};
 
@@ -200,12 +441,12 @@ decl_module! {
 
 			let channel_id: ChannelId<T> = Self::get_id(&counterparty, &sender);
 
-			ensure!(!<Channels<T>>::exists(&channel_id), "Channel must not exist.");
+			ensure!(!<Channels<T>>::exists(&channel_id), Error::ChannelExists.into());
 
-			ensure!(Signature::verify(&signature, (Channel::Funded(channel_balance.clone()) as Channel<T::Balance, T::Moment>).encode().as_slice(), &counterparty_pubkey), "Signature must be valid.");
+			ensure!(Signature::verify(&signature, hopr_primitives::funding_message(&channel_balance).as_slice(), &counterparty_pubkey), Error::InvalidSignature.into());
 
-			ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(&sender, funds), "User does have not enough funds.");
-			ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(&counterparty, funds), "Counterparty does not have enough funds.");
+			ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(&sender, funds), Error::InsufficientFunds.into());
+			ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(&counterparty, funds), Error::CounterpartyInsufficientFunds.into());
 
 			// ==== State change ================================
 			Self::test_and_set_nonce(<T as system::Trait>::Hashing::hash(signature.as_ref()))?;
@@ -213,6 +454,7 @@ decl_module! {
 			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(&sender, funds)?;
 			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(&counterparty, funds)?;
 
+			<ChannelIds<T>>::mutate(|ids| ids.push(channel_id));
 			<Channels<T>>::insert(channel_id, Channel::Active(channel_balance.clone()));
 
 			Self::deposit_event(RawEvent::Opened(channel_id, channel_balance.balance, channel_balance.balance_a));
@@ -225,9 +467,9 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(<States<T>>::exists(&sender), "Call init() before setting a new on-chain secret.");
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
 
-			ensure!(Self::state(&sender).secret != hash, "New and old hash must not be the same.");
+			ensure!(Self::state(&sender).secret != hash, Error::SameSecret.into());
 
 			// ==== State change ================================
 			<States<T>>::mutate(&sender, |state| {
@@ -242,96 +484,42 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(!<States<T>>::exists(&sender), "State must be set at most once.");
+			ensure!(!<States<T>>::exists(&sender), Error::StateAlreadySet.into());
 
 			// ==== State change ================================
-			<States<T>>::insert(&sender, State {
-				pubkey: Public::from_raw(*pubkey.as_fixed_bytes()),
-				secret: hash,
-			});
+			<States<T>>::insert(&sender, State::new(hash, Public::from_raw(*pubkey.as_fixed_bytes())));
 
 			Ok(())
 		}
 
-		/// Redeems a previously issued ticket.
-		pub fn redeem_ticket(origin, signature: Signature, counterparty: T::AccountId, pre_image: PreImage<T>, s_a: PreImage<T>, s_b: PreImage<T>, amount: T::Balance, win_prob: T::Hash) -> Result {
-			// ==== Verification ================================
+		/// Redeems a previously issued ticket until the end of the block `expiry`.
+		///
+		/// The ticket must be issued for an `on_chain_secret` on the hash chain between the stored
+		/// on-chain secret and `pre_image`, which then becomes the new on-chain secret. This way,
+		/// tickets of lost pre-images can be skipped.
+		///
+		/// The key half `s_b` is taken from the `acknowledgement` that `downstream`, the next node
+		/// on the path of the packet, signed with `acknowledgement_signature`.
+		pub fn redeem_ticket(origin, signature: Signature, counterparty: T::AccountId, on_chain_secret: T::Hash, pre_image: PreImage<T>, s_a: PreImage<T>, acknowledgement: Acknowledgement<T::Hash>, acknowledgement_signature: Signature, downstream: T::AccountId, amount: T::Balance, win_prob: T::Hash, expiry: T::BlockNumber) -> Result {
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
-
-			ensure!(<States<T>>::exists(&sender), "Party must have called init() before.");
-			ensure!(<States<T>>::exists(&counterparty), "Party must have called init() before.");
-
-			ensure!(amount > <T::Balance as As<u64>>::sa(0), "Amount must be strictly greater than zero.");
-
-			let on_chain_secret = Self::state(&sender).secret;
-			ensure!(<T as system::Trait>::Hashing::hash(pre_image.as_ref()) == on_chain_secret, "Given value is not a pre-image of the stored on-chain secret");
-
-			let counterparty_pubkey = Self::state(&counterparty).pubkey;
-
-			let channel_id = Self::get_id(&sender, &counterparty);
-
-			let mut channel_balance = match Self::channels(channel_id) {
-				Channel::PendingSettlement(_, timestamp) if timestamp::Module::<T>::now() > timestamp => return Err("Ticket redemption must have happened before end of pending window."),
-				Channel::PendingSettlement(channel_balance, _) |  Channel::Active(channel_balance) => channel_balance,
-				_ => return Err("Channel does not exist and/or its state does not fit."),
-			};
-
-			if Self::is_party_a(&sender, &counterparty) {
-				ensure!(channel_balance.balance_a.checked_add(&amount).ok_or("Integer error.")? <= channel_balance.balance, "Transferred funds must not exceed channel balance.");
-			} else {
-				ensure!(channel_balance.balance_a.checked_sub(&amount).ok_or("Integer error.")? >= <<T as balances::Trait>::Balance as As<u64>>::sa(0), "Transferred funds must not exceed channel balance.");
-			}
-
-			let hashed_s_a = <T as system::Trait>::Hashing::hash(s_a.as_ref());
-			let hashed_s_b = <T as system::Trait>::Hashing::hash(s_b.as_ref());
-
-			let challenge = (hashed_s_a, hashed_s_b).using_encoded(<T as system::Trait>::Hashing::hash);
-
-			let ticket: LotteryTicket<T::Hash, T::Balance> = LotteryTicket {
-				challenge,
+			Self::redeem(sender, &[TicketRedemption {
+				signature,
+				counterparty,
 				on_chain_secret,
+				pre_image,
+				s_a,
+				acknowledgement,
+				acknowledgement_signature,
+				downstream,
 				amount,
-				win_prob
-			};
-			let hashed_ticket = ticket.using_encoded(<T as system::Trait>::Hashing::hash);
-
-			ensure!(Self::cmp_hash(&hashed_ticket, &win_prob), "Ticket must be a win.");
-
-			ensure!(Verify::verify(&signature, ticket.encode().as_slice(), &counterparty_pubkey), "Signature must be valid.");
-
-			// ==== Prepare state change=========================
-			if Self::is_party_a(&sender, &counterparty) {
-				channel_balance.balance_a = channel_balance.balance_a.checked_add(&amount).ok_or("Integer error.")?;
-			} else {
-				channel_balance.balance_a = channel_balance.balance_a.checked_sub(&amount).ok_or("Integer error.")?;
-			}
-
-			// ==== State change ================================
-			Self::test_and_set_nonce(<T as system::Trait>::Hashing::hash(signature.as_ref()))?;
-
-			<States<T>>::mutate(&sender, |state| {
-				state.secret = pre_image;
-			});
-
-			<Channels<T>>::mutate(&channel_id, |channel| {
-				*channel = match channel {
-					Channel::Active(_) => Channel::Active(channel_balance),
-					Channel::PendingSettlement(_, timestamp) => {
-						Self::deposit_event(RawEvent::PushedBackSettlement(channel_id, channel_balance.balance_a));
-						Channel::PendingSettlement(channel_balance, timestamp.clone())
-					},
-					_ => return Err("Channel does not exist and/or its state does not fit."),
-				};
-				Ok(())
-			})?;
-
-			Ok(())
+				win_prob,
+				expiry,
+			}])
 		}
 
 		pub fn redeem_aggregated_tickets() -> Result {
-			Err("Not implemented")
+			Err(Error::NotImplemented.into())
 		}
 
 		/// Restores the channel to a previously agreed backup state.
@@ -339,27 +527,28 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
+			ensure!(sender != counterparty, Error::SameAccount.into());
 
-			ensure!(<States<T>>::exists(&sender), "Party must have called init() before.");
-			ensure!(<States<T>>::exists(&counterparty), "Party must have called init() before.");
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+			ensure!(<States<T>>::exists(&counterparty), Error::UnknownState.into());
 
 			let channel_id = Self::get_id(&sender, &counterparty);
 
 			let channel_balance = match Self::channels(channel_id) {
 				Channel::Active(channel_balance) | Channel::Funded(channel_balance) => channel_balance,
-				_ => return Err("Channel does not exist and/or its state does not fit."),
+				Channel::Uninitialized => return Err(Error::ChannelDoesNotExist.into()),
+				_ => return Err(Error::WrongChannelState.into()),
 			};
 
-			ensure!(channel_balance == claimed_channel_balance, "Channel must be in the agreed state.");
+			ensure!(channel_balance == claimed_channel_balance, Error::ChannelBalanceMismatch.into());
 
 			let counterparty_pubkey = Self::state(&counterparty).pubkey;
 
-			let message = ("restore_transaction", channel_id, claimed_channel_balance).encode();
-			ensure!(Signature::verify(&signature, message.as_slice(), &counterparty_pubkey), "Signature must be valid.");
+			let message = hopr_primitives::recovery_message(&channel_id, &claimed_channel_balance);
+			ensure!(Signature::verify(&signature, message.as_slice(), &counterparty_pubkey), Error::InvalidSignature.into());
 
 			// ==== State change ================================
-			let end_of_pending_window = timestamp::Module::<T>::now().checked_add(&<T::Moment as As<u64>>::sa(Self::pending_window())).ok_or("Integer error")?;
+			let end_of_pending_window = timestamp::Module::<T>::now().checked_add(&<T::Moment as As<u64>>::sa(Self::pending_window())).ok_or(Error::Overflow)?;
 			<Channels<T>>::mutate(&channel_id, |channel| {
 				*channel = Channel::PendingSettlement(channel_balance.clone(), end_of_pending_window);
 			});
@@ -373,17 +562,18 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
+			ensure!(sender != counterparty, Error::SameAccount.into());
 
 			let channel_id = Self::get_id(&sender, &counterparty);
 
 			let channel_balance = match Self::channels(channel_id) {
 				Channel::Active(channel_balance) => channel_balance,
-				_ => return Err("Channel does not exist and/or its state does not fit."),
+				Channel::Uninitialized => return Err(Error::ChannelDoesNotExist.into()),
+				_ => return Err(Error::WrongChannelState.into()),
 			};
 
 			// ==== State change ================================
-			let end_of_pending_window = timestamp::Module::<T>::now().checked_add(&<T::Moment as As<u64>>::sa(Self::pending_window())).ok_or("Integer error")?;
+			let end_of_pending_window = timestamp::Module::<T>::now().checked_add(&<T::Moment as As<u64>>::sa(Self::pending_window())).ok_or(Error::Overflow)?;
 			<Channels<T>>::insert(channel_id, Channel::PendingSettlement(channel_balance.clone(), end_of_pending_window));
 
 			Self::deposit_event(RawEvent::InitiatedSettlement(channel_id, channel_balance.balance_a));
@@ -395,33 +585,176 @@ decl_module! {
 			// ==== Verification ================================
 			let sender = ensure_signed(origin)?;
 
-			ensure!(sender != counterparty, "Sender and counterparty must not be the same.");
+			ensure!(sender != counterparty, Error::SameAccount.into());
 
-			ensure!(<States<T>>::exists(&sender), "Party must have called init() before.");
-			ensure!(<States<T>>::exists(&counterparty), "Party must have called init() before.");
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+			ensure!(<States<T>>::exists(&counterparty), Error::UnknownState.into());
 
 			let channel_id = Self::get_id(&sender, &counterparty);
 			let channel = Self::channels(&channel_id);
 
 			let channel_balance = match channel {
-				Channel::PendingSettlement(_, timestamp) if timestamp::Module::<T>::now() < timestamp => return Err("Channel is not yet recoverable."),
+				Channel::PendingSettlement(_, timestamp) if timestamp::Module::<T>::now() < timestamp => return Err(Error::PendingWindowNotElapsed.into()),
 				Channel::PendingSettlement(channel_balance, _) => channel_balance,
-				_ => return Err("Channel does not exist and/or its state does not fit."),
+				Channel::Uninitialized => return Err(Error::ChannelDoesNotExist.into()),
+				_ => return Err(Error::WrongChannelState.into()),
 			};
+			let balance_b = channel_balance.balance_b().ok_or(Error::Overflow)?;
 
 			// ==== State change ================================
 			if Self::is_party_a(&sender, &counterparty) {
 				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&sender, channel_balance.balance_a);
-				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&counterparty, channel_balance.balance.checked_sub(&channel_balance.balance_a).ok_or("Integer error")?);
+				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&counterparty, balance_b);
 			} else {
-				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&sender, channel_balance.balance.checked_sub(&channel_balance.balance_a).ok_or("Integer error")?);
+				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&sender, balance_b);
 				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&counterparty, channel_balance.balance_a);
 			}
 
 			<Channels<T>>::remove(channel_id);
+			<ChannelIds<T>>::mutate(|ids| ids.retain(|id| *id != channel_id));
+
+			Self::deposit_event(RawEvent::Withdrawn(channel_id, channel_balance.balance, channel_balance.balance_a));
+
+			Ok(())
+		}
+
+		/// Announces the peer id and multiaddrs of the node of the sender, replacing its previous
+		/// announcement.
+		pub fn announce(origin, peer_id: Vec<u8>, multiaddrs: Vec<Vec<u8>>) -> Result {
+			// ==== Verification ================================
+			let sender = ensure_signed(origin)?;
+
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+			ensure!(!peer_id.is_empty() && peer_id.len() <= MAX_PEER_ID_LEN, Error::InvalidPeerId.into());
+			ensure!(!multiaddrs.is_empty() && multiaddrs.len() <= MAX_MULTIADDRS, Error::InvalidMultiaddrCount.into());
+			ensure!(multiaddrs.iter().all(|addr| !addr.is_empty() && addr.len() <= MAX_MULTIADDR_LEN), Error::InvalidMultiaddr.into());
+
+			// ==== State change ================================
+			if !<Announcements<T>>::exists(&sender) {
+				<AnnouncedNodes<T>>::mutate(|nodes| nodes.push(sender.clone()));
+			}
+			<Announcements<T>>::insert(&sender, Announcement { peer_id, multiaddrs });
+
+			Self::deposit_event(RawEvent::Announced(sender));
+
+			Ok(())
+		}
+
+		/// Locks the conditional payment that `payer` signed for the sender in their channel.
+		///
+		/// The amount is taken from the part of the payer until the payment is claimed or refunded.
+		pub fn lock_payment(origin, payer: T::AccountId, lock: HashLock<T::Hash, T::Balance, T::BlockNumber>, signature: Signature) -> Result {
+			// ==== Verification ================================
+			let sender = ensure_signed(origin)?;
+
+			ensure!(sender != payer, Error::SameAccount.into());
+
+			ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+			ensure!(<States<T>>::exists(&payer), Error::UnknownState.into());
+
+			ensure!(lock.amount > <T::Balance as As<u64>>::sa(0), Error::ZeroAmount.into());
+			ensure!(lock.deadline >= <system::Module<T>>::block_number(), Error::DeadlinePassed.into());
+
+			let channel_id = Self::get_id(&sender, &payer);
+			ensure!(lock.channel_id == channel_id, Error::WrongHashLockChannel.into());
+
+			let mut channel_balance = match Self::channels(channel_id) {
+				Channel::PendingSettlement(_, timestamp) if timestamp::Module::<T>::now() > timestamp => return Err(Error::PendingWindowElapsed.into()),
+				Channel::PendingSettlement(channel_balance, _) | Channel::Active(channel_balance) => channel_balance,
+				Channel::Uninitialized => return Err(Error::ChannelDoesNotExist.into()),
+				_ => return Err(Error::WrongChannelState.into()),
+			};
+
+			ensure!(Signature::verify(&signature, hopr_primitives::hash_lock_message(&lock).as_slice(), &Self::state(&payer).pubkey), Error::InvalidSignature.into());
+
+			// ==== Prepare state change=========================
+			// The funds stay reserved by the payer, but no longer belong to the channel.
+			channel_balance.balance = channel_balance.balance.checked_sub(&lock.amount).ok_or(Error::Overflow)?;
+			if Self::is_party_a(&payer, &sender) {
+				ensure!(channel_balance.balance_a >= lock.amount, Error::BalanceExceeded.into());
+				channel_balance.balance_a = channel_balance.balance_a - lock.amount;
+			} else {
+				ensure!(channel_balance.balance_a <= channel_balance.balance, Error::BalanceExceeded.into());
+			}
+
+			// ==== State change ================================
+			let lock_id = <T as system::Trait>::Hashing::hash(signature.as_ref());
+			Self::test_and_set_nonce(lock_id)?;
+
+			Self::update_channel_balance(&channel_id, channel_balance)?;
+			<LockedPayments<T>>::insert(lock_id, LockedPayment {
+				payer,
+				payee: sender,
+				hash_lock: lock.hash_lock,
+				amount: lock.amount,
+				deadline: lock.deadline,
+			});
+
+			Self::deposit_event(RawEvent::LockedPayment(channel_id, lock_id, lock.amount));
+
+			Ok(())
+		}
+
+		/// Claims a locked payment by revealing the pre-image of its hash lock.
+		pub fn claim_payment(origin, lock_id: T::Hash, pre_image: T::Hash) -> Result {
+			// ==== Verification ================================
+			let sender = ensure_signed(origin)?;
+
+			let payment = Self::locked_payment(lock_id).ok_or(Error::UnknownLockedPayment)?;
+			ensure!(sender == payment.payee, Error::NotPayee.into());
+			ensure!(<system::Module<T>>::block_number() <= payment.deadline, Error::ClaimAfterDeadline.into());
+			ensure!(<T as system::Trait>::Hashing::hash(pre_image.as_ref()) == payment.hash_lock, Error::NotHashLockPreImage.into());
+
+			// ==== Prepare state change=========================
+			Self::ensure_can_move_reserved(&payment.payer, &payment.payee, payment.amount)?;
+			let channel_balance = Self::released_channel_balance(&payment.payee, &payment.payer, payment.amount)?;
+
+			// ==== State change ================================
+			<LockedPayments<T>>::remove(lock_id);
+
+			Self::move_reserved(&payment.payer, &payment.payee, payment.amount)?;
+			Self::release_locked(&payment.payee, &payment.payer, payment.amount, channel_balance)?;
+
+			Self::deposit_event(RawEvent::ClaimedPayment(lock_id, pre_image));
+
+			Ok(())
+		}
+
+		/// Returns a locked payment to the payer once its deadline has passed.
+		pub fn refund_payment(origin, lock_id: T::Hash) -> Result {
+			// ==== Verification ================================
+			let sender = ensure_signed(origin)?;
+
+			let payment = Self::locked_payment(lock_id).ok_or(Error::UnknownLockedPayment)?;
+			ensure!(sender == payment.payer, Error::NotPayer.into());
+			ensure!(<system::Module<T>>::block_number() > payment.deadline, Error::RefundBeforeDeadline.into());
+
+			// ==== Prepare state change=========================
+			let channel_balance = Self::released_channel_balance(&payment.payer, &payment.payee, payment.amount)?;
+
+			// ==== State change ================================
+			<LockedPayments<T>>::remove(lock_id);
+
+			Self::release_locked(&payment.payer, &payment.payee, payment.amount, channel_balance)?;
+
+			Self::deposit_event(RawEvent::RefundedPayment(lock_id));
 
 			Ok(())
 		}
+
+		/// Redeems several tickets of one or more channels at once.
+		///
+		/// Every ticket must be issued for an on-chain secret between the one that the previous
+		/// tickets leave behind and its own pre-image, so the pre-images walk down the hash chain of
+		/// the secret. Either all tickets are redeemed or none.
+		pub fn redeem_tickets(origin, tickets: Vec<Redemption<T>>) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(!tickets.is_empty(), Error::NoTickets.into());
+			ensure!(tickets.len() <= MAX_REDEEMED_TICKETS, Error::TooManyTickets.into());
+
+			Self::redeem(sender, &tickets)
+		}
 	}
 }
 
@@ -440,151 +773,316 @@ decl_event!(
 		PushedBackSettlement(Hash, Balance),
 		/// Payment channel opened by third party.
 		OpenedFor(AccountId, AccountId, Balance, Balance),
+		/// Ticket was redeemed.
+		RedeemedTicket(Hash, Balance),
+		/// Channel was settled and its balance paid out, given as balance and balance_a.
+		Withdrawn(Hash, Balance, Balance),
+		/// Node of the account announced where it can be reached.
+		Announced(AccountId),
+		/// Conditional payment was locked in the channel, given as channel id, lock id and amount.
+		LockedPayment(Hash, Hash, Balance),
+		/// Locked payment was claimed with the given pre-image of its hash lock.
+		ClaimedPayment(Hash, Hash),
+		/// Locked payment was returned to the payer.
+		RefundedPayment(Hash),
 	}
 );
 
 impl<T: Trait> Module<T> {
-	fn test_and_set_nonce(nonce: T::Hash) -> Result {
-		ensure!(!<Nonces<T>>::exists(nonce), "Nonce was already used.");
-		<Nonces<T>>::insert(nonce, true);
-		Ok(())
+	/// How many hashes a pre-image that redeems a ticket may lie below the on-chain secret.
+	pub fn max_pre_image_depth() -> u32 {
+		Self::configured_pre_image_depth().min(MAX_PRE_IMAGE_DEPTH_LIMIT)
 	}
 
-	fn is_party_a(a: &T::AccountId, b: &T::AccountId) -> bool {
-		a < b
+	/// The on-chain secret of `account`, or `None` if it has not called `init()`.
+	pub fn on_chain_secret(account: &T::AccountId) -> Option<T::Hash> {
+		if <States<T>>::exists(account) {
+			Some(Self::state(account).secret)
+		} else {
+			None
+		}
 	}
 
-	/// Give the payment channels a meaningful ID that is the same for both
-	/// parties
-	fn get_id(a: &T::AccountId, b: &T::AccountId) -> ChannelId<T> {
-		if Self::is_party_a(&a, &b) {
-			(a, b).using_encoded(<T as system::Trait>::Hashing::hash)
-		} else {
-			(b, a).using_encoded(<T as system::Trait>::Hashing::hash)
+	/// Nodes that announced where they can be reached, in the order of their first announcement.
+	pub fn announcements() -> Vec<(T::AccountId, Announcement)> {
+		Self::announced_nodes().into_iter()
+			.filter_map(|account| Self::announcement(&account).map(|announcement| (account, announcement)))
+			.collect()
+	}
+
+	/// Channels that are not withdrawn yet, in the order they were created.
+	pub fn all_channels() -> Vec<(ChannelId<T>, Channel<T::Balance, T::Moment>)> {
+		Self::channel_ids().into_iter()
+			.map(|channel_id| (channel_id, Self::channels(channel_id)))
+			.collect()
+	}
+
+	/// Key under which the channel with the given id is stored, e.g. to request a read proof.
+	pub fn channel_storage_key(channel_id: &ChannelId<T>) -> Vec<u8> {
+		runtime_io::twox_128(&<Channels<T>>::key_for(channel_id)).to_vec()
+	}
+
+	/// Key under which the state of `account` is stored, e.g. to request a read proof.
+	pub fn state_storage_key(account: &T::AccountId) -> Vec<u8> {
+		runtime_io::twox_128(&<States<T>>::key_for(account)).to_vec()
+	}
+
+	/// Redeems `redemptions` of `sender` in the given order.
+	///
+	/// All tickets are checked before any state changes, with the signatures last, so that a
+	/// single invalid ticket rejects the whole batch.
+	fn redeem(sender: T::AccountId, redemptions: &[Redemption<T>]) -> Result {
+		// ==== Verification ================================
+		ensure!(<States<T>>::exists(&sender), Error::UnknownState.into());
+
+		let mut on_chain_secret = Self::state(&sender).secret;
+		// Channels of the tickets with their new balance, the end of their pending window if they are
+		// pending settlement and the amount that the sender receives.
+		let mut channels: Vec<(T::AccountId, ChannelId<T>, ChannelBalance<T::Balance>, Option<T::Moment>, T::Balance)> = Vec::new();
+		let mut nonces = Vec::with_capacity(redemptions.len());
+		let mut signatures = Vec::with_capacity(2 * redemptions.len());
+
+		for redemption in redemptions {
+			let counterparty = &redemption.counterparty;
+			let amount = redemption.amount;
+
+			ensure!(sender != *counterparty, Error::SameAccount.into());
+			ensure!(<States<T>>::exists(counterparty), Error::UnknownState.into());
+
+			ensure!(amount > <T::Balance as As<u64>>::sa(0), Error::ZeroAmount.into());
+
+			let issued_in_between = hopr_primitives::hash_chain_contains::<T::Hashing>(&redemption.pre_image, &on_chain_secret, &redemption.on_chain_secret, Self::max_pre_image_depth())
+				.ok_or(Error::NotPreImage)?;
+			ensure!(issued_in_between, Error::OnChainSecretMismatch.into());
+
+			let channel_id = Self::get_id(&sender, counterparty);
+			let index = match channels.iter().position(|(_, id, _, _, _)| *id == channel_id) {
+				Some(index) => index,
+				None => {
+					let (channel_balance, pending_until) = match Self::channels(channel_id) {
+						Channel::PendingSettlement(_, timestamp) if timestamp::Module::<T>::now() > timestamp => return Err(Error::PendingWindowElapsed.into()),
+						Channel::PendingSettlement(channel_balance, timestamp) => (channel_balance, Some(timestamp)),
+						Channel::Active(channel_balance) => (channel_balance, None),
+						Channel::Uninitialized => return Err(Error::ChannelDoesNotExist.into()),
+						_ => return Err(Error::WrongChannelState.into()),
+					};
+					channels.push((counterparty.clone(), channel_id, channel_balance, pending_until, <T::Balance as As<u64>>::sa(0)));
+					channels.len() - 1
+				},
+			};
+			let (_, _, channel_balance, _, received) = &mut channels[index];
+
+			if Self::is_party_a(&sender, counterparty) {
+				ensure!(channel_balance.balance_a.checked_add(&amount).ok_or(Error::Overflow)? <= channel_balance.balance, Error::BalanceExceeded.into());
+				channel_balance.balance_a = channel_balance.balance_a.checked_add(&amount).ok_or(Error::Overflow)?;
+			} else {
+				ensure!(channel_balance.balance_a.checked_sub(&amount).ok_or(Error::Overflow)? >= <<T as balances::Trait>::Balance as As<u64>>::sa(0), Error::BalanceExceeded.into());
+				channel_balance.balance_a = channel_balance.balance_a.checked_sub(&amount).ok_or(Error::Overflow)?;
+			}
+			*received = received.checked_add(&amount).ok_or(Error::Overflow)?;
+
+			let downstream = &redemption.downstream;
+			let acknowledgement = &redemption.acknowledgement;
+			ensure!(sender != *downstream, Error::OwnAcknowledgement.into());
+			ensure!(<States<T>>::exists(downstream), Error::UnknownDownstream.into());
+			ensure!(acknowledgement.matches::<T::Hashing>(redemption.s_a.as_ref()), Error::AcknowledgementMismatch.into());
+
+			// The ticket commits to the public key of `downstream`, so the signature of the counterparty
+			// only verifies if the acknowledgement comes from the node that it forwarded the packet to.
+			let downstream_pubkey = Self::state(downstream).pubkey;
+			let next_hop = hopr_primitives::next_hop::<T::Hashing>(downstream_pubkey.as_ref());
+			let ticket: LotteryTicket<T::Hash, T::Balance, T::BlockNumber> = LotteryTicket::new(*acknowledgement.challenge(), next_hop, redemption.on_chain_secret, amount, redemption.win_prob, redemption.expiry);
+
+			ensure!(!ticket.is_expired(&<system::Module<T>>::block_number()), Error::TicketExpired.into());
+			ensure!(ticket.is_win::<T::Hashing>(), Error::NotAWin.into());
+
+			let nonce = <T as system::Trait>::Hashing::hash(redemption.signature.as_ref());
+			ensure!(!<Nonces<T>>::exists(nonce) && !nonces.contains(&nonce), Error::NonceUsed.into());
+			nonces.push(nonce);
+
+			signatures.push((
+				&redemption.acknowledgement_signature,
+				hopr_primitives::acknowledgement_message(acknowledgement),
+				downstream_pubkey,
+				Error::InvalidAcknowledgementSignature,
+			));
+			signatures.push((&redemption.signature, ticket.encode(), Self::state(counterparty).pubkey, Error::InvalidSignature));
+
+			on_chain_secret = redemption.pre_image;
+		}
+
+		// Every channel has another counterparty and moving funds to the sender keeps its free balance,
+		// so moving the funds of one channel does not invalidate the check of another.
+		for (counterparty, _, _, _, received) in &channels {
+			Self::ensure_can_move_reserved(counterparty, &sender, *received)?;
 		}
+
+		Self::verify_signatures(&signatures)?;
+
+		// ==== State change ================================
+		// Nothing below fails: `move_reserved` only fails if `ensure_can_move_reserved` did, and the
+		// channels are stored in the state that was checked above.
+		for nonce in nonces {
+			<Nonces<T>>::insert(nonce, true);
+		}
+
+		<States<T>>::mutate(&sender, |state| {
+			state.secret = on_chain_secret;
+		});
+
+		for (counterparty, channel_id, channel_balance, pending_until, received) in channels {
+			// The reserved funds of both parties always add up to their part of the channel balance,
+			// so that `withdraw` can pay out each part by unreserving it.
+			Self::move_reserved(&counterparty, &sender, received)?;
+
+			let channel = match pending_until {
+				Some(timestamp) => {
+					Self::deposit_event(RawEvent::PushedBackSettlement(channel_id, channel_balance.balance_a));
+					Channel::PendingSettlement(channel_balance, timestamp)
+				},
+				None => Channel::Active(channel_balance),
+			};
+			<Channels<T>>::insert(channel_id, channel);
+		}
+
+		for redemption in redemptions {
+			Self::deposit_event(RawEvent::RedeemedTicket(Self::get_id(&sender, &redemption.counterparty), redemption.amount));
+		}
+
+		Ok(())
 	}
 
-	fn cmp_hash(first_hash: &T::Hash, second_hash: &T::Hash) -> bool {
-		*(first_hash.as_ref()) < *(second_hash.as_ref())
+	/// Checks every `(signature, message, signer, error)` and fails with the error of the first
+	/// invalid signature.
+	///
+	/// The runtime interface of this Substrate version has no batch verification of sr25519
+	/// signatures, so they are verified one after another. Callers collect the signatures until
+	/// all other checks have passed, so that they can switch to batch verification once it exists.
+	fn verify_signatures(signatures: &[(&Signature, Vec<u8>, Public, Error)]) -> Result {
+		for (signature, message, signer, error) in signatures {
+			ensure!(Verify::verify(*signature, message.as_slice(), signer), (*error).into());
+		}
+		Ok(())
 	}
-}
 
-/// tests for this module
-#[cfg(test)]
-mod tests {
-	use super::*;
+	/// Checks that `move_reserved` can move `amount` of the reserved funds of `from` to `to`.
+	fn ensure_can_move_reserved(from: &T::AccountId, to: &T::AccountId, amount: T::Balance) -> Result {
+		ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserved_balance(from) >= amount, Error::CounterpartyInsufficientFunds.into());
+		// `repatriate_reserved` only pays accounts that exist.
+		ensure!(<balances::Module<T> as Currency<<T as system::Trait>::AccountId>>::total_balance(to) > <T::Balance as As<u64>>::sa(0), Error::InsufficientFunds.into());
+		// The funds arrive as free balance and are reserved again right away, so the free balance
+		// of `to` stays the same and only its locks can prevent the reservation.
+		ensure!(<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::can_reserve(to, <T::Balance as As<u64>>::sa(0)), Error::InsufficientFunds.into());
+		Ok(())
+	}
 
-	use primitives::{sr25519, Blake2Hasher, Hasher, Pair, H256};
-	use runtime_io::{with_externalities, TestExternalities};
-	use runtime_primitives::{
-		testing::{Digest, DigestItem, Header},
-		traits::{BlakeTwo256, IdentityLookup, Verify},
-		BuildStorage,
-	};
-	use support::{assert_noop, assert_ok, impl_outer_origin};
-
-	type AccountId = <AccountSignature as Verify>::Signer;
-	type AccountSignature = sr25519::Signature;
-
-	impl_outer_origin! {
-		pub enum Origin for HoprTest {}
+	/// Moves `amount` of the reserved funds of `from` to the reserved funds of `to`.
+	///
+	/// Does not fail if `ensure_can_move_reserved` passed for the same arguments.
+	fn move_reserved(from: &T::AccountId, to: &T::AccountId, amount: T::Balance) -> Result {
+		let remaining = <balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::repatriate_reserved(from, to, amount)?;
+		ensure!(remaining == <T::Balance as As<u64>>::sa(0), Error::CounterpartyInsufficientFunds.into());
+		<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(to, amount)
 	}
 
-	// For testing the module, we construct most of a mock runtime. This means
-	// first constructing a configuration type (`Test`) which `impl`s each of the
-	// configuration traits of modules we want to use.
-	#[derive(Clone, Eq, PartialEq)]
-	pub struct HoprTest;
-	impl system::Trait for HoprTest {
-		type Origin = Origin;
-		type Index = u64;
-		type BlockNumber = u64;
-		type Hash = H256;
-		type Hashing = BlakeTwo256;
-		type Digest = Digest;
-		type AccountId = AccountId;
-		type Lookup = IdentityLookup<Self::AccountId>;
-		type Header = Header;
-		type Event = ();
-		type Log = DigestItem;
+	/// Stores `channel_balance` as the balance of the open channel `channel_id`.
+	fn update_channel_balance(channel_id: &ChannelId<T>, channel_balance: ChannelBalance<T::Balance>) -> Result {
+		<Channels<T>>::mutate(channel_id, |channel| {
+			*channel = match channel {
+				Channel::Active(_) => Channel::Active(channel_balance),
+				Channel::PendingSettlement(_, timestamp) => Channel::PendingSettlement(channel_balance, timestamp.clone()),
+				Channel::Uninitialized => return Err(Error::ChannelDoesNotExist.into()),
+				_ => return Err(Error::WrongChannelState.into()),
+			};
+			Ok(())
+		})
 	}
 
-	impl timestamp::Trait for HoprTest {
-		/// A timestamp: seconds since the unix epoch.
-		type Moment = u64;
-		type OnTimestampSet = ();
+	/// The balance of the channel of `account` with `counterparty` once the unlocked `amount` is
+	/// added to the part of `account`, or `None` if the channel was settled in the meantime.
+	fn released_channel_balance(account: &T::AccountId, counterparty: &T::AccountId, amount: T::Balance) -> rstd::result::Result<Option<ChannelBalance<T::Balance>>, &'static str> {
+		let mut channel_balance = match Self::channels(Self::get_id(account, counterparty)) {
+			Channel::Active(channel_balance) | Channel::PendingSettlement(channel_balance, _) => channel_balance,
+			_ => return Ok(None),
+		};
+
+		channel_balance.balance = channel_balance.balance.checked_add(&amount).ok_or(Error::Overflow)?;
+		if Self::is_party_a(account, counterparty) {
+			channel_balance.balance_a = channel_balance.balance_a.checked_add(&amount).ok_or(Error::Overflow)?;
+		}
+
+		Ok(Some(channel_balance))
 	}
 
-	impl balances::Trait for HoprTest {
-		/// The type for recording an account's balance.
-		type Balance = u128;
-		/// What to do if an account's free balance gets zeroed.
-		type OnFreeBalanceZero = ();
-		/// What to do if a new account is created.
-		type OnNewAccount = ();
-		/// The uniquitous event type.
-		type Event = ();
-
-		type TransactionPayment = ();
-		type DustRemoval = ();
-		type TransferPayment = ();
+	/// Adds the unlocked `amount`, which `account` has reserved, to its part of the channel with
+	/// `counterparty` by storing `channel_balance` from `released_channel_balance`. If the channel
+	/// was settled in the meantime, the funds are paid out instead.
+	fn release_locked(account: &T::AccountId, counterparty: &T::AccountId, amount: T::Balance, channel_balance: Option<ChannelBalance<T::Balance>>) -> Result {
+		match channel_balance {
+			Some(channel_balance) => Self::update_channel_balance(&Self::get_id(account, counterparty), channel_balance),
+			None => {
+				<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(account, amount);
+				Ok(())
+			},
+		}
 	}
 
-	// impl sudo::Trait for HoprTest {
-	// 	type Event = ();
-	// 	type Proposal = ();
-	// }
+	fn test_and_set_nonce(nonce: T::Hash) -> Result {
+		ensure!(!<Nonces<T>>::exists(nonce), Error::NonceUsed.into());
+		<Nonces<T>>::insert(nonce, true);
+		Ok(())
+	}
 
-	impl super::Trait for HoprTest {
-		type Event = ();
+	fn is_party_a(a: &T::AccountId, b: &T::AccountId) -> bool {
+		hopr_primitives::is_party_a(a, b)
 	}
 
-	type Hopr = Module<HoprTest>;
+	/// Give the payment channels a meaningful ID that is the same for both
+	/// parties
+	fn get_id(a: &T::AccountId, b: &T::AccountId) -> ChannelId<T> {
+		hopr_primitives::channel_id::<T::Hashing, _>(a, b)
+	}
+}
 
-	fn new_test_ext() -> TestExternalities<Blake2Hasher> {
-		let alice = account_key("Alice");
-		let bob = account_key("Bob");
+#[cfg(test)]
+mod mock;
 
-		let mut t = system::GenesisConfig::<HoprTest>::default()
-			.build_storage()
-			.unwrap()
-			.0;
-		t.extend(
-			balances::GenesisConfig::<HoprTest> {
-				transaction_base_fee: 0,
-				transaction_byte_fee: 0,
-				existential_deposit: 0,
-				transfer_fee: 0,
-				creation_fee: 0,
-				balances: vec![alice, bob]
-					.iter()
-					.cloned()
-					.map(|k| (k, 1 << 60))
-					.collect(),
-				vesting: vec![],
-			}
-			.build_storage()
-			.unwrap()
-			.0,
-		);
-		t.into()
+#[cfg(test)]
+mod property_tests;
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::mock::*;
+
+	use primitives::{sr25519, Blake2Hasher, Hasher, Pair, H256};
+	use runtime_io::with_externalities;
+	use runtime_primitives::traits::BlakeTwo256;
+	use support::{assert_noop, assert_ok, traits::{LockableCurrency, WithdrawReasons}};
+
+	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+		ExtBuilder::default().build()
 	}
 
 	const PRE_IMAGE: [u8; 32] = [0u8; 32];
 
-	fn account_key(s: &str) -> AccountId {
-		sr25519::Pair::from_string(&format!("//{}", s), None)
-			.expect("static values are valid; qed")
-			.public()
-	}
+	/// Last block that the tickets of the tests can be redeemed in.
+	const EXPIRY: u64 = 100;
 
 	fn my_account_key(seed: [u8; 32]) -> AccountId {
 		println!("{:?}", sr25519::Pair::from_seed(seed).public());
 		sr25519::Pair::from_seed(seed).public()
 	}
 
-	fn key(s: &str) -> sr25519::Pair {
-		sr25519::Pair::from_string(&format!("//{}", s), None).expect("static values are valid; qed")
+	#[test]
+	fn error_codes_are_stable() {
+		for (code, error) in Error::ALL.iter().enumerate() {
+			assert_eq!(error.code() as usize, code);
+			// Every message identifies its error.
+			assert_eq!(Error::ALL.iter().filter(|other| other.message() == error.message()).count(), 1);
+		}
+		assert_eq!(Error::NotImplemented.code(), 38);
 	}
 
 	#[test]
@@ -618,7 +1116,38 @@ mod tests {
 					account_id.into(),
 					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
 				),
-				"State must be set at most once."
+				Error::StateAlreadySet.into()
+			);
+		});
+	}
+
+	#[test]
+	fn verify_genesis_states() {
+		let account_id = account_key("Alice");
+		let hashed_secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+
+		let mut ext = ExtBuilder::default()
+			.pending_window(20)
+			.state(account_id.clone(), hashed_secret)
+			.build();
+
+		with_externalities(&mut ext, || {
+			assert_eq!(Hopr::pending_window(), 20);
+			assert_eq!(
+				Hopr::state(account_id.clone()),
+				State {
+					pubkey: account_id.clone(),
+					secret: hashed_secret
+				}
+			);
+
+			assert_noop!(
+				Hopr::init(
+					Origin::signed(account_id.clone()),
+					account_id.into(),
+					hashed_secret
+				),
+				Error::StateAlreadySet.into()
 			);
 		});
 	}
@@ -634,7 +1163,7 @@ mod tests {
 
 			assert_noop!(
 				Hopr::set_secret(sender.clone(), second_hash.clone()),
-				"Call init() before setting a new on-chain secret."
+				Error::UnknownState.into()
 			);
 
 			assert_ok!(Hopr::init(
@@ -647,7 +1176,7 @@ mod tests {
 
 			assert_noop!(
 				Hopr::set_secret(sender, second_hash),
-				"New and old hash must not be the same."
+				Error::SameSecret.into()
 			);
 		})
 	}
@@ -663,16 +1192,16 @@ mod tests {
 
 			assert_noop!(
 				Hopr::create(sender.clone(), 1, account_id_counterparty.clone()),
-				"Party must have called init() before."
+				Error::UnknownState.into()
 			);
 			assert_noop!(
 				Hopr::create(counterparty.clone(), 1, account_id.clone()),
-				"Party must have called init() before."
+				Error::UnknownState.into()
 			);
 
 			assert_noop!(
 				Hopr::create(sender.clone(), 1, account_id.clone()),
-				"Sender and counterparty must not be the same."
+				Error::SameAccount.into()
 			);
 
 			assert_ok!(Hopr::init(
@@ -737,20 +1266,20 @@ mod tests {
 
 			assert_noop!(
 				Hopr::create(sender.clone(), 1, account_id_counterparty.clone()),
-				"Party must have called init() before."
+				Error::UnknownState.into()
 			);
 			assert_noop!(
 				Hopr::create(counterparty.clone(), 1, account_id.clone()),
-				"Party must have called init() before."
+				Error::UnknownState.into()
 			);
 
 			assert_noop!(
 				Hopr::create(sender.clone(), 1, account_id.clone()),
-				"Sender and counterparty must not be the same."
+				Error::SameAccount.into()
 			);
 			assert_noop!(
 				Hopr::create(sender.clone(), 0, account_id_counterparty.clone()),
-				"Funds must be strictly greater than zero."
+				Error::ZeroAmount.into()
 			);
 
 			assert_ok!(Hopr::init(
@@ -783,7 +1312,8 @@ mod tests {
 
 			let channel_id = Hopr::get_id(&account_id, &account_id_counterparty);
 
-			assert_eq!(Hopr::channels(channel_id), Channel::Active(channel_balance));
+			assert_eq!(Hopr::channels(channel_id), Channel::Active(channel_balance.clone()));
+			assert_eq!(Hopr::all_channels(), vec![(channel_id, Channel::Active(channel_balance))]);
 
 			assert_noop!(
 				Hopr::create_funded(
@@ -792,7 +1322,7 @@ mod tests {
 					signature.clone(),
 					1
 				),
-				"Channel must not exist."
+				Error::ChannelExists.into()
 			);
 		})
 	}
@@ -827,7 +1357,7 @@ mod tests {
 					account_id_counterparty.clone(),
 					signature.clone()
 				),
-				"Sender and counterparty must not be the same."
+				Error::SameAccount.into()
 			);
 
 			assert_noop!(
@@ -836,7 +1366,7 @@ mod tests {
 					account_id_counterparty.clone(),
 					signature.clone()
 				),
-				"Party must have called init() before."
+				Error::UnknownState.into()
 			);
 			assert_noop!(
 				Hopr::set_active(
@@ -844,7 +1374,7 @@ mod tests {
 					account_id.clone(),
 					signature_sender.clone()
 				),
-				"Party must have called init() before."
+				Error::UnknownState.into()
 			);
 
 			assert_ok!(Hopr::init(
@@ -864,7 +1394,7 @@ mod tests {
 					account_id_counterparty.clone(),
 					signature.clone()
 				),
-				"Channel does not exist and/or its state does not fit."
+				Error::ChannelDoesNotExist.into()
 			);
 
 			assert_ok!(Hopr::create(
@@ -888,7 +1418,7 @@ mod tests {
 
 			assert_noop!(
 				Hopr::create(sender.clone(), 1, account_id_counterparty.clone()),
-				"Channel must not be created twice."
+				Error::ChannelExists.into()
 			);
 
 			assert_eq!(
@@ -902,7 +1432,7 @@ mod tests {
 					account_id_counterparty.clone(),
 					signature.clone()
 				),
-				"Channel does not exist and/or its state does not fit."
+				Error::WrongChannelState.into()
 			);
 
 			assert_eq!(Hopr::channels(channel_id), Channel::Active(channel_balance));
@@ -940,6 +1470,7 @@ mod tests {
 			};
 
 			let channel_id = Hopr::get_id(&account_id, &account_id_counterparty);
+			assert_eq!(Hopr::all_channels(), vec![(channel_id, Channel::Funded(channel_balance.clone()))]);
 
 			let opening_signature = key("Bob").sign(
 				(Channel::Funded(channel_balance.clone()) as Channel<u128, u64>)
@@ -952,6 +1483,7 @@ mod tests {
 				account_id_counterparty.clone(),
 				opening_signature.clone()
 			));
+			assert_eq!(Hopr::all_channels(), vec![(channel_id, Channel::Active(channel_balance.clone()))]);
 
 			let message = ("restore_transaction", channel_id, &channel_balance).encode();
 
@@ -971,7 +1503,7 @@ mod tests {
 					recovery_signature.clone(),
 					channel_balance.clone()
 				),
-				"Channel does not exist and/or its state does not fit."
+				Error::WrongChannelState.into()
 			);
 
 			let timestamp = timestamp::Module::<HoprTest>::now()
@@ -984,7 +1516,7 @@ mod tests {
 
 			assert_noop!(
 				Hopr::withdraw(sender.clone(), account_id_counterparty.clone()),
-				"Channel is not yet recoverable."
+				Error::PendingWindowNotElapsed.into()
 			);
 
 			assert_ok!(Hopr::dispatch(
@@ -998,10 +1530,11 @@ mod tests {
 			));
 
 			assert_eq!(Hopr::channels(channel_id.clone()), Channel::Uninitialized);
+			assert!(Hopr::all_channels().is_empty());
 
 			assert_noop!(
 				Hopr::withdraw(sender.clone(), account_id_counterparty.clone()),
-				"Channel does not exist and/or its state does not fit."
+				Error::ChannelDoesNotExist.into()
 			);
 		})
 	}
@@ -1053,26 +1586,40 @@ mod tests {
 			let hashed_s_b = <Blake2Hasher as Hasher>::hash(&s_b);
 
 			let challenge = (hashed_s_a, hashed_s_b).using_encoded(<Blake2Hasher as Hasher>::hash);
-			let ticket: LotteryTicket<H256, u128> = LotteryTicket {
+			let ticket: LotteryTicket<H256, u128, u64> = LotteryTicket::new(
 				challenge,
-				on_chain_secret: counterparty_on_chain_secret,
-				amount: 1,
-				win_prob: <Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
-			};
+				next_hop("Charlie"),
+				counterparty_on_chain_secret,
+				1,
+				<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
+				EXPIRY,
+			);
 			let redeem_signature = key("Alice").sign(ticket.encode().as_slice());
 
+			assert_ok!(Hopr::init(
+				Origin::signed(account_key("Charlie")),
+				account_key("Charlie").into(),
+				<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
+			));
+			let acknowledgement = acknowledgement(&H256::from(s_a), &H256::from(s_b));
+			let acknowledgement_signature = sign_acknowledgement("Charlie", &acknowledgement);
+
 			assert_noop!(
 				Hopr::redeem_ticket(
 					counterparty.clone(),
 					redeem_signature.clone(),
 					account_id.clone(),
+					counterparty_on_chain_secret,
 					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
 					H256::from(s_a.clone()),
-					H256::from(s_b.clone()),
+					acknowledgement.clone(),
+					acknowledgement_signature.clone(),
+					account_key("Charlie"),
 					1,
-					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
+					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
+					EXPIRY
 				),
-				"Channel does not exist and/or its state does not fit."
+				Error::WrongChannelState.into()
 			);
 
 			assert_ok!(Hopr::set_active(
@@ -1091,13 +1638,17 @@ mod tests {
 					counterparty.clone(),
 					redeem_signature.clone(),
 					account_id.clone(),
+					counterparty_on_chain_secret,
 					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
 					H256::from(s_a.clone()),
-					H256::from(s_b.clone()),
+					acknowledgement.clone(),
+					acknowledgement_signature.clone(),
+					account_key("Charlie"),
 					2,
-					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
+					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
+					EXPIRY
 				),
-				"Transferred funds must not exceed channel balance."
+				Error::BalanceExceeded.into()
 			);
 
 			assert_noop!(
@@ -1105,24 +1656,32 @@ mod tests {
 					counterparty.clone(),
 					opening_signature.clone(),
 					account_id.clone(),
+					counterparty_on_chain_secret,
 					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
 					H256::from(s_a.clone()),
-					H256::from(s_b.clone()),
+					acknowledgement.clone(),
+					acknowledgement_signature.clone(),
+					account_key("Charlie"),
 					1,
-					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
+					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
+					EXPIRY
 				),
-				"Signature must be valid."
+				Error::InvalidSignature.into()
 			);
 
 			assert_ok!(Hopr::redeem_ticket(
 				counterparty.clone(),
 				redeem_signature.clone(),
 				account_id.clone(),
+				counterparty_on_chain_secret,
 				<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
 				H256::from(s_a.clone()),
-				H256::from(s_b.clone()),
+				acknowledgement.clone(),
+				acknowledgement_signature.clone(),
+				account_key("Charlie"),
 				1,
-				<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
+				<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
+				EXPIRY
 			));
 
 			assert_noop!(
@@ -1130,13 +1689,17 @@ mod tests {
 					counterparty.clone(),
 					redeem_signature.clone(),
 					account_id.clone(),
+					counterparty_on_chain_secret,
 					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
 					H256::from(s_a.clone()),
-					H256::from(s_b.clone()),
+					acknowledgement.clone(),
+					acknowledgement_signature.clone(),
+					account_key("Charlie"),
 					1,
-					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE)
+					<Blake2Hasher as Hasher>::hash(&PRE_IMAGE),
+					EXPIRY
 				),
-				"Given value is not a pre-image of the stored on-chain secret"
+				Error::NotPreImage.into()
 			);
 
 			let new_channel_balance: ChannelBalance<u128> = ChannelBalance {
@@ -1164,7 +1727,7 @@ mod tests {
 
 			assert_noop!(
 				Hopr::withdraw(sender.clone(), account_id_counterparty.clone()),
-				"Channel is not yet recoverable."
+				Error::PendingWindowNotElapsed.into()
 			);
 
 			assert_ok!(Hopr::dispatch(
@@ -1176,6 +1739,621 @@ mod tests {
 				sender.clone(),
 				account_id_counterparty.clone()
 			));
+
+			assert_eq!(Balances::reserved_balance(&account_id), 0);
+			assert_eq!(Balances::reserved_balance(&account_id_counterparty), 0);
+			assert_eq!(Balances::free_balance(&account_id), ENDOWMENT - 1);
+			assert_eq!(Balances::free_balance(&account_id_counterparty), ENDOWMENT + 1);
+			assert_eq!(hopr_events().last(), Some(&RawEvent::Withdrawn(channel_id, 1, 1)));
+		})
+	}
+
+	#[test]
+	fn verify_events() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), secret)
+			.state(bob.clone(), secret)
+			.build();
+
+		with_externalities(&mut ext, || {
+			let channel_id = Hopr::get_id(&alice, &bob);
+			let channel_balance = if Hopr::is_party_a(&alice, &bob) {
+				ChannelBalance { balance: 5, balance_a: 5 }
+			} else {
+				ChannelBalance { balance: 5, balance_a: 0 }
+			};
+
+			assert_ok!(Hopr::create(Origin::signed(alice.clone()), 5, bob.clone()));
+			assert_ok!(Hopr::set_active(
+				Origin::signed(alice.clone()),
+				bob.clone(),
+				sign_funding("Bob", &channel_balance)
+			));
+			assert_ok!(Hopr::initiate_recovery(
+				Origin::signed(alice.clone()),
+				bob.clone(),
+				sign_recovery("Bob", &channel_id, &channel_balance),
+				channel_balance.clone()
+			));
+
+			assert_eq!(
+				hopr_events(),
+				vec![
+					RawEvent::Funded(alice.clone(), 5, channel_balance.balance_a),
+					RawEvent::Opened(channel_id, 5, channel_balance.balance_a),
+					RawEvent::InitiatedSettlement(channel_id, channel_balance.balance_a),
+				]
+			);
+		})
+	}
+
+	#[test]
+	fn verify_redeem_ticket_during_settlement() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let alice_secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+		let channel_balance = ChannelBalance { balance: 10, balance_a: 5 };
+
+		let mut ext = ExtBuilder::default()
+			.balance(alice.clone(), 100)
+			.balance(bob.clone(), 100)
+			.state(alice.clone(), alice_secret)
+			.state(bob.clone(), <Blake2Hasher as Hasher>::hash(alice_secret.as_ref()))
+			.state(account_key("Charlie"), alice_secret)
+			.channel(alice.clone(), bob.clone(), Channel::PendingSettlement(channel_balance.clone(), 30))
+			.build();
+
+		with_externalities(&mut ext, || {
+			let channel_id = Hopr::get_id(&alice, &bob);
+			assert_eq!(Balances::reserved_balance(&alice), 5);
+			assert_eq!(Balances::reserved_balance(&bob), 5);
+
+			let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+			let win_prob = H256::repeat_byte(0xff);
+			let ticket = LotteryTicket::new(challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()), next_hop("Charlie"), alice_secret, 2, win_prob, EXPIRY);
+			let acknowledgement = acknowledgement(&s_a, &s_b);
+			let acknowledgement_signature = sign_acknowledgement("Charlie", &acknowledgement);
+
+			set_now(31);
+			assert_noop!(
+				Hopr::redeem_ticket(Origin::signed(alice.clone()), sign_ticket("Bob", &ticket), bob.clone(), alice_secret, H256::from(PRE_IMAGE), s_a, acknowledgement.clone(), acknowledgement_signature.clone(), account_key("Charlie"), 2, win_prob, EXPIRY),
+				Error::PendingWindowElapsed.into()
+			);
+
+			set_now(30);
+			assert_ok!(Hopr::redeem_ticket(Origin::signed(alice.clone()), sign_ticket("Bob", &ticket), bob.clone(), alice_secret, H256::from(PRE_IMAGE), s_a, acknowledgement, acknowledgement_signature, account_key("Charlie"), 2, win_prob, EXPIRY));
+
+			let balance_a = if Hopr::is_party_a(&alice, &bob) { 7 } else { 3 };
+			assert_eq!(
+				hopr_events(),
+				vec![
+					RawEvent::PushedBackSettlement(channel_id, balance_a),
+					RawEvent::RedeemedTicket(channel_id, 2),
+				]
+			);
+			assert_eq!(
+				Hopr::channels(channel_id),
+				Channel::PendingSettlement(ChannelBalance { balance: 10, balance_a }, 30)
+			);
+			assert_eq!(Balances::reserved_balance(&alice), 7);
+			assert_eq!(Balances::reserved_balance(&bob), 3);
+			assert_eq!(Balances::free_balance(&alice) + Balances::free_balance(&bob), 190);
+		})
+	}
+
+	#[test]
+	fn verify_redeem_ticket_requires_acknowledgement() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let alice_secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), alice_secret)
+			.state(bob.clone(), alice_secret)
+			.state(account_key("Charlie"), alice_secret)
+			.state(account_key("Eve"), alice_secret)
+			.channel(alice.clone(), bob.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build();
+
+		with_externalities(&mut ext, || {
+			let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+			let win_prob = H256::repeat_byte(0xff);
+			let ticket = LotteryTicket::new(challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()), next_hop("Charlie"), alice_secret, 2, win_prob, EXPIRY);
+			let signature = sign_ticket("Bob", &ticket);
+			let acknowledgement = acknowledgement(&s_a, &s_b);
+			// Redeems the ticket with `acknowledgement` signed by `signer` and issued by `downstream`.
+			let redeem = |acknowledgement: Acknowledgement<H256>, signer: &str, downstream: &str| {
+				let acknowledgement_signature = sign_acknowledgement(signer, &acknowledgement);
+				Hopr::redeem_ticket(
+					Origin::signed(alice.clone()),
+					signature.clone(),
+					bob.clone(),
+					alice_secret,
+					H256::from(PRE_IMAGE),
+					s_a,
+					acknowledgement,
+					acknowledgement_signature,
+					account_key(downstream),
+					2,
+					win_prob,
+					EXPIRY,
+				)
+			};
+
+			assert_noop!(redeem(acknowledgement.clone(), "Alice", "Alice"), Error::OwnAcknowledgement.into());
+			assert_noop!(redeem(acknowledgement.clone(), "Dave", "Dave"), Error::UnknownDownstream.into());
+			assert_noop!(
+				redeem(Acknowledgement::new(*acknowledgement.challenge(), s_a), "Charlie", "Charlie"),
+				Error::AcknowledgementMismatch.into()
+			);
+			assert_noop!(redeem(acknowledgement.clone(), "Bob", "Charlie"), Error::InvalidAcknowledgementSignature.into());
+			// Eve is registered but Bob forwarded the packet to Charlie, so the ticket does not pay for her acknowledgement.
+			assert_noop!(redeem(acknowledgement.clone(), "Eve", "Eve"), Error::InvalidSignature.into());
+
+			assert_ok!(redeem(acknowledgement, "Charlie", "Charlie"));
+			assert_eq!(hopr_events(), vec![RawEvent::RedeemedTicket(Hopr::get_id(&alice, &bob), 2)]);
+		});
+	}
+
+	#[test]
+	fn verify_redeem_ticket_before_expiry() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let alice_secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), alice_secret)
+			.state(bob.clone(), alice_secret)
+			.state(account_key("Charlie"), alice_secret)
+			.channel(alice.clone(), bob.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build();
+
+		with_externalities(&mut ext, || {
+			let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+			let win_prob = H256::repeat_byte(0xff);
+			let ticket = LotteryTicket::new(challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()), next_hop("Charlie"), alice_secret, 2, win_prob, EXPIRY);
+			let acknowledgement = acknowledgement(&s_a, &s_b);
+			let redeem = || Hopr::redeem_ticket(
+				Origin::signed(alice.clone()),
+				sign_ticket("Bob", &ticket),
+				bob.clone(),
+				alice_secret,
+				H256::from(PRE_IMAGE),
+				s_a,
+				acknowledgement.clone(),
+				sign_acknowledgement("Charlie", &acknowledgement),
+				account_key("Charlie"),
+				2,
+				win_prob,
+				EXPIRY,
+			);
+
+			System::set_block_number(EXPIRY + 1);
+			assert_noop!(redeem(), Error::TicketExpired.into());
+
+			System::set_block_number(EXPIRY);
+			assert_ok!(redeem());
+		});
+	}
+
+	/// Redemption by Alice of a winning ticket of `issuer` for `on_chain_secret`, acknowledged by
+	/// `downstream`.
+	fn redemption(issuer: &str, on_chain_secret: H256, pre_image: H256, downstream: &str, amount: u128) -> Redemption<HoprTest> {
+		let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+		let win_prob = H256::repeat_byte(0xff);
+		let ticket = LotteryTicket::new(challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()), next_hop(downstream), on_chain_secret, amount, win_prob, EXPIRY);
+		let acknowledgement = acknowledgement(&s_a, &s_b);
+
+		TicketRedemption {
+			signature: sign_ticket(issuer, &ticket),
+			counterparty: account_key(issuer),
+			on_chain_secret,
+			pre_image,
+			s_a,
+			acknowledgement_signature: sign_acknowledgement(downstream, &acknowledgement),
+			acknowledgement,
+			downstream: account_key(downstream),
+			amount,
+			win_prob,
+			expiry: EXPIRY,
+		}
+	}
+
+	#[test]
+	fn verify_redeem_tickets() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let charlie = account_key("Charlie");
+		// Alice redeems two tickets, which walk down the hash chain `secret = H(H(pre_image))`.
+		let pre_image = H256::from(PRE_IMAGE);
+		let first_pre_image = BlakeTwo256::hash(pre_image.as_ref());
+		let secret = BlakeTwo256::hash(first_pre_image.as_ref());
+
+		let mut ext = ExtBuilder::default()
+			.balance(charlie.clone(), 100)
+			.state(alice.clone(), secret)
+			.state(bob.clone(), secret)
+			.state(charlie.clone(), secret)
+			.channel(alice.clone(), bob.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.channel(alice.clone(), charlie.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build();
+
+		with_externalities(&mut ext, || {
+			assert_noop!(Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![]), Error::NoTickets.into());
+
+			// The second ticket was issued for the current secret instead of the next one.
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![
+					redemption("Bob", secret, first_pre_image, "Charlie", 2),
+					redemption("Charlie", secret, first_pre_image, "Bob", 3),
+				]),
+				Error::NotPreImage.into()
+			);
+			// A single invalid signature rejects all tickets.
+			let mut invalid = redemption("Charlie", first_pre_image, pre_image, "Bob", 3);
+			invalid.signature = sign_acknowledgement("Charlie", &invalid.acknowledgement);
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", secret, first_pre_image, "Charlie", 2), invalid]),
+				Error::InvalidSignature.into()
+			);
+
+			assert_ok!(Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![
+				redemption("Bob", secret, first_pre_image, "Charlie", 2),
+				redemption("Charlie", first_pre_image, pre_image, "Bob", 3),
+			]));
+
+			assert_eq!(Hopr::on_chain_secret(&alice), Some(pre_image));
+			assert_eq!(Balances::reserved_balance(&alice), 5 + 5 + 2 + 3);
+			assert_eq!(Balances::reserved_balance(&bob), 5 - 2);
+			assert_eq!(Balances::reserved_balance(&charlie), 5 - 3);
+			assert_eq!(hopr_events(), vec![
+				RawEvent::RedeemedTicket(Hopr::get_id(&alice, &bob), 2),
+				RawEvent::RedeemedTicket(Hopr::get_id(&alice, &charlie), 3),
+			]);
+		});
+	}
+
+	#[test]
+	fn redeem_tickets_of_one_channel() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let pre_image = H256::from(PRE_IMAGE);
+		let first_pre_image = BlakeTwo256::hash(pre_image.as_ref());
+		let secret = BlakeTwo256::hash(first_pre_image.as_ref());
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), secret)
+			.state(bob.clone(), secret)
+			.state(account_key("Charlie"), secret)
+			.channel(alice.clone(), bob.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build();
+
+		with_externalities(&mut ext, || {
+			// Both tickets together exceed the part of Bob.
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![
+					redemption("Bob", secret, first_pre_image, "Charlie", 3),
+					redemption("Bob", first_pre_image, pre_image, "Charlie", 3),
+				]),
+				(if Hopr::is_party_a(&alice, &bob) { Error::BalanceExceeded } else { Error::Overflow }).into()
+			);
+
+			assert_ok!(Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![
+				redemption("Bob", secret, first_pre_image, "Charlie", 3),
+				redemption("Bob", first_pre_image, pre_image, "Charlie", 2),
+			]));
+
+			let balance_a = if Hopr::is_party_a(&alice, &bob) { 10 } else { 0 };
+			assert_eq!(Hopr::channels(Hopr::get_id(&alice, &bob)), Channel::Active(ChannelBalance { balance: 10, balance_a }));
+			assert_eq!(Balances::reserved_balance(&alice), 10);
+			assert_eq!(Balances::reserved_balance(&bob), 0);
+		});
+	}
+
+	#[test]
+	fn redeem_ticket_with_deep_pre_image() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		// The on-chain secret of Alice is `H(H(H(pre_image)))`.
+		let pre_image = H256::from(PRE_IMAGE);
+		let first_pre_image = BlakeTwo256::hash(pre_image.as_ref());
+		let second_pre_image = BlakeTwo256::hash(first_pre_image.as_ref());
+		let secret = BlakeTwo256::hash(second_pre_image.as_ref());
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), secret)
+			.state(bob.clone(), secret)
+			.state(account_key("Charlie"), secret)
+			.max_pre_image_depth(2)
+			.channel(alice.clone(), bob.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build();
+
+		with_externalities(&mut ext, || {
+			// The pre-image lies deeper than the maximum depth of 2.
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", second_pre_image, pre_image, "Charlie", 2)]),
+				Error::NotPreImage.into()
+			);
+			// The ticket was issued for the pre-image itself.
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", first_pre_image, first_pre_image, "Charlie", 2)]),
+				Error::OnChainSecretMismatch.into()
+			);
+
+			assert_ok!(Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", second_pre_image, first_pre_image, "Charlie", 2)]));
+			assert_eq!(Hopr::on_chain_secret(&alice), Some(first_pre_image));
+		});
+	}
+
+	#[test]
+	fn max_pre_image_depth_is_capped() {
+		with_externalities(&mut ExtBuilder::default().max_pre_image_depth(MAX_PRE_IMAGE_DEPTH_LIMIT + 1).build(), || {
+			assert_eq!(Hopr::max_pre_image_depth(), MAX_PRE_IMAGE_DEPTH_LIMIT);
+		});
+	}
+
+	#[test]
+	fn redeem_tickets_checks_reserved_funds_first() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let pre_image = H256::from(PRE_IMAGE);
+		let secret = BlakeTwo256::hash(pre_image.as_ref());
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), secret)
+			.state(bob.clone(), secret)
+			.state(account_key("Charlie"), secret)
+			.channel(alice.clone(), bob.clone(), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build();
+
+		with_externalities(&mut ext, || {
+			// Bob no longer has his part of the channel reserved, so nothing may change.
+			Balances::unreserve(&bob, 4);
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", secret, pre_image, "Charlie", 2)]),
+				Error::CounterpartyInsufficientFunds.into()
+			);
+			Balances::reserve(&bob, 4).unwrap();
+
+			// Alice cannot reserve the funds that she receives.
+			Balances::set_lock(*b"hoprtest", &alice, 100, 10, WithdrawReasons::all());
+			assert_noop!(
+				Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", secret, pre_image, "Charlie", 2)]),
+				Error::InsufficientFunds.into()
+			);
+			Balances::remove_lock(*b"hoprtest", &alice);
+
+			assert_ok!(Hopr::redeem_tickets(Origin::signed(alice.clone()), vec![redemption("Bob", secret, pre_image, "Charlie", 2)]));
+			assert_eq!(Balances::reserved_balance(&alice), 5 + 2);
+			assert_eq!(Balances::reserved_balance(&bob), 5 - 2);
+		});
+	}
+
+	/// Externalities with an active channel between Alice and Bob, in which both have 5.
+	fn locked_payment_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+		let secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+		ExtBuilder::default()
+			.balance(account_key("Alice"), 100)
+			.balance(account_key("Bob"), 100)
+			.state(account_key("Alice"), secret)
+			.state(account_key("Bob"), secret)
+			.channel(account_key("Alice"), account_key("Bob"), Channel::Active(ChannelBalance { balance: 10, balance_a: 5 }))
+			.build()
+	}
+
+	/// Locks a payment of 3 from Alice to Bob until block 10 and returns its id.
+	fn lock_payment_to_bob(hash_lock: H256) -> H256 {
+		let lock = HashLock { channel_id: Hopr::get_id(&account_key("Alice"), &account_key("Bob")), hash_lock, amount: 3, deadline: 10 };
+		let signature = sign_hash_lock("Alice", &lock);
+		assert_ok!(Hopr::lock_payment(Origin::signed(account_key("Bob")), account_key("Alice"), lock, signature.clone()));
+		BlakeTwo256::hash(signature.as_ref())
+	}
+
+	/// Part of `name` in the channel between Alice and Bob.
+	fn part(name: &str) -> u128 {
+		let channel_balance = match Hopr::channels(Hopr::get_id(&account_key("Alice"), &account_key("Bob"))) {
+			Channel::Active(channel_balance) | Channel::PendingSettlement(channel_balance, _) => channel_balance,
+			_ => return 0,
+		};
+		if Hopr::is_party_a(&account_key(name), &account_key(if name == "Alice" { "Bob" } else { "Alice" })) {
+			channel_balance.balance_a
+		} else {
+			channel_balance.balance - channel_balance.balance_a
+		}
+	}
+
+	#[test]
+	fn verify_lock_payment() {
+		with_externalities(&mut locked_payment_ext(), || {
+			let alice = account_key("Alice");
+			let bob = account_key("Bob");
+			let channel_id = Hopr::get_id(&alice, &bob);
+			let lock = HashLock { channel_id, hash_lock: H256::repeat_byte(1), amount: 3, deadline: 10 };
+
+			assert_noop!(
+				Hopr::lock_payment(Origin::signed(bob.clone()), alice.clone(), lock.clone(), sign_hash_lock("Bob", &lock)),
+				Error::InvalidSignature.into()
+			);
+			let other_channel = HashLock { channel_id: H256::repeat_byte(2), ..lock.clone() };
+			assert_noop!(
+				Hopr::lock_payment(Origin::signed(bob.clone()), alice.clone(), other_channel.clone(), sign_hash_lock("Alice", &other_channel)),
+				Error::WrongHashLockChannel.into()
+			);
+			let too_much = HashLock { amount: 6, ..lock.clone() };
+			assert_noop!(
+				Hopr::lock_payment(Origin::signed(bob.clone()), alice.clone(), too_much.clone(), sign_hash_lock("Alice", &too_much)),
+				Error::BalanceExceeded.into()
+			);
+
+			System::set_block_number(11);
+			assert_noop!(
+				Hopr::lock_payment(Origin::signed(bob.clone()), alice.clone(), lock.clone(), sign_hash_lock("Alice", &lock)),
+				Error::DeadlinePassed.into()
+			);
+
+			System::set_block_number(10);
+			let lock_id = lock_payment_to_bob(H256::repeat_byte(1));
+			assert_eq!(hopr_events().last(), Some(&RawEvent::LockedPayment(channel_id, lock_id, 3)));
+			assert_eq!((part("Alice"), part("Bob")), (2, 5));
+			assert_eq!(Balances::reserved_balance(&alice), 5);
+		});
+	}
+
+	#[test]
+	fn claimed_payment_moves_to_payee() {
+		with_externalities(&mut locked_payment_ext(), || {
+			let secret = H256::repeat_byte(7);
+			let lock_id = lock_payment_to_bob(BlakeTwo256::hash(secret.as_ref()));
+
+			assert_noop!(Hopr::claim_payment(Origin::signed(account_key("Alice")), lock_id, secret), Error::NotPayee.into());
+			assert_noop!(
+				Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, H256::repeat_byte(8)),
+				Error::NotHashLockPreImage.into()
+			);
+			assert_noop!(Hopr::refund_payment(Origin::signed(account_key("Alice")), lock_id), Error::RefundBeforeDeadline.into());
+
+			System::set_block_number(10);
+			assert_ok!(Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret));
+			assert_eq!(hopr_events().last(), Some(&RawEvent::ClaimedPayment(lock_id, secret)));
+			assert_eq!((part("Alice"), part("Bob")), (2, 8));
+			assert_eq!(Balances::reserved_balance(&account_key("Alice")), 2);
+			assert_eq!(Balances::reserved_balance(&account_key("Bob")), 8);
+			assert_noop!(Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret), Error::UnknownLockedPayment.into());
+		});
+	}
+
+	#[test]
+	fn failed_claim_keeps_locked_payment() {
+		with_externalities(&mut locked_payment_ext(), || {
+			let secret = H256::repeat_byte(7);
+			let lock_id = lock_payment_to_bob(BlakeTwo256::hash(secret.as_ref()));
+
+			// Bob cannot reserve the funds that he receives, so the payment stays locked.
+			Balances::set_lock(*b"hoprtest", &account_key("Bob"), 100, 10, WithdrawReasons::all());
+			assert_noop!(
+				Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret),
+				Error::InsufficientFunds.into()
+			);
+			assert!(Hopr::locked_payment(lock_id).is_some());
+
+			// Alice no longer has the locked funds reserved.
+			Balances::remove_lock(*b"hoprtest", &account_key("Bob"));
+			Balances::unreserve(&account_key("Alice"), 5);
+			assert_noop!(
+				Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret),
+				Error::CounterpartyInsufficientFunds.into()
+			);
+			assert!(Hopr::locked_payment(lock_id).is_some());
+
+			Balances::reserve(&account_key("Alice"), 5).unwrap();
+			assert_ok!(Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret));
+		});
+	}
+
+	#[test]
+	fn expired_payment_is_refunded() {
+		with_externalities(&mut locked_payment_ext(), || {
+			let secret = H256::repeat_byte(7);
+			let lock_id = lock_payment_to_bob(BlakeTwo256::hash(secret.as_ref()));
+
+			System::set_block_number(11);
+			assert_noop!(
+				Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret),
+				Error::ClaimAfterDeadline.into()
+			);
+			assert_noop!(Hopr::refund_payment(Origin::signed(account_key("Bob")), lock_id), Error::NotPayer.into());
+
+			assert_ok!(Hopr::refund_payment(Origin::signed(account_key("Alice")), lock_id));
+			assert_eq!(hopr_events().last(), Some(&RawEvent::RefundedPayment(lock_id)));
+			assert_eq!((part("Alice"), part("Bob")), (5, 5));
+			assert_eq!(Balances::reserved_balance(&account_key("Alice")), 5);
+		});
+	}
+
+	#[test]
+	fn locked_payment_is_paid_out_after_withdraw() {
+		with_externalities(&mut locked_payment_ext(), || {
+			let secret = H256::repeat_byte(7);
+			let lock_id = lock_payment_to_bob(BlakeTwo256::hash(secret.as_ref()));
+
+			assert_ok!(Hopr::initiate_settlement(Origin::signed(account_key("Alice")), account_key("Bob")));
+			set_now(PENDING_WINDOW + 1);
+			assert_ok!(Hopr::withdraw(Origin::signed(account_key("Alice")), account_key("Bob")));
+			assert_eq!(Balances::reserved_balance(&account_key("Alice")), 3);
+
+			assert_ok!(Hopr::claim_payment(Origin::signed(account_key("Bob")), lock_id, secret));
+			assert_eq!(Balances::reserved_balance(&account_key("Alice")), 0);
+			assert_eq!(Balances::free_balance(&account_key("Alice")), 97);
+			assert_eq!(Balances::reserved_balance(&account_key("Bob")), 0);
+			assert_eq!(Balances::free_balance(&account_key("Bob")), 103);
+		});
+	}
+
+	#[test]
+	fn verify_announce() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+		let peer_id = vec![1u8; 38];
+		let multiaddr = b"/ip4/127.0.0.1/tcp/9091".to_vec();
+
+		with_externalities(&mut ExtBuilder::default().state(alice.clone(), secret).state(bob.clone(), secret).build(), || {
+			assert_noop!(
+				Hopr::announce(Origin::signed(account_key("Charlie")), peer_id.clone(), vec![multiaddr.clone()]),
+				Error::UnknownState.into()
+			);
+			assert_noop!(Hopr::announce(Origin::signed(alice.clone()), vec![], vec![multiaddr.clone()]), Error::InvalidPeerId.into());
+			assert_noop!(
+				Hopr::announce(Origin::signed(alice.clone()), vec![1u8; MAX_PEER_ID_LEN + 1], vec![multiaddr.clone()]),
+				Error::InvalidPeerId.into()
+			);
+			assert_noop!(Hopr::announce(Origin::signed(alice.clone()), peer_id.clone(), vec![]), Error::InvalidMultiaddrCount.into());
+			assert_noop!(
+				Hopr::announce(Origin::signed(alice.clone()), peer_id.clone(), vec![multiaddr.clone(); MAX_MULTIADDRS + 1]),
+				Error::InvalidMultiaddrCount.into()
+			);
+			assert_noop!(
+				Hopr::announce(Origin::signed(alice.clone()), peer_id.clone(), vec![vec![0u8; MAX_MULTIADDR_LEN + 1]]),
+				Error::InvalidMultiaddr.into()
+			);
+
+			assert_ok!(Hopr::announce(Origin::signed(bob.clone()), peer_id.clone(), vec![multiaddr.clone()]));
+			assert_ok!(Hopr::announce(Origin::signed(alice.clone()), peer_id.clone(), vec![multiaddr.clone()]));
+			assert_eq!(hopr_events().last(), Some(&RawEvent::Announced(alice.clone())));
+
+			// Announcing again replaces the multiaddrs but keeps the position in the list.
+			let moved = b"/ip4/10.0.0.1/tcp/9091".to_vec();
+			assert_ok!(Hopr::announce(Origin::signed(bob.clone()), peer_id.clone(), vec![moved.clone()]));
+			assert_eq!(<AnnouncedNodes<HoprTest>>::get(), vec![bob.clone(), alice.clone()]);
+			assert_eq!(Hopr::announcements(), vec![
+				(bob.clone(), Announcement { peer_id: peer_id.clone(), multiaddrs: vec![moved] }),
+				(alice.clone(), Announcement { peer_id: peer_id.clone(), multiaddrs: vec![multiaddr] }),
+			]);
+		});
+	}
+
+	#[test]
+	fn verify_storage_keys() {
+		let alice = account_key("Alice");
+		let bob = account_key("Bob");
+		let secret = <Blake2Hasher as Hasher>::hash(&PRE_IMAGE);
+		let channel = Channel::Active(ChannelBalance { balance: 2, balance_a: 1 });
+
+		let mut ext = ExtBuilder::default()
+			.state(alice.clone(), secret)
+			.channel(alice.clone(), bob.clone(), channel.clone())
+			.build();
+
+		with_externalities(&mut ext, || {
+			let channel_id = Hopr::get_id(&alice, &bob);
+
+			assert_eq!(runtime_io::storage(&Hopr::channel_storage_key(&channel_id)), Some(channel.encode()));
+			assert_eq!(runtime_io::storage(&Hopr::state_storage_key(&alice)), Some(Hopr::state(&alice).encode()));
+			assert_eq!(runtime_io::storage(&Hopr::state_storage_key(&bob)), None);
 		})
 	}
 }
@@ -0,0 +1,249 @@
+//! Mock runtime for the tests of the hopr module.
+//!
+//! `ExtBuilder` sets up balances, registered accounts, the pending window and channels, and the
+//! events that the module deposits can be read back with `hopr_events`.
+
+use super::*;
+
+use primitives::{sr25519, Blake2Hasher, Pair, H256};
+use runtime_io::{with_externalities, TestExternalities};
+use runtime_primitives::{
+	testing::{Digest, DigestItem, Header},
+	traits::{BlakeTwo256, IdentityLookup, Verify},
+	BuildStorage,
+};
+use support::{impl_outer_event, impl_outer_origin};
+
+pub type AccountId = <AccountSignature as Verify>::Signer;
+pub type AccountSignature = sr25519::Signature;
+
+impl_outer_origin! {
+	pub enum Origin for HoprTest {}
+}
+
+mod hopr {
+	pub use crate::hopr::Event;
+}
+
+impl_outer_event! {
+	pub enum TestEvent for HoprTest {
+		balances<T>,
+		hopr<T>,
+	}
+}
+
+// For testing the module, we construct most of a mock runtime. This means
+// first constructing a configuration type (`Test`) which `impl`s each of the
+// configuration traits of modules we want to use.
+#[derive(Clone, Eq, PartialEq)]
+pub struct HoprTest;
+impl system::Trait for HoprTest {
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type Digest = Digest;
+	type AccountId = AccountId;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = TestEvent;
+	type Log = DigestItem;
+}
+
+impl timestamp::Trait for HoprTest {
+	/// A timestamp: seconds since the unix epoch.
+	type Moment = u64;
+	type OnTimestampSet = ();
+}
+
+impl balances::Trait for HoprTest {
+	/// The type for recording an account's balance.
+	type Balance = u128;
+	/// What to do if an account's free balance gets zeroed.
+	type OnFreeBalanceZero = ();
+	/// What to do if a new account is created.
+	type OnNewAccount = ();
+	/// The uniquitous event type.
+	type Event = TestEvent;
+
+	type TransactionPayment = ();
+	type DustRemoval = ();
+	type TransferPayment = ();
+}
+
+impl Trait for HoprTest {
+	type Event = TestEvent;
+}
+
+pub type Hopr = Module<HoprTest>;
+pub type Balances = balances::Module<HoprTest>;
+pub type System = system::Module<HoprTest>;
+pub type Timestamp = timestamp::Module<HoprTest>;
+
+/// Free balance of the accounts that the default builder endows.
+pub const ENDOWMENT: u128 = 1 << 60;
+
+/// Builds the externalities of a test.
+///
+/// By default, Alice and Bob are endowed with `ENDOWMENT`, nobody has called `init` and the
+/// pending window is `PENDING_WINDOW` and pre-images may lie `MAX_PRE_IMAGE_DEPTH` hashes deep.
+pub struct ExtBuilder {
+	balances: Vec<(AccountId, u128)>,
+	states: Vec<(AccountId, H256, H256)>,
+	pending_window: u64,
+	max_pre_image_depth: u32,
+	channels: Vec<(AccountId, AccountId, Channel<u128, u64>)>,
+}
+
+impl Default for ExtBuilder {
+	fn default() -> Self {
+		ExtBuilder {
+			balances: vec![(account_key("Alice"), ENDOWMENT), (account_key("Bob"), ENDOWMENT)],
+			states: vec![],
+			pending_window: PENDING_WINDOW,
+			max_pre_image_depth: MAX_PRE_IMAGE_DEPTH,
+			channels: vec![],
+		}
+	}
+}
+
+impl ExtBuilder {
+	/// Sets the free balance of `account`, replacing the default endowments.
+	pub fn balance(mut self, account: AccountId, balance: u128) -> Self {
+		self.balances.retain(|(a, _)| *a != account);
+		self.balances.push((account, balance));
+		self
+	}
+
+	/// Registers `account` with its own public key and the on-chain secret `secret`, as if it had
+	/// called `init`.
+	pub fn state(mut self, account: AccountId, secret: H256) -> Self {
+		let pubkey = H256::from_slice(account.as_ref());
+		self.states.push((account, pubkey, secret));
+		self
+	}
+
+	pub fn pending_window(mut self, pending_window: u64) -> Self {
+		self.pending_window = pending_window;
+		self
+	}
+
+	pub fn max_pre_image_depth(mut self, max_pre_image_depth: u32) -> Self {
+		self.max_pre_image_depth = max_pre_image_depth;
+		self
+	}
+
+	/// Adds the channel between `a` and `b`. The funds of both parties in the channel are reserved.
+	pub fn channel(mut self, a: AccountId, b: AccountId, channel: Channel<u128, u64>) -> Self {
+		self.channels.push((a, b, channel));
+		self
+	}
+
+	pub fn build(self) -> TestExternalities<Blake2Hasher> {
+		let mut t = system::GenesisConfig::<HoprTest>::default()
+			.build_storage()
+			.unwrap()
+			.0;
+		t.extend(
+			balances::GenesisConfig::<HoprTest> {
+				transaction_base_fee: 0,
+				transaction_byte_fee: 0,
+				existential_deposit: 0,
+				transfer_fee: 0,
+				creation_fee: 0,
+				balances: self.balances,
+				vesting: vec![],
+			}
+			.build_storage()
+			.unwrap()
+			.0,
+		);
+		t.extend(
+			GenesisConfig::<HoprTest> {
+				pending_window: self.pending_window,
+				max_pre_image_depth: self.max_pre_image_depth,
+				states: self.states,
+			}
+			.build_storage()
+			.unwrap()
+			.0,
+		);
+
+		let mut ext = TestExternalities::<Blake2Hasher>::from(t);
+		with_externalities(&mut ext, || {
+			for (a, b, channel) in self.channels {
+				let channel_balance = match &channel {
+					Channel::Uninitialized => continue,
+					Channel::Funded(channel_balance)
+					| Channel::Active(channel_balance)
+					| Channel::PendingSettlement(channel_balance, _) => channel_balance.clone(),
+				};
+
+				let (party_a, party_b) = if Hopr::is_party_a(&a, &b) { (a, b) } else { (b, a) };
+				Balances::reserve(&party_a, channel_balance.balance_a).expect("party A has enough funds");
+				Balances::reserve(&party_b, channel_balance.balance - channel_balance.balance_a)
+					.expect("party B has enough funds");
+
+				<Channels<HoprTest>>::insert(Hopr::get_id(&party_a, &party_b), channel);
+			}
+		});
+		ext
+	}
+}
+
+pub fn key(name: &str) -> sr25519::Pair {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed")
+}
+
+pub fn account_key(name: &str) -> AccountId {
+	key(name).public()
+}
+
+/// Signature of `name` that agrees on funding a channel with `channel_balance`.
+pub fn sign_funding(name: &str, channel_balance: &ChannelBalance<u128>) -> Signature {
+	key(name).sign(&hopr_primitives::funding_message(channel_balance))
+}
+
+/// Signature of `name` that agrees on `channel_balance` as backup state of the channel.
+pub fn sign_recovery(name: &str, channel_id: &H256, channel_balance: &ChannelBalance<u128>) -> Signature {
+	key(name).sign(&hopr_primitives::recovery_message(channel_id, channel_balance))
+}
+
+/// The `next_hop` of a ticket that is acknowledged by `name`.
+pub fn next_hop(name: &str) -> H256 {
+	hopr_primitives::next_hop::<BlakeTwo256>(account_key(name).as_ref())
+}
+
+/// Signature of the issuer `name` on `ticket`.
+pub fn sign_ticket(name: &str, ticket: &LotteryTicket<H256, u128, u64>) -> Signature {
+	key(name).sign(&ticket.encode())
+}
+
+/// Acknowledgement of the packet of a ticket with the key halves `s_a` and `s_b`.
+pub fn acknowledgement(s_a: &H256, s_b: &H256) -> Acknowledgement<H256> {
+	Acknowledgement::new(challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()), *s_b)
+}
+
+/// Signature of the downstream node `name` on `acknowledgement`.
+pub fn sign_acknowledgement(name: &str, acknowledgement: &Acknowledgement<H256>) -> Signature {
+	key(name).sign(&hopr_primitives::acknowledgement_message(acknowledgement))
+}
+
+/// Signature of the payer `name` on `lock`.
+pub fn sign_hash_lock(name: &str, lock: &HashLock<H256, u128, u64>) -> Signature {
+	key(name).sign(&hopr_primitives::hash_lock_message(lock))
+}
+
+/// The events of the hopr module that were deposited so far.
+pub fn hopr_events() -> Vec<RawEvent<AccountId, H256, u128>> {
+	System::events().into_iter().filter_map(|record| match record.event {
+		TestEvent::hopr(event) => Some(event),
+		_ => None,
+	}).collect()
+}
+
+/// Sets the current time, which is checked against the end of pending windows.
+pub fn set_now(now: u64) {
+	Timestamp::set_timestamp(now);
+}
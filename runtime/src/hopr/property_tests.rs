@@ -0,0 +1,269 @@
+//! Property-based tests of the channel state machine.
+//!
+//! Random sequences of calls from several accounts are applied to the module and the invariants
+//! of the channels are checked after every step.
+
+use super::mock::{set_now, AccountId, Balances, ExtBuilder, Hopr, Origin, Timestamp};
+use super::*;
+
+use primitives::{sr25519, Blake2Hasher, Hasher, Pair, H256};
+use proptest::prelude::*;
+use runtime_io::{with_externalities, TestExternalities};
+use runtime_primitives::traits::BlakeTwo256;
+
+const ACCOUNTS: usize = 3;
+
+/// Free balance of every account at genesis. Small enough that funding fails every now and then.
+const ENDOWMENT: u128 = 1_000;
+
+/// Number of pre-images per account, i.e. the maximum number of tickets it can redeem.
+const PRE_IMAGES: usize = 64;
+
+#[derive(Clone, Debug)]
+enum Action {
+	Init(usize),
+	/// `create` sent by the first account with the second as counterparty.
+	Create(usize, usize, u64),
+	/// `set_active` with the signature of the counterparty on the current funded balance.
+	SetActive(usize, usize),
+	/// `redeem_ticket` by the first account of a ticket issued by the second one, acknowledged by
+	/// the account after the first one.
+	RedeemTicket(usize, usize, u64),
+	InitiateSettlement(usize, usize),
+	Withdraw(usize, usize),
+	AdvanceTime(u64),
+}
+
+fn action() -> impl Strategy<Value = Action> {
+	let account = 0..ACCOUNTS;
+	prop_oneof![
+		account.clone().prop_map(Action::Init),
+		(account.clone(), account.clone(), 1u64..400).prop_map(|(a, b, funds)| Action::Create(a, b, funds)),
+		(account.clone(), account.clone()).prop_map(|(a, b)| Action::SetActive(a, b)),
+		(account.clone(), account.clone(), 1u64..200).prop_map(|(a, b, amount)| Action::RedeemTicket(a, b, amount)),
+		(account.clone(), account.clone()).prop_map(|(a, b)| Action::InitiateSettlement(a, b)),
+		(account.clone(), account).prop_map(|(a, b)| Action::Withdraw(a, b)),
+		(1u64..2 * PENDING_WINDOW).prop_map(Action::AdvanceTime),
+	]
+}
+
+/// The accounts of the test together with the hash chains of their on-chain secrets.
+struct Accounts {
+	keys: Vec<sr25519::Pair>,
+	/// `pre_images[i][k]` is the on-chain secret of account `i` after `k` redeemed tickets.
+	pre_images: Vec<Vec<H256>>,
+	redeemed: Vec<usize>,
+}
+
+impl Accounts {
+	fn new() -> Self {
+		let keys: Vec<sr25519::Pair> = ["Alice", "Bob", "Charlie"].iter()
+			.map(|name| sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed"))
+			.collect();
+
+		let pre_images = keys.iter().map(|key| {
+			let mut chain = vec![<Blake2Hasher as Hasher>::hash(key.public().as_ref())];
+			for _ in 0..PRE_IMAGES {
+				let next = <Blake2Hasher as Hasher>::hash(chain[chain.len() - 1].as_ref());
+				chain.push(next);
+			}
+			chain.reverse();
+			chain
+		}).collect();
+
+		Accounts {
+			keys,
+			pre_images,
+			redeemed: vec![0; ACCOUNTS],
+		}
+	}
+
+	fn id(&self, i: usize) -> AccountId {
+		self.keys[i].public()
+	}
+
+	fn ids(&self) -> Vec<AccountId> {
+		(0..ACCOUNTS).map(|i| self.id(i)).collect()
+	}
+
+	fn secret(&self, i: usize) -> H256 {
+		self.pre_images[i][self.redeemed[i]]
+	}
+
+	fn pre_image(&self, i: usize) -> H256 {
+		self.pre_images[i][self.redeemed[i] + 1]
+	}
+}
+
+fn new_test_ext(accounts: &Accounts) -> TestExternalities<Blake2Hasher> {
+	accounts.ids().into_iter()
+		.fold(ExtBuilder::default(), |builder, id| builder.balance(id, ENDOWMENT))
+		.build()
+}
+
+/// Whether a call may succeed on a channel in the state `channel`.
+fn is_legal(action: &Action, channel: &Channel<u128, u64>, now: u64) -> bool {
+	match (action, channel) {
+		(Action::Create(..), Channel::Uninitialized) | (Action::Create(..), Channel::Funded(_)) => true,
+		(Action::SetActive(..), Channel::Funded(_)) => true,
+		(Action::RedeemTicket(..), Channel::Active(_)) => true,
+		(Action::RedeemTicket(..), Channel::PendingSettlement(_, deadline)) => now <= *deadline,
+		(Action::InitiateSettlement(..), Channel::Active(_)) => true,
+		(Action::Withdraw(..), Channel::PendingSettlement(_, deadline)) => now >= *deadline,
+		_ => false,
+	}
+}
+
+/// Whether `after` is the state that a successful call leaves a channel in.
+fn is_expected_transition(action: &Action, before: &Channel<u128, u64>, after: &Channel<u128, u64>) -> bool {
+	match (action, after) {
+		(Action::Create(..), Channel::Funded(_)) => true,
+		(Action::SetActive(..), Channel::Active(_)) => true,
+		(Action::RedeemTicket(..), Channel::Active(_)) => matches_variant(before, after),
+		(Action::RedeemTicket(..), Channel::PendingSettlement(_, deadline)) => match before {
+			Channel::PendingSettlement(_, old_deadline) => old_deadline == deadline,
+			_ => false,
+		},
+		(Action::InitiateSettlement(..), Channel::PendingSettlement(..)) => true,
+		(Action::Withdraw(..), Channel::Uninitialized) => true,
+		_ => false,
+	}
+}
+
+fn matches_variant(a: &Channel<u128, u64>, b: &Channel<u128, u64>) -> bool {
+	std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// The channel that `action` is applied to.
+fn channel_id(accounts: &Accounts, action: &Action) -> Option<H256> {
+	match *action {
+		Action::Create(a, b, _)
+		| Action::SetActive(a, b)
+		| Action::RedeemTicket(a, b, _)
+		| Action::InitiateSettlement(a, b)
+		| Action::Withdraw(a, b) => Some(Hopr::get_id(&accounts.id(a), &accounts.id(b))),
+		Action::Init(_) | Action::AdvanceTime(_) => None,
+	}
+}
+
+/// Applies `action` and returns the result of calls on a channel.
+fn apply(accounts: &mut Accounts, action: &Action) -> Option<Result> {
+	let res = match *action {
+		Action::Init(a) => {
+			// Fails for registered accounts, which leaves nothing to check.
+			let _ = Hopr::init(Origin::signed(accounts.id(a)), H256::from_slice(accounts.id(a).as_ref()), accounts.secret(a));
+			return None;
+		},
+		Action::Create(a, b, funds) => Hopr::create(Origin::signed(accounts.id(a)), funds.into(), accounts.id(b)),
+		Action::SetActive(a, b) => {
+			let channel_balance = match Hopr::channels(Hopr::get_id(&accounts.id(a), &accounts.id(b))) {
+				Channel::Funded(channel_balance) => channel_balance,
+				_ => ChannelBalance { balance: 0, balance_a: 0 },
+			};
+			let signature = accounts.keys[b].sign(&hopr_primitives::funding_message(&channel_balance));
+			Hopr::set_active(Origin::signed(accounts.id(a)), accounts.id(b), signature)
+		},
+		Action::RedeemTicket(a, b, amount) => {
+			let (s_a, s_b) = (H256::repeat_byte(3), H256::repeat_byte(4));
+			let win_prob = H256::repeat_byte(0xff);
+			// The block number does not change, so tickets never expire.
+			let expiry = 0;
+			// Any account other than the redeemer can be the next node on the path.
+			let downstream = (a + 1) % ACCOUNTS;
+			let ticket = LotteryTicket::new(
+				challenge::<BlakeTwo256>(s_a.as_ref(), s_b.as_ref()),
+				hopr_primitives::next_hop::<BlakeTwo256>(accounts.id(downstream).as_ref()),
+				accounts.secret(a),
+				u128::from(amount),
+				win_prob,
+				expiry,
+			);
+			let signature = accounts.keys[b].sign(&ticket.encode());
+			let acknowledgement = Acknowledgement::new(*ticket.challenge(), s_b);
+			let acknowledgement_signature = accounts.keys[downstream].sign(&hopr_primitives::acknowledgement_message(&acknowledgement));
+			let res = Hopr::redeem_ticket(
+				Origin::signed(accounts.id(a)),
+				signature,
+				accounts.id(b),
+				accounts.secret(a),
+				accounts.pre_image(a),
+				s_a,
+				acknowledgement,
+				acknowledgement_signature,
+				accounts.id(downstream),
+				amount.into(),
+				win_prob,
+				expiry,
+			);
+			if res.is_ok() {
+				accounts.redeemed[a] += 1;
+			}
+			res
+		},
+		Action::InitiateSettlement(a, b) => Hopr::initiate_settlement(Origin::signed(accounts.id(a)), accounts.id(b)),
+		Action::Withdraw(a, b) => Hopr::withdraw(Origin::signed(accounts.id(a)), accounts.id(b)),
+		Action::AdvanceTime(delta) => {
+			set_now(Timestamp::now() + delta);
+			return None;
+		},
+	};
+	Some(res)
+}
+
+/// Checks the invariants that hold between any two calls.
+fn check_invariants(accounts: &Accounts) -> Result {
+	let ids = accounts.ids();
+
+	let total: u128 = ids.iter().map(|id| Balances::free_balance(id) + Balances::reserved_balance(id)).sum();
+	ensure!(total == ENDOWMENT * ACCOUNTS as u128, "Funds were created or lost.");
+
+	for a in &ids {
+		let mut locked = 0u128;
+		for b in ids.iter().filter(|b| *b != a) {
+			let channel_balance = match Hopr::channels(Hopr::get_id(a, b)) {
+				Channel::Uninitialized => continue,
+				Channel::Funded(channel_balance) | Channel::Active(channel_balance) => channel_balance,
+				Channel::PendingSettlement(channel_balance, _) => channel_balance,
+			};
+
+			ensure!(channel_balance.balance_a <= channel_balance.balance, "balance_a exceeds the channel balance.");
+			locked += if Hopr::is_party_a(a, b) {
+				channel_balance.balance_a
+			} else {
+				channel_balance.balance - channel_balance.balance_a
+			};
+		}
+		ensure!(Balances::reserved_balance(a) == locked, "Reserved balance does not match the channel balances.");
+	}
+
+	Ok(())
+}
+
+proptest! {
+	#[test]
+	fn channel_invariants_hold(actions in prop::collection::vec(action(), 1..40)) {
+		let mut accounts = Accounts::new();
+		let mut ext = new_test_ext(&accounts);
+		with_externalities(&mut ext, || {
+			for action in &actions {
+				let channel_id = channel_id(&accounts, action);
+				let before = channel_id.map(Hopr::channels);
+				let now = Timestamp::now();
+
+				if let (Some(res), Some(channel_id), Some(before)) = (apply(&mut accounts, action), channel_id, before) {
+					let after = Hopr::channels(channel_id);
+					if res.is_ok() {
+						prop_assert!(is_legal(action, &before, now), "{:?} succeeded on {:?}", action, before);
+						prop_assert!(is_expected_transition(action, &before, &after), "{:?} turned {:?} into {:?}", action, before, after);
+					} else {
+						prop_assert_eq!(&before, &after, "{:?} failed but changed the channel", action);
+					}
+				}
+
+				let invariants = check_invariants(&accounts);
+				prop_assert!(invariants.is_ok(), "{:?} after {:?}", invariants, action);
+			}
+			Ok(())
+		})?;
+	}
+}
@@ -18,7 +18,7 @@ use runtime_primitives::{
 };
 use client::{
 	block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
-	runtime_api, impl_runtime_apis
+	runtime_api, decl_runtime_apis, impl_runtime_apis
 };
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
@@ -32,6 +32,7 @@ pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
 pub use runtime_primitives::{Permill, Perbill};
 pub use timestamp::BlockPeriod;
+pub use hopr::{MAX_PRE_IMAGE_DEPTH, MAX_PRE_IMAGE_DEPTH_LIMIT, PENDING_WINDOW};
 pub use support::{StorageValue, construct_runtime};
 
 /// The type that is used for identifying authorities.
@@ -55,8 +56,24 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// The type for recording an account's balance.
+pub type Balance = u128;
+
+/// A timestamp: seconds since the unix epoch.
+pub type Moment = u64;
+
 /// Used for the module template in `./hopr.rs`
-mod hopr;
+pub mod hopr;
+
+/// Bridge of the HOPR token on Ethereum in `./bridge.rs`
+pub mod bridge;
+
+/// Registry of relay nodes in `./registry.rs`
+pub mod registry;
+
+/// Benchmarks of the hopr module in `./benchmarks.rs`
+#[cfg(feature = "runtime-benchmarks")]
+pub mod benchmarks;
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -95,8 +112,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("hopr-polkadot"),
 	impl_name: create_runtime_str!("hopr-polkadot"),
 	authoring_version: 3,
-	spec_version: 4,
-	impl_version: 4,
+	spec_version: 12,
+	impl_version: 12,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -162,13 +179,13 @@ impl indices::Trait for Runtime {
 
 impl timestamp::Trait for Runtime {
 	/// A timestamp: seconds since the unix epoch.
-	type Moment = u64;
+	type Moment = Moment;
 	type OnTimestampSet = Aura;
 }
 
 impl balances::Trait for Runtime {
 	/// The type for recording an account's balance.
-	type Balance = u128;
+	type Balance = Balance;
 	/// What to do if an account's free balance gets zeroed.
 	type OnFreeBalanceZero = ();
 	/// What to do if a new account is created.
@@ -193,6 +210,14 @@ impl hopr::Trait for Runtime {
 	// type AccountId = AccountId;
 }
 
+impl bridge::Trait for Runtime {
+	type Event = Event;
+}
+
+impl registry::Trait for Runtime {
+	type Event = Event;
+}
+
 construct_runtime!(
 	pub enum Runtime with Log(InternalLog: DigestItem<Hash, AuthorityId, AuthoritySignature>) where
 		Block = Block,
@@ -206,14 +231,16 @@ construct_runtime!(
 		Indices: indices,
 		Balances: balances,
 		Sudo: sudo,
-		Hopr: hopr::{Module, Call, Storage, Event<T>},
+		Hopr: hopr::{Module, Call, Storage, Config<T>, Event<T>},
+		Bridge: bridge::{Module, Call, Storage, Config<T>, Event<T>},
+		Registry: registry::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
 /// The type used as a helper for interpreting the sender of transactions.
 type Context = system::ChainContext<Runtime>;
 /// The address format for describing accounts.
-type Address = <Indices as StaticLookup>::Source;
+pub type Address = <Indices as StaticLookup>::Source;
 /// Block header type as expected by this runtime.
 pub type Header = generic::Header<BlockNumber, BlakeTwo256, Log>;
 /// Block type as expected by this runtime.
@@ -227,6 +254,42 @@ pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Nonce, Call>;
 /// Executive: handles dispatch to the various modules.
 pub type Executive = executive::Executive<Runtime, Block, Context, Balances, AllModules>;
 
+decl_runtime_apis! {
+	/// The API to query the hopr module from outside of the runtime.
+	pub trait HoprApi {
+		/// The on-chain secret of `account`, or `None` if it has not called `init`.
+		fn on_chain_secret(account: AccountId) -> Option<Hash>;
+		/// The channel with the given id.
+		fn channel(channel_id: Hash) -> hopr::Channel<Balance, Moment>;
+		/// Channels that are not withdrawn yet with their id, in the order they were created.
+		fn channels() -> Vec<(Hash, hopr::Channel<Balance, Moment>)>;
+		/// The current time, against which the end of pending windows is checked.
+		fn now() -> Moment;
+		/// The events that the hopr module deposited in the current block.
+		fn events() -> Vec<hopr::RawEvent<AccountId, Hash, Balance>>;
+		/// Nodes that announced where they can be reached, in the order of their first announcement.
+		fn announced_nodes() -> Vec<(AccountId, hopr::Announcement)>;
+		/// How many hashes a pre-image that redeems a ticket may lie below the on-chain secret.
+		fn max_pre_image_depth() -> u32;
+		/// Code and message of every error that calls of the hopr module fail with.
+		fn errors() -> Vec<(u8, Vec<u8>)>;
+	}
+
+	/// The API to query the registered relay nodes, e.g. for path selection.
+	pub trait RelayRegistryApi {
+		/// Registered relays with their bond, in the order they registered.
+		fn relays() -> Vec<(AccountId, Balance)>;
+		/// Whether `account` is a registered relay.
+		fn is_relay(account: AccountId) -> bool;
+	}
+
+	/// The API to query the index of the next extrinsic of an account.
+	pub trait AccountNonceApi {
+		/// The index that the next extrinsic signed by `account` has to use.
+		fn account_nonce(account: AccountId) -> Nonce;
+	}
+}
+
 // Implement our runtime API endpoints. This is just a bunch of proxying.
 impl_runtime_apis! {
 	impl runtime_api::Core<Block> for Runtime {
@@ -298,4 +361,57 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	impl HoprApi<Block> for Runtime {
+		fn on_chain_secret(account: AccountId) -> Option<Hash> {
+			Hopr::on_chain_secret(&account)
+		}
+
+		fn channel(channel_id: Hash) -> hopr::Channel<Balance, Moment> {
+			Hopr::channels(channel_id)
+		}
+
+		fn channels() -> Vec<(Hash, hopr::Channel<Balance, Moment>)> {
+			Hopr::all_channels()
+		}
+
+		fn now() -> Moment {
+			Timestamp::now()
+		}
+
+		fn events() -> Vec<hopr::RawEvent<AccountId, Hash, Balance>> {
+			System::events().into_iter().filter_map(|record| match record.event {
+				Event::hopr(event) => Some(event),
+				_ => None,
+			}).collect()
+		}
+
+		fn announced_nodes() -> Vec<(AccountId, hopr::Announcement)> {
+			Hopr::announcements()
+		}
+
+		fn max_pre_image_depth() -> u32 {
+			Hopr::max_pre_image_depth()
+		}
+
+		fn errors() -> Vec<(u8, Vec<u8>)> {
+			hopr::Error::ALL.iter().map(|error| (error.code(), error.message().as_bytes().to_vec())).collect()
+		}
+	}
+
+	impl RelayRegistryApi<Block> for Runtime {
+		fn relays() -> Vec<(AccountId, Balance)> {
+			Registry::relays_with_bonds()
+		}
+
+		fn is_relay(account: AccountId) -> bool {
+			Registry::is_relay(&account)
+		}
+	}
+
+	impl AccountNonceApi<Block> for Runtime {
+		fn account_nonce(account: AccountId) -> Nonce {
+			System::account_nonce(account)
+		}
+	}
 }
@@ -0,0 +1,277 @@
+//! Registry of relay nodes.
+//!
+//! Accounts that called `init` on the hopr module register as relays by bonding at least the
+//! minimum bond. The bond is reserved until the relay leaves the registry and the unbonding delay
+//! has passed, so that misbehaving relays can be slashed by other modules through `slash`.
+
+use parity_codec::{Decode, Encode};
+use rstd::prelude::*;
+use runtime_primitives::traits::{CheckedAdd, CheckedSub, Zero};
+use support::{
+	decl_event, decl_module, decl_storage, dispatch::Result, ensure, traits::ReservableCurrency,
+	StorageMap, StorageValue,
+};
+use system::ensure_signed;
+
+#[cfg(test)]
+mod mock;
+
+/// Funds of a former relay that are released at block `until`.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Unbonding<Balance, BlockNumber> {
+	pub amount: Balance,
+	pub until: BlockNumber,
+}
+
+pub trait Trait: crate::hopr::Trait {
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as registry {
+		/// Smallest bond that a relay has to keep.
+		MinimumBond get(minimum_bond) config(): T::Balance;
+		/// Number of blocks between leaving the registry and the release of the bond.
+		UnbondingDelay get(unbonding_delay) config(): T::BlockNumber;
+		/// Registered relays, in the order they registered.
+		Relays get(relays): Vec<T::AccountId>;
+		Bonds get(bond): map T::AccountId => T::Balance;
+		Unbondings get(unbonding): map T::AccountId => Option<Unbonding<T::Balance, T::BlockNumber>>;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event<T>() = default;
+
+		/// Registers the sender as relay with the given bond.
+		pub fn register(origin, bond: T::Balance) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(!Self::is_relay(&sender), "Account is a relay already.");
+			ensure!(<crate::hopr::Module<T>>::on_chain_secret(&sender).is_some(), "Party must have called init() before.");
+			ensure!(bond >= Self::minimum_bond(), "Bond is smaller than the minimum bond.");
+
+			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(&sender, bond)?;
+
+			<Bonds<T>>::insert(&sender, bond);
+			<Relays<T>>::mutate(|relays| relays.push(sender.clone()));
+
+			Self::deposit_event(RawEvent::Registered(sender, bond));
+
+			Ok(())
+		}
+
+		/// Adds `amount` to the bond of the sender.
+		pub fn bond_extra(origin, amount: T::Balance) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(Self::is_relay(&sender), "Account is not a relay.");
+			let bond = Self::bond(&sender).checked_add(&amount).ok_or("Integer error.")?;
+
+			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::reserve(&sender, amount)?;
+			<Bonds<T>>::insert(&sender, bond);
+
+			Self::deposit_event(RawEvent::Bonded(sender, bond));
+
+			Ok(())
+		}
+
+		/// Leaves the registry. The bond is released after the unbonding delay.
+		pub fn unregister(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(Self::is_relay(&sender), "Account is not a relay.");
+
+			Self::remove_relay(&sender)?;
+
+			Ok(())
+		}
+
+		/// Releases the bond of the sender once its unbonding delay has passed.
+		pub fn withdraw_unbonded(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let unbonding = Self::unbonding(&sender).ok_or("Account has no funds unbonding.")?;
+			ensure!(<system::Module<T>>::block_number() >= unbonding.until, "Funds are still unbonding.");
+
+			<Unbondings<T>>::remove(&sender);
+			<balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::unreserve(&sender, unbonding.amount);
+
+			Self::deposit_event(RawEvent::Withdrawn(sender, unbonding.amount));
+
+			Ok(())
+		}
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where
+		<T as system::Trait>::AccountId,
+		<T as system::Trait>::BlockNumber,
+		<T as balances::Trait>::Balance {
+		/// Relay registered with the given bond.
+		Registered(AccountId, Balance),
+		/// Bond of the relay was increased to the given amount.
+		Bonded(AccountId, Balance),
+		/// Relay left the registry, its bond is released at the given block.
+		Unregistered(AccountId, Balance, BlockNumber),
+		/// Released bond was paid out.
+		Withdrawn(AccountId, Balance),
+		/// Relay was slashed by the given amount.
+		Slashed(AccountId, Balance),
+	}
+);
+
+impl<T: Trait> Module<T> {
+	pub fn is_relay(account: &T::AccountId) -> bool {
+		<Bonds<T>>::exists(account)
+	}
+
+	/// Registered relays with their bond.
+	pub fn relays_with_bonds() -> Vec<(T::AccountId, T::Balance)> {
+		Self::relays().into_iter().map(|relay| {
+			let bond = Self::bond(&relay);
+			(relay, bond)
+		}).collect()
+	}
+
+	/// Slashes up to `amount` of the bond of `account`, including funds that are unbonding, and
+	/// returns the slashed amount.
+	///
+	/// A relay whose bond falls below the minimum bond is removed from the registry.
+	pub fn slash(account: &T::AccountId, amount: T::Balance) -> T::Balance {
+		let bond = Self::bond(account);
+		let unbonding = Self::unbonding(account);
+		let unbonding_amount = unbonding.as_ref().map(|u| u.amount).unwrap_or_else(Zero::zero);
+
+		let from_bond = if amount < bond { amount } else { bond };
+		let rest = amount - from_bond;
+		let from_unbonding = if rest < unbonding_amount { rest } else { unbonding_amount };
+		let slashed = from_bond + from_unbonding;
+		if slashed.is_zero() {
+			return slashed;
+		}
+
+		let _ = <balances::Module<T> as ReservableCurrency<<T as system::Trait>::AccountId>>::slash_reserved(account, slashed);
+
+		if let Some(unbonding) = unbonding {
+			if from_unbonding == unbonding.amount {
+				<Unbondings<T>>::remove(account);
+			} else {
+				<Unbondings<T>>::insert(account, Unbonding { amount: unbonding.amount - from_unbonding, ..unbonding });
+			}
+		}
+		if Self::is_relay(account) {
+			<Bonds<T>>::insert(account, bond - from_bond);
+		}
+
+		Self::deposit_event(RawEvent::Slashed(account.clone(), slashed));
+
+		if Self::is_relay(account) && bond - from_bond < Self::minimum_bond() {
+			// Cannot fail, as the account is a relay and the unbonding amount does not overflow.
+			let _ = Self::remove_relay(account);
+		}
+
+		slashed
+	}
+
+	/// Removes `account` from the registry and starts unbonding its bond.
+	fn remove_relay(account: &T::AccountId) -> Result {
+		let bond = Self::bond(account);
+		let until = <system::Module<T>>::block_number() + Self::unbonding_delay();
+		let amount = match Self::unbonding(account) {
+			Some(unbonding) => unbonding.amount.checked_add(&bond).ok_or("Integer error.")?,
+			None => bond,
+		};
+
+		<Bonds<T>>::remove(account);
+		<Relays<T>>::mutate(|relays| relays.retain(|relay| relay != account));
+		<Unbondings<T>>::insert(account, Unbonding { amount, until });
+
+		Self::deposit_event(RawEvent::Unregistered(account.clone(), bond, until));
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::mock::*;
+	use super::*;
+	use runtime_io::with_externalities;
+	use support::{assert_noop, assert_ok};
+
+	#[test]
+	fn registers_relays_with_bond() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_noop!(Registry::register(Origin::signed(account("Charlie")), MINIMUM_BOND), "Party must have called init() before.");
+			assert_noop!(Registry::register(Origin::signed(account("Alice")), MINIMUM_BOND - 1), "Bond is smaller than the minimum bond.");
+
+			assert_ok!(Registry::register(Origin::signed(account("Alice")), MINIMUM_BOND));
+			assert_noop!(Registry::register(Origin::signed(account("Alice")), MINIMUM_BOND), "Account is a relay already.");
+			assert_ok!(Registry::register(Origin::signed(account("Bob")), 2 * MINIMUM_BOND));
+
+			assert_eq!(Registry::relays_with_bonds(), vec![(account("Alice"), MINIMUM_BOND), (account("Bob"), 2 * MINIMUM_BOND)]);
+			assert_eq!(Balances::reserved_balance(&account("Alice")), MINIMUM_BOND);
+			assert_eq!(registry_events().last(), Some(&RawEvent::Registered(account("Bob"), 2 * MINIMUM_BOND)));
+
+			assert_ok!(Registry::bond_extra(Origin::signed(account("Alice")), 5));
+			assert_eq!(Registry::bond(&account("Alice")), MINIMUM_BOND + 5);
+			assert_eq!(Balances::reserved_balance(&account("Alice")), MINIMUM_BOND + 5);
+		});
+	}
+
+	#[test]
+	fn releases_bond_after_unbonding_delay() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			assert_ok!(Registry::register(Origin::signed(account("Alice")), MINIMUM_BOND));
+
+			assert_ok!(Registry::unregister(Origin::signed(account("Alice"))));
+			assert!(Registry::relays().is_empty());
+			assert_eq!(registry_events().last(), Some(&RawEvent::Unregistered(account("Alice"), MINIMUM_BOND, 1 + UNBONDING_DELAY)));
+
+			System::set_block_number(UNBONDING_DELAY);
+			assert_noop!(Registry::withdraw_unbonded(Origin::signed(account("Alice"))), "Funds are still unbonding.");
+
+			System::set_block_number(1 + UNBONDING_DELAY);
+			assert_ok!(Registry::withdraw_unbonded(Origin::signed(account("Alice"))));
+			assert_eq!(Balances::reserved_balance(&account("Alice")), 0);
+			assert_eq!(Balances::free_balance(&account("Alice")), ENDOWMENT);
+			assert_noop!(Registry::withdraw_unbonded(Origin::signed(account("Alice"))), "Account has no funds unbonding.");
+		});
+	}
+
+	#[test]
+	fn slashing_removes_relay_below_minimum_bond() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_ok!(Registry::register(Origin::signed(account("Alice")), MINIMUM_BOND + 10));
+
+			assert_eq!(Registry::slash(&account("Alice"), 10), 10);
+			assert!(Registry::is_relay(&account("Alice")));
+
+			assert_eq!(Registry::slash(&account("Alice"), 1), 1);
+			assert!(!Registry::is_relay(&account("Alice")));
+			assert_eq!(Registry::unbonding(&account("Alice")).map(|u| u.amount), Some(MINIMUM_BOND - 1));
+
+			// Unbonding funds can still be slashed, but not more than that.
+			assert_eq!(Registry::slash(&account("Alice"), MINIMUM_BOND), MINIMUM_BOND - 1);
+			assert_eq!(Registry::unbonding(&account("Alice")), None);
+			assert_eq!(Balances::reserved_balance(&account("Alice")), 0);
+			assert_eq!(Balances::free_balance(&account("Alice")), ENDOWMENT - MINIMUM_BOND - 10);
+		});
+	}
+
+	#[test]
+	fn slashing_leaves_channel_funds_alone() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_ok!(Registry::register(Origin::signed(account("Alice")), MINIMUM_BOND));
+			assert_ok!(Hopr::create(Origin::signed(account("Alice")), 7, account("Bob")));
+
+			assert_eq!(Registry::slash(&account("Alice"), 2 * MINIMUM_BOND), MINIMUM_BOND);
+			assert_eq!(Balances::reserved_balance(&account("Alice")), 7);
+		});
+	}
+}
@@ -0,0 +1,135 @@
+//! Mock runtime for the tests of the registry module.
+//!
+//! Alice, Bob and Charlie are endowed with `ENDOWMENT`, Alice and Bob have called `init` on the
+//! hopr module.
+
+use super::*;
+
+use crate::hopr;
+use primitives::{sr25519, Blake2Hasher, Pair, H256};
+use runtime_io::TestExternalities;
+use runtime_primitives::{
+	testing::{Digest, DigestItem, Header},
+	traits::{BlakeTwo256, IdentityLookup, Verify},
+	BuildStorage,
+};
+use support::{impl_outer_event, impl_outer_origin};
+
+pub type AccountId = <sr25519::Signature as Verify>::Signer;
+
+impl_outer_origin! {
+	pub enum Origin for RegistryTest {}
+}
+
+mod registry {
+	pub use crate::registry::Event;
+}
+
+impl_outer_event! {
+	pub enum TestEvent for RegistryTest {
+		balances<T>,
+		hopr<T>,
+		registry<T>,
+	}
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct RegistryTest;
+impl system::Trait for RegistryTest {
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type Digest = Digest;
+	type AccountId = AccountId;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = TestEvent;
+	type Log = DigestItem;
+}
+
+impl timestamp::Trait for RegistryTest {
+	type Moment = u64;
+	type OnTimestampSet = ();
+}
+
+impl balances::Trait for RegistryTest {
+	type Balance = u128;
+	type OnFreeBalanceZero = ();
+	type OnNewAccount = ();
+	type Event = TestEvent;
+
+	type TransactionPayment = ();
+	type DustRemoval = ();
+	type TransferPayment = ();
+}
+
+impl hopr::Trait for RegistryTest {
+	type Event = TestEvent;
+}
+
+impl Trait for RegistryTest {
+	type Event = TestEvent;
+}
+
+pub type Registry = Module<RegistryTest>;
+pub type Hopr = hopr::Module<RegistryTest>;
+pub type Balances = balances::Module<RegistryTest>;
+pub type System = system::Module<RegistryTest>;
+
+pub const ENDOWMENT: u128 = 1_000;
+pub const MINIMUM_BOND: u128 = 100;
+pub const UNBONDING_DELAY: u64 = 10;
+
+pub fn new_test_ext() -> TestExternalities<Blake2Hasher> {
+	let mut t = system::GenesisConfig::<RegistryTest>::default().build_storage().unwrap().0;
+	t.extend(
+		balances::GenesisConfig::<RegistryTest> {
+			transaction_base_fee: 0,
+			transaction_byte_fee: 0,
+			existential_deposit: 0,
+			transfer_fee: 0,
+			creation_fee: 0,
+			balances: ["Alice", "Bob", "Charlie"].iter().map(|name| (account(name), ENDOWMENT)).collect(),
+			vesting: vec![],
+		}
+		.build_storage()
+		.unwrap()
+		.0,
+	);
+	t.extend(
+		hopr::GenesisConfig::<RegistryTest> {
+			pending_window: hopr::PENDING_WINDOW,
+			max_pre_image_depth: hopr::MAX_PRE_IMAGE_DEPTH,
+			states: ["Alice", "Bob"].iter().map(|name| {
+				(account(name), H256::from_slice(account(name).as_ref()), H256::repeat_byte(1))
+			}).collect(),
+		}
+		.build_storage()
+		.unwrap()
+		.0,
+	);
+	t.extend(
+		GenesisConfig::<RegistryTest> {
+			minimum_bond: MINIMUM_BOND,
+			unbonding_delay: UNBONDING_DELAY,
+		}
+		.build_storage()
+		.unwrap()
+		.0,
+	);
+	t.into()
+}
+
+pub fn account(name: &str) -> AccountId {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed").public()
+}
+
+/// The events of the registry module that were deposited so far.
+pub fn registry_events() -> Vec<RawEvent<AccountId, u64, u128>> {
+	System::events().into_iter().filter_map(|record| match record.event {
+		TestEvent::registry(event) => Some(event),
+		_ => None,
+	}).collect()
+}
@@ -1,8 +1,12 @@
+use crate::params::GenesisParams;
 use hopr_polkadot_runtime::{
-	AccountId, BalancesConfig, ConsensusConfig, GenesisConfig, IndicesConfig, SudoConfig,
-	TimestampConfig,
+	AccountId, BalancesConfig, BridgeConfig, ConsensusConfig, GenesisConfig, HoprConfig, IndicesConfig,
+	RegistryConfig, SudoConfig, TimestampConfig, MAX_PRE_IMAGE_DEPTH, PENDING_WINDOW,
 };
-use primitives::{ed25519, sr25519, Pair};
+use primitives::{crypto::Ss58Codec, ed25519, sr25519, Pair};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use substrate_service;
 
 use ed25519::Public as AuthorityId;
@@ -24,85 +28,92 @@ pub enum Alternative {
 	LocalTestnet,
 }
 
-fn authority_key(s: &str) -> AuthorityId {
-	ed25519::Pair::from_string(&format!("//{}", s), None)
-		.expect("static values are valid; qed")
-		.public()
+/// Accounts and amounts that make up the genesis of the built-in chains.
+///
+/// Keys are given as secret URIs such as `//Alice`, endowed accounts and the sudo key
+/// may also be given as SS58 addresses.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GenesisAccounts {
+	/// Aura authorities.
+	pub authorities: Vec<String>,
+	/// Accounts that receive `endowment` at genesis.
+	pub endowed_accounts: Vec<String>,
+	/// Account that holds the sudo key.
+	pub sudo_key: String,
+	/// Free balance of every endowed account.
+	pub endowment: u128,
 }
 
-fn _account_key(s: &str) -> AccountId {
-	sr25519::Pair::from_string(&format!("//{}", s), None)
-		.expect("static values are valid; qed")
-		.public()
+/// Default endowment of every endowed account.
+const DEFAULT_ENDOWMENT: u128 = 1 << 60;
+
+/// Smallest bond of a relay node.
+const MINIMUM_RELAY_BOND: u128 = 1 << 40;
+
+/// Blocks until the bond of a relay that left the registry is released, a day of 10 second blocks.
+const RELAY_UNBONDING_DELAY: u64 = 8_640;
+
+impl GenesisAccounts {
+	fn new(authorities: &[&str], endowed_accounts: &[&str], sudo_key: &str) -> Self {
+		GenesisAccounts {
+			authorities: authorities.iter().map(|s| s.to_string()).collect(),
+			endowed_accounts: endowed_accounts.iter().map(|s| s.to_string()).collect(),
+			sudo_key: sudo_key.to_string(),
+			endowment: DEFAULT_ENDOWMENT,
+		}
+	}
+
+	/// Reads genesis accounts from a JSON file.
+	pub fn from_json_file(path: &Path) -> Result<Self, String> {
+		let file = File::open(path)
+			.map_err(|e| format!("Error opening genesis accounts file `{}`: {}", path.display(), e))?;
+		serde_json::from_reader(file)
+			.map_err(|e| format!("Error parsing genesis accounts file `{}`: {}", path.display(), e))
+	}
+
+	/// Fails on invalid keys, which would otherwise only fail once the genesis gets built.
+	fn check(&self) -> Result<(), String> {
+		self.authority_keys()?;
+		self.endowed_account_keys()?;
+		account_key(&self.sudo_key)?;
+		Ok(())
+	}
+
+	fn authority_keys(&self) -> Result<Vec<AuthorityId>, String> {
+		self.authorities.iter().map(|uri| authority_key(uri)).collect()
+	}
+
+	fn endowed_account_keys(&self) -> Result<Vec<AccountId>, String> {
+		self.endowed_accounts.iter().map(|uri| account_key(uri)).collect()
+	}
 }
 
-fn my_account_key(seed: [u8; 32]) -> AccountId {
-	println!("{:?}", sr25519::Pair::from_seed(seed).public());
-	sr25519::Pair::from_seed(seed).public()
+fn authority_key(uri: &str) -> Result<AuthorityId, String> {
+	ed25519::Pair::from_string(uri, None)
+		.map(|pair| pair.public())
+		.map_err(|e| format!("Invalid authority key `{}`: {:?}", uri, e))
+}
+
+fn account_key(uri: &str) -> Result<AccountId, String> {
+	if let Ok(account) = AccountId::from_ss58check(uri) {
+		return Ok(account);
+	}
+	sr25519::Pair::from_string(uri, None)
+		.map(|pair| pair.public())
+		.map_err(|e| format!("Invalid account `{}`: {:?}", uri, e))
 }
 
 impl Alternative {
 	/// Get an actual chain config from one of the alternatives.
-	pub(crate) fn load(self) -> Result<ChainSpec, String> {
+	pub fn load(self) -> Result<ChainSpec, String> {
+		self.genesis_accounts().check()?;
+
 		Ok(match self {
 			Alternative::Development => ChainSpec::from_genesis(
 				"Development",
 				"dev",
-				|| {
-					testnet_genesis(
-						vec![authority_key("Alice")],
-						vec![
-							// Alice
-							my_account_key([
-								179, 250, 241, 51, 4, 74, 235, 236, 189, 136, 113, 169, 32, 129,
-								135, 131, 248, 227, 232, 9, 164, 37, 241, 49, 4, 100, 146, 146, 81,
-								16, 235, 192,
-							]),
-							// Bob
-							my_account_key([
-								92, 184, 239, 54, 33, 217, 77, 221, 42, 146, 115, 208, 8, 121, 247,
-								77, 22, 150, 119, 174, 141, 58, 197, 86, 220, 44, 141, 25, 78, 125,
-								133, 200,
-							]),
-							// Charlie
-							my_account_key([
-								6, 65, 105, 137, 215, 135, 117, 129, 163, 126, 249, 126, 14, 39,
-								82, 123, 17, 57, 246, 246, 58, 66, 192, 87, 230, 15, 140, 69, 170,
-								80, 17, 169,
-							]),
-							// Dave
-							my_account_key([
-								113, 155, 140, 235, 183, 41, 119, 207, 73, 191, 154, 176, 163, 187,
-								131, 252, 23, 40, 32, 211, 203, 180, 232, 142, 111, 133, 132, 121,
-								246, 193, 228, 212,
-							]),
-							// Ed
-							my_account_key([
-								94, 136, 136, 113, 183, 90, 5, 193, 102, 64, 43, 51, 74, 231, 121,
-								188, 160, 234, 98, 115, 116, 154, 36, 220, 52, 5, 27, 102, 141,
-								253, 233, 169,
-							]),
-							// Fred
-							my_account_key([
-								94, 136, 136, 113, 183, 90, 5, 193, 102, 64, 43, 51, 74, 231, 121,
-								188, 160, 234, 98, 115, 116, 154, 36, 220, 52, 5, 27, 102, 141,
-								253, 233, 169,
-							]),
-							// George
-							my_account_key([
-								66, 176, 165, 38, 87, 35, 196, 6, 36, 89, 13, 175, 226, 10, 123,
-								214, 71, 119, 2, 207, 31, 101, 233, 72, 6, 15, 84, 64, 74, 60, 123,
-								92,
-							]),
-						],
-						// Alice
-						my_account_key([
-							179, 250, 241, 51, 4, 74, 235, 236, 189, 136, 113, 169, 32, 129, 135,
-							131, 248, 227, 232, 9, 164, 37, 241, 49, 4, 100, 146, 146, 81, 16, 235,
-							192,
-						]),
-					)
-				},
+				|| testnet_genesis(&Alternative::Development.genesis_accounts()),
 				vec![],
 				None,
 				None,
@@ -112,61 +123,7 @@ impl Alternative {
 			Alternative::LocalTestnet => ChainSpec::from_genesis(
 				"Local Testnet",
 				"local_testnet",
-				|| {
-					testnet_genesis(
-						vec![authority_key("Alice"), authority_key("Bob")],
-						vec![
-							// Alice
-							my_account_key([
-								179, 250, 241, 51, 4, 74, 235, 236, 189, 136, 113, 169, 32, 129,
-								135, 131, 248, 227, 232, 9, 164, 37, 241, 49, 4, 100, 146, 146, 81,
-								16, 235, 192,
-							]),
-							// Bob
-							my_account_key([
-								92, 184, 239, 54, 33, 217, 77, 221, 42, 146, 115, 208, 8, 121, 247,
-								77, 22, 150, 119, 174, 141, 58, 197, 86, 220, 44, 141, 25, 78, 125,
-								133, 200,
-							]),
-							// Charlie
-							my_account_key([
-								6, 65, 105, 137, 215, 135, 117, 129, 163, 126, 249, 126, 14, 39,
-								82, 123, 17, 57, 246, 246, 58, 66, 192, 87, 230, 15, 140, 69, 170,
-								80, 17, 169,
-							]),
-							// Dave
-							my_account_key([
-								113, 155, 140, 235, 183, 41, 119, 207, 73, 191, 154, 176, 163, 187,
-								131, 252, 23, 40, 32, 211, 203, 180, 232, 142, 111, 133, 132, 121,
-								246, 193, 228, 212,
-							]),
-							// Ed
-							my_account_key([
-								94, 136, 136, 113, 183, 90, 5, 193, 102, 64, 43, 51, 74, 231, 121,
-								188, 160, 234, 98, 115, 116, 154, 36, 220, 52, 5, 27, 102, 141,
-								253, 233, 169,
-							]),
-							// Fred
-							my_account_key([
-								94, 136, 136, 113, 183, 90, 5, 193, 102, 64, 43, 51, 74, 231, 121,
-								188, 160, 234, 98, 115, 116, 154, 36, 220, 52, 5, 27, 102, 141,
-								253, 233, 169,
-							]),
-							// George
-							my_account_key([
-								66, 176, 165, 38, 87, 35, 196, 6, 36, 89, 13, 175, 226, 10, 123,
-								214, 71, 119, 2, 207, 31, 101, 233, 72, 6, 15, 84, 64, 74, 60, 123,
-								92,
-							]),
-						],
-						// Alice
-						my_account_key([
-							179, 250, 241, 51, 4, 74, 235, 236, 189, 136, 113, 169, 32, 129, 135,
-							131, 248, 227, 232, 9, 164, 37, 241, 49, 4, 100, 146, 146, 81, 16, 235,
-							192,
-						]),
-					)
-				},
+				|| testnet_genesis(&Alternative::LocalTestnet.genesis_accounts()),
 				vec![],
 				None,
 				None,
@@ -176,20 +133,84 @@ impl Alternative {
 		})
 	}
 
+	/// The chain config of this alternative with `accounts` in its genesis instead of the defaults.
+	pub fn load_with_accounts(self, accounts: &GenesisAccounts) -> Result<ChainSpec, String> {
+		accounts.check()?;
+
+		// `ChainSpec::from_genesis` only takes a plain function, so the genesis is replaced in the
+		// JSON of the spec, which keeps the name, id and other fields of the built-in chain.
+		let mut json: serde_json::Value = serde_json::from_str(&self.load()?.to_json(false)?)
+			.map_err(|e| format!("Error reading built-in chain spec: {}", e))?;
+		json["genesis"] = serde_json::json!({ "runtime": testnet_genesis(accounts) });
+		let json = serde_json::to_vec(&json).map_err(|e| format!("Error writing chain spec: {}", e))?;
+
+		// The spec reads its genesis lazily, so it needs the JSON for the rest of the process.
+		ChainSpec::from_embedded(Box::leak(json.into_boxed_slice()))
+	}
+
+	/// The default genesis accounts of this chain.
+	fn genesis_accounts(&self) -> GenesisAccounts {
+		let endowed_accounts = ["//Alice", "//Bob", "//Charlie", "//Dave", "//Ed", "//Fred", "//George"];
+		match self {
+			Alternative::Development => GenesisAccounts::new(&["//Alice"], &endowed_accounts, "//Alice"),
+			Alternative::LocalTestnet => GenesisAccounts::new(&["//Alice", "//Bob"], &endowed_accounts, "//Alice"),
+		}
+	}
+
 	pub(crate) fn from(s: &str) -> Option<Self> {
 		match s {
 			"dev" => Some(Alternative::Development),
-			"" | "local" => Some(Alternative::LocalTestnet),
+			"" | "local" | "local_testnet" => Some(Alternative::LocalTestnet),
 			_ => None,
 		}
 	}
+
+	/// The ids of all built-in chains, as accepted by `from`.
+	pub(crate) fn ids() -> &'static [&'static str] {
+		&["dev", "local"]
+	}
 }
 
-fn testnet_genesis(
-	initial_authorities: Vec<AuthorityId>,
-	endowed_accounts: Vec<AccountId>,
-	root_key: AccountId,
-) -> GenesisConfig {
+/// Loads a chain specification either from one of the built-in alternatives or,
+/// if `id` is not one of them, from the JSON file that `id` points to.
+pub(crate) fn load_spec(id: &str) -> Result<Option<ChainSpec>, String> {
+	Ok(match Alternative::from(id) {
+		Some(spec) => Some(spec.load()?),
+		None if Path::new(id).is_file() => Some(ChainSpec::from_json_file(PathBuf::from(id))?),
+		None => None,
+	})
+}
+
+/// Loads the built-in chain that `chain`, the value of `--chain`, selects with the genesis accounts
+/// given on the command line.
+///
+/// Returns `None` if no genesis accounts were given, and fails if `chain` is not a built-in chain,
+/// e.g. a chain spec file, whose genesis the flags would otherwise silently replace or be ignored for.
+pub(crate) fn load_spec_with_accounts(chain: &str, params: &GenesisParams) -> Result<Option<ChainSpec>, String> {
+	if params.is_empty() {
+		return Ok(None);
+	}
+
+	let alternative = Alternative::from(chain).ok_or_else(|| format!(
+		"Genesis accounts can only be set for the built-in chains {:?}, not for `{}`.",
+		Alternative::ids(),
+		chain,
+	))?;
+
+	let mut accounts = match &params.genesis_accounts {
+		Some(path) => GenesisAccounts::from_json_file(path)?,
+		None => alternative.genesis_accounts(),
+	};
+	params.apply(&mut accounts);
+
+	alternative.load_with_accounts(&accounts).map(Some)
+}
+
+fn testnet_genesis(accounts: &GenesisAccounts) -> GenesisConfig {
+	let initial_authorities = accounts.authority_keys().expect("keys are checked in `Alternative::load`; qed");
+	let endowed_accounts = accounts.endowed_account_keys().expect("keys are checked in `Alternative::load`; qed");
+	let root_key = account_key(&accounts.sudo_key).expect("keys are checked in `Alternative::load`; qed");
+
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
 			code: include_bytes!("../runtime/wasm/target/wasm32-unknown-unknown/release/hopr_polkadot_runtime_wasm.compact.wasm").to_vec(),
@@ -208,11 +229,25 @@ fn testnet_genesis(
 			existential_deposit: 500,
 			transfer_fee: 0,
 			creation_fee: 0,
-			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60)).collect(),
+			balances: endowed_accounts.iter().cloned().map(|k|(k, accounts.endowment)).collect(),
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+		hopr: Some(HoprConfig {
+			pending_window: PENDING_WINDOW,
+			max_pre_image_depth: MAX_PRE_IMAGE_DEPTH,
+			states: vec![],
+		}),
+		// Relayers are registered by the sudo key.
+		bridge: Some(BridgeConfig {
+			relayers: vec![],
+			threshold: 0,
+		}),
+		registry: Some(RegistryConfig {
+			minimum_bond: MINIMUM_RELAY_BOND,
+			unbonding_delay: RELAY_UNBONDING_DELAY,
+		}),
 	}
 }
@@ -1,12 +1,25 @@
-use crate::service;
+use crate::{indexer, light, metrics, redeemer, service, ticket};
 use futures::{future, Future, sync::oneshot};
 use std::cell::RefCell;
+use std::ffi::OsString;
+use std::fs;
 use tokio::runtime::Runtime;
 pub use substrate_cli::{VersionInfo, IntoExit, error};
-use substrate_cli::{informant, parse_and_execute, NoCustom};
+use substrate_cli::{informant, parse_and_execute};
 use substrate_service::{ServiceFactory, Roles as ServiceRoles};
-use crate::chain_spec;
+use crate::chain_spec::{self, load_spec};
+#[cfg(feature = "runtime-benchmarks")]
+use crate::params::BenchmarkCmd;
+use crate::params::{CustomSubcommands, ExportSpecCmd, GenesisParams, HoprParams, RunParams};
+use crate::ticket::TicketStore;
+use hopr_polkadot_runtime::AccountId;
+use primitives::{sr25519, Pair};
+use std::path::PathBuf;
 use std::ops::Deref;
+#[cfg(feature = "runtime-benchmarks")]
+use std::time::Duration;
+#[cfg(feature = "runtime-benchmarks")]
+use hopr_polkadot_runtime::benchmarks;
 use log::info;
 
 /// Parse command line arguments into service configuration.
@@ -15,9 +28,18 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 	T: Into<std::ffi::OsString> + Clone,
 	E: IntoExit,
 {
-	parse_and_execute::<service::Factory, NoCustom, NoCustom, _, _, _, _, _>(
+	let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+	// The genesis flags depend on what `--chain` selects, not on the id of the loaded spec, which a
+	// chain spec file exported from a built-in chain shares with it.
+	let chain = chain_arg(&args);
+
+	let ret = parse_and_execute::<service::Factory, CustomSubcommands, RunParams, _, _, _, _, _>(
 		load_spec, &version, "substrate-node", args, exit,
-	 	|exit, _custom_args, config| {
+	 	|exit, run_params, mut config| {
+			if let Some(spec) = chain_spec::load_spec_with_accounts(&chain, &run_params.genesis)? {
+				config.chain_spec = spec;
+			}
+
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by {}, 2017, 2018", version.author);
@@ -27,26 +49,162 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 			let runtime = Runtime::new().map_err(|e| format!("{:?}", e))?;
 			let executor = runtime.executor();
 			match config.roles {
-				ServiceRoles::LIGHT => run_until_exit(
-					runtime,
-				 	service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
-					exit
-				),
-				_ => run_until_exit(
-					runtime,
-					service::Factory::new_full(config, executor).map_err(|e| format!("{:?}", e))?,
-					exit
-				),
+				ServiceRoles::LIGHT => {
+					let follow = light_account(&run_params.hopr)?;
+					let service = service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?;
+					if let Some((account, counterparties)) = follow {
+						light::start(&service, account, counterparties);
+					}
+					run_until_exit(runtime, service, exit)
+				},
+				_ => {
+					let hopr = HoprSetup::new(&run_params.hopr, &config.database_path)?;
+					let service = service::Factory::new_full(config, executor.clone()).map_err(|e| format!("{:?}", e))?;
+					if let Some(hopr) = hopr {
+						redeemer::start(&service, hopr.key, hopr.tickets, &executor);
+					}
+					if let Some(addr) = run_params.metrics.addr() {
+						metrics::start(&service, addr, &executor)?;
+					}
+					if let Some(path) = &run_params.index_db {
+						indexer::start(&service, path)?;
+					}
+					run_until_exit(runtime, service, exit)
+				},
 			}.map_err(|e| format!("{:?}", e))
 		}
-	).map_err(Into::into).map(|_| ())
+	)?;
+
+	match ret {
+		Some(CustomSubcommands::ExportSpec(cmd)) => export_spec(cmd),
+		Some(CustomSubcommands::Ticket(cmd)) => ticket::run(cmd),
+		#[cfg(feature = "runtime-benchmarks")]
+		Some(CustomSubcommands::Benchmark(cmd)) => benchmark(cmd),
+		None => Ok(()),
+	}
+}
+
+/// The chain that `args` select, as the chain spec loader receives it: the value of `--chain`, or
+/// `dev` with `--dev`, or the empty default.
+fn chain_arg(args: &[OsString]) -> String {
+	let mut chain = None;
+	let mut dev = false;
+	let mut args = args.iter().map(|arg| arg.to_string_lossy());
+	while let Some(arg) = args.next() {
+		if arg == "--chain" {
+			chain = args.next().map(|value| value.into_owned());
+		} else if arg.starts_with("--chain=") {
+			chain = Some(arg["--chain=".len()..].to_string());
+		} else if arg == "--dev" {
+			dev = true;
+		}
+	}
+	chain.unwrap_or_else(|| if dev { "dev".into() } else { String::new() })
+}
+
+/// The hopr account that the node acts for.
+struct HoprSetup {
+	key: sr25519::Pair,
+	tickets: TicketStore,
+}
+
+impl HoprSetup {
+	/// Returns `None` if no hopr key was given.
+	fn new(params: &HoprParams, database_path: &str) -> Result<Option<Self>, String> {
+		let key = match &params.hopr_key {
+			Some(uri) => sr25519::Pair::from_string(uri, None)
+				.map_err(|e| format!("Invalid hopr key: {:?}", e))?,
+			None => return Ok(None),
+		};
+
+		let tickets_dir = params.tickets_dir.clone().unwrap_or_else(|| {
+			let chain_dir = PathBuf::from(database_path).parent().map(|p| p.to_path_buf()).unwrap_or_default();
+			chain_dir.join("hopr").join("tickets")
+		});
+		let tickets = TicketStore::open(&tickets_dir)
+			.map_err(|e| format!("Error opening tickets directory {}: {}", tickets_dir.display(), e))?;
+
+		Ok(Some(HoprSetup { key, tickets }))
+	}
 }
 
-fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
-	Ok(match chain_spec::Alternative::from(id) {
-		Some(spec) => Some(spec.load()?),
-		None => None,
-	})
+/// The hopr account and counterparties that a light node follows, if a hopr key was given.
+fn light_account(params: &HoprParams) -> Result<Option<(AccountId, Vec<AccountId>)>, String> {
+	let account = match &params.hopr_key {
+		Some(uri) => sr25519::Pair::from_string(uri, None)
+			.map_err(|e| format!("Invalid hopr key: {:?}", e))?
+			.public(),
+		None => return Ok(None),
+	};
+	let counterparties = params.counterparties.iter()
+		.map(|counterparty| ticket::parse_public(counterparty))
+		.collect::<Result<_, _>>()?;
+
+	Ok(Some((account, counterparties)))
+}
+
+/// Writes the chain spec selected by `cmd` as JSON, either to the given file or to stdout.
+fn export_spec(cmd: ExportSpecCmd) -> error::Result<()> {
+	let spec = resolve_spec(&cmd.chain, &cmd.genesis)?;
+	let json = spec.to_json(cmd.raw)?;
+
+	match cmd.output {
+		Some(path) => {
+			fs::write(&path, json)?;
+			info!("Exported chain spec `{}` to {}", spec.id(), path.display());
+		},
+		None => println!("{}", json),
+	}
+
+	Ok(())
+}
+
+/// Runs the benchmarks of the hopr calls in the native runtime and prints the measurements.
+#[cfg(feature = "runtime-benchmarks")]
+fn benchmark(cmd: BenchmarkCmd) -> error::Result<()> {
+	let results = match &cmd.extrinsic {
+		Some(name) => vec![benchmarks::run(name, cmd.repeat)?],
+		None => benchmarks::run_all(cmd.repeat)?,
+	};
+
+	println!(
+		"{:<20} {:>8} {:>10} {:>10} {:>10} {:>6} {:>7} {:>8} {:>6}",
+		"extrinsic", "repeat", "min (µs)", "mean (µs)", "max (µs)", "reads", "writes", "removals", "bytes",
+	);
+	for result in results {
+		println!(
+			"{:<20} {:>8} {:>10} {:>10} {:>10} {:>6} {:>7} {:>8} {:>6}",
+			result.name,
+			result.repetitions,
+			micros(result.min),
+			micros(result.mean),
+			micros(result.max),
+			result.reads,
+			result.writes,
+			result.removals,
+			result.bytes_written,
+		);
+	}
+
+	Ok(())
+}
+
+/// The built-in chain `chain` with the given genesis accounts, or the chain spec at that path.
+fn resolve_spec(chain: &str, genesis: &GenesisParams) -> error::Result<chain_spec::ChainSpec> {
+	let spec = match chain_spec::load_spec_with_accounts(chain, genesis)? {
+		Some(spec) => Some(spec),
+		None => load_spec(chain)?,
+	};
+	Ok(spec.ok_or_else(|| format!(
+		"Unknown chain spec `{}`, expected one of {:?} or the path to a JSON file.",
+		chain,
+		chain_spec::Alternative::ids(),
+	))?)
+}
+
+#[cfg(feature = "runtime-benchmarks")]
+fn micros(duration: Duration) -> u64 {
+	duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros())
 }
 
 fn run_until_exit<T, C, E>(
@@ -92,3 +250,20 @@ impl IntoExit for Exit {
 		exit.map_err(drop)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chain(args: &[&str]) -> String {
+		chain_arg(&args.iter().map(OsString::from).collect::<Vec<_>>())
+	}
+
+	#[test]
+	fn chain_arg_follows_chain_and_dev_flags() {
+		assert_eq!(chain(&["hopr-polkadot"]), "");
+		assert_eq!(chain(&["hopr-polkadot", "--dev"]), "dev");
+		assert_eq!(chain(&["hopr-polkadot", "--chain", "my.json", "--endowment", "1"]), "my.json");
+		assert_eq!(chain(&["hopr-polkadot", "--chain=local", "--dev"]), "local");
+	}
+}
@@ -0,0 +1,469 @@
+//! Writes the history of hopr channels into a SQLite database.
+//!
+//! Every imported block is indexed with its hopr calls and events, so that blocks of every fork
+//! stay in the database. The `canonical` flag of the blocks follows the best chain: on a re-org,
+//! the retracted blocks are unset and the enacted ones set, so queries that join on
+//! `blocks.canonical = 1` only see the history of the best chain.
+//!
+//! Calls are recorded as they appear in the block body, whether or not they succeeded. The
+//! events are what the calls actually changed.
+
+use crate::service::Factory;
+use hopr_polkadot_runtime::{
+	hopr::{self, RawEvent}, opaque::Block, AccountId, Balance, Call, Hash, HoprApi, Runtime, UncheckedExtrinsic,
+};
+use log::{info, warn};
+use parity_codec::{Decode, Encode};
+use rusqlite::{params, Connection, OptionalExtension};
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{BlakeTwo256, Block as BlockT, Header as HeaderT, ProvideRuntimeApi};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use substrate_service::{FullClient, ServiceFactory};
+
+const SCHEMA: &str = "
+	CREATE TABLE IF NOT EXISTS blocks (
+		hash TEXT PRIMARY KEY,
+		number INTEGER NOT NULL,
+		parent_hash TEXT NOT NULL,
+		canonical INTEGER NOT NULL DEFAULT 0
+	);
+	CREATE INDEX IF NOT EXISTS blocks_number ON blocks (number);
+	CREATE TABLE IF NOT EXISTS calls (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		extrinsic INTEGER NOT NULL,
+		signer TEXT,
+		name TEXT NOT NULL,
+		args TEXT NOT NULL,
+		PRIMARY KEY (block_hash, extrinsic)
+	);
+	CREATE TABLE IF NOT EXISTS fundings (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		event INTEGER NOT NULL,
+		account TEXT NOT NULL,
+		balance TEXT NOT NULL,
+		balance_a TEXT NOT NULL,
+		PRIMARY KEY (block_hash, event)
+	);
+	CREATE TABLE IF NOT EXISTS channels (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		event INTEGER NOT NULL,
+		channel_id TEXT NOT NULL,
+		balance TEXT NOT NULL,
+		balance_a TEXT NOT NULL,
+		PRIMARY KEY (block_hash, event)
+	);
+	CREATE INDEX IF NOT EXISTS channels_id ON channels (channel_id);
+	CREATE TABLE IF NOT EXISTS redemptions (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		event INTEGER NOT NULL,
+		channel_id TEXT NOT NULL,
+		amount TEXT NOT NULL,
+		PRIMARY KEY (block_hash, event)
+	);
+	CREATE INDEX IF NOT EXISTS redemptions_channel_id ON redemptions (channel_id);
+	CREATE TABLE IF NOT EXISTS settlements (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		event INTEGER NOT NULL,
+		channel_id TEXT NOT NULL,
+		kind TEXT NOT NULL,
+		balance TEXT,
+		balance_a TEXT NOT NULL,
+		PRIMARY KEY (block_hash, event)
+	);
+	CREATE INDEX IF NOT EXISTS settlements_channel_id ON settlements (channel_id);
+	CREATE TABLE IF NOT EXISTS locked_payments (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		event INTEGER NOT NULL,
+		lock_id TEXT NOT NULL,
+		kind TEXT NOT NULL,
+		channel_id TEXT,
+		amount TEXT,
+		pre_image TEXT,
+		PRIMARY KEY (block_hash, event)
+	);
+	CREATE INDEX IF NOT EXISTS locked_payments_lock_id ON locked_payments (lock_id);
+	CREATE TABLE IF NOT EXISTS announcements (
+		block_hash TEXT NOT NULL REFERENCES blocks (hash),
+		event INTEGER NOT NULL,
+		account TEXT NOT NULL,
+		PRIMARY KEY (block_hash, event)
+	);
+";
+
+/// A block with its hopr calls and events, ready to be written to the index.
+pub struct IndexedBlock {
+	pub hash: Hash,
+	pub number: u64,
+	pub parent_hash: Hash,
+	pub calls: Vec<IndexedCall>,
+	pub events: Vec<RawEvent<AccountId, Hash, Balance>>,
+}
+
+/// A call of the hopr module in a block body.
+pub struct IndexedCall {
+	/// Position of the extrinsic in the block.
+	pub extrinsic: u32,
+	/// Address that signed the extrinsic.
+	pub signer: Option<String>,
+	pub call: hopr::Call<Runtime>,
+}
+
+/// The SQLite database of the index.
+pub struct Index {
+	conn: Connection,
+}
+
+impl Index {
+	/// Opens the database at `path` and creates the tables that are missing.
+	pub fn open(path: &Path) -> Result<Self, String> {
+		let conn = Connection::open(path).map_err(|e| format!("Error opening index {}: {}", path.display(), e))?;
+		conn.execute_batch(SCHEMA).map_err(|e| format!("Error creating index tables: {}", e))?;
+		Ok(Index { conn })
+	}
+
+	/// Whether the block is in the index, and if so whether it is on the best chain.
+	pub fn canonical(&self, hash: &Hash) -> Result<Option<bool>, String> {
+		self.conn.query_row("SELECT canonical FROM blocks WHERE hash = ?1", params![to_hex(hash)], |row| row.get(0))
+			.optional()
+			.map_err(db_error)
+	}
+
+	/// The number of the first indexed block, or `None` if the index is empty.
+	pub fn first_number(&self) -> Result<Option<u64>, String> {
+		let number: Option<i64> = self.conn.query_row("SELECT MIN(number) FROM blocks", params![], |row| row.get(0))
+			.map_err(db_error)?;
+		Ok(number.map(|number| number as u64))
+	}
+
+	/// Writes `block` unless it is indexed already. The block is not canonical until it is part of
+	/// a route passed to `set_best`.
+	pub fn insert(&mut self, block: &IndexedBlock) -> Result<(), String> {
+		let tx = self.conn.transaction().map_err(db_error)?;
+		let block_hash = to_hex(&block.hash);
+
+		let inserted = tx.execute(
+			"INSERT OR IGNORE INTO blocks (hash, number, parent_hash) VALUES (?1, ?2, ?3)",
+			params![block_hash, block.number as i64, to_hex(&block.parent_hash)],
+		).map_err(db_error)?;
+		if inserted == 0 {
+			return Ok(());
+		}
+
+		for call in &block.calls {
+			tx.execute(
+				"INSERT INTO calls (block_hash, extrinsic, signer, name, args) VALUES (?1, ?2, ?3, ?4, ?5)",
+				params![block_hash, call.extrinsic, call.signer, call_name(&call.call), format!("{:?}", call.call)],
+			).map_err(db_error)?;
+		}
+
+		for (position, event) in block.events.iter().enumerate() {
+			let position = position as u32;
+			match event {
+				RawEvent::Funded(account, balance, balance_a) => tx.execute(
+					"INSERT INTO fundings (block_hash, event, account, balance, balance_a) VALUES (?1, ?2, ?3, ?4, ?5)",
+					params![block_hash, position, to_hex(account), balance.to_string(), balance_a.to_string()],
+				),
+				RawEvent::Opened(channel_id, balance, balance_a) => tx.execute(
+					"INSERT INTO channels (block_hash, event, channel_id, balance, balance_a) VALUES (?1, ?2, ?3, ?4, ?5)",
+					params![block_hash, position, to_hex(channel_id), balance.to_string(), balance_a.to_string()],
+				),
+				RawEvent::OpenedFor(a, b, balance, balance_a) => tx.execute(
+					"INSERT INTO channels (block_hash, event, channel_id, balance, balance_a) VALUES (?1, ?2, ?3, ?4, ?5)",
+					params![
+						block_hash,
+						position,
+						to_hex(&hopr_primitives::channel_id::<BlakeTwo256, _>(a, b)),
+						balance.to_string(),
+						balance_a.to_string(),
+					],
+				),
+				RawEvent::RedeemedTicket(channel_id, amount) => tx.execute(
+					"INSERT INTO redemptions (block_hash, event, channel_id, amount) VALUES (?1, ?2, ?3, ?4)",
+					params![block_hash, position, to_hex(channel_id), amount.to_string()],
+				),
+				RawEvent::InitiatedSettlement(channel_id, balance_a) => insert_settlement(&tx, &block_hash, position, channel_id, "initiated", None, balance_a),
+				RawEvent::PushedBackSettlement(channel_id, balance_a) => insert_settlement(&tx, &block_hash, position, channel_id, "pushed_back", None, balance_a),
+				RawEvent::Withdrawn(channel_id, balance, balance_a) => insert_settlement(&tx, &block_hash, position, channel_id, "withdrawn", Some(balance), balance_a),
+				RawEvent::LockedPayment(channel_id, lock_id, amount) => tx.execute(
+					"INSERT INTO locked_payments (block_hash, event, lock_id, kind, channel_id, amount) VALUES (?1, ?2, ?3, 'locked', ?4, ?5)",
+					params![block_hash, position, to_hex(lock_id), to_hex(channel_id), amount.to_string()],
+				),
+				RawEvent::ClaimedPayment(lock_id, pre_image) => tx.execute(
+					"INSERT INTO locked_payments (block_hash, event, lock_id, kind, pre_image) VALUES (?1, ?2, ?3, 'claimed', ?4)",
+					params![block_hash, position, to_hex(lock_id), to_hex(pre_image)],
+				),
+				RawEvent::RefundedPayment(lock_id) => tx.execute(
+					"INSERT INTO locked_payments (block_hash, event, lock_id, kind) VALUES (?1, ?2, ?3, 'refunded')",
+					params![block_hash, position, to_hex(lock_id)],
+				),
+				RawEvent::Announced(account) => tx.execute(
+					"INSERT INTO announcements (block_hash, event, account) VALUES (?1, ?2, ?3)",
+					params![block_hash, position, to_hex(account)],
+				),
+			}.map_err(db_error)?;
+		}
+
+		tx.commit().map_err(db_error)
+	}
+
+	/// Makes `route` the best chain above the block `ancestor`.
+	///
+	/// `route` holds the hashes of the enacted blocks, which have to be indexed already. Every
+	/// other block above `ancestor` is retracted.
+	pub fn set_best(&mut self, ancestor: u64, route: &[Hash]) -> Result<(), String> {
+		let tx = self.conn.transaction().map_err(db_error)?;
+		tx.execute("UPDATE blocks SET canonical = 0 WHERE number > ?1 AND canonical = 1", params![ancestor as i64])
+			.map_err(db_error)?;
+		for hash in route {
+			tx.execute("UPDATE blocks SET canonical = 1 WHERE hash = ?1", params![to_hex(hash)]).map_err(db_error)?;
+		}
+		tx.commit().map_err(db_error)
+	}
+}
+
+fn insert_settlement(
+	tx: &rusqlite::Transaction,
+	block_hash: &str,
+	position: u32,
+	channel_id: &Hash,
+	kind: &str,
+	balance: Option<&Balance>,
+	balance_a: &Balance,
+) -> rusqlite::Result<usize> {
+	tx.execute(
+		"INSERT INTO settlements (block_hash, event, channel_id, kind, balance, balance_a) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+		params![block_hash, position, to_hex(channel_id), kind, balance.map(Balance::to_string), balance_a.to_string()],
+	)
+}
+
+/// Name of the dispatchable of `call`.
+fn call_name(call: &hopr::Call<Runtime>) -> &'static str {
+	match call {
+		hopr::Call::create(..) => "create",
+		hopr::Call::set_active(..) => "set_active",
+		hopr::Call::create_funded(..) => "create_funded",
+		hopr::Call::set_secret(..) => "set_secret",
+		hopr::Call::init(..) => "init",
+		hopr::Call::redeem_ticket(..) => "redeem_ticket",
+		hopr::Call::redeem_tickets(..) => "redeem_tickets",
+		hopr::Call::initiate_recovery(..) => "initiate_recovery",
+		hopr::Call::initiate_settlement(..) => "initiate_settlement",
+		hopr::Call::withdraw(..) => "withdraw",
+		hopr::Call::announce(..) => "announce",
+		hopr::Call::lock_payment(..) => "lock_payment",
+		hopr::Call::claim_payment(..) => "claim_payment",
+		hopr::Call::refund_payment(..) => "refund_payment",
+		_ => "unknown",
+	}
+}
+
+fn to_hex<T: AsRef<[u8]>>(value: &T) -> String {
+	format!("0x{}", hex::encode(value.as_ref()))
+}
+
+fn db_error(e: rusqlite::Error) -> String {
+	format!("Index database error: {}", e)
+}
+
+/// Follows the best chain of a full node into an `Index`.
+pub struct Indexer {
+	client: Arc<FullClient<Factory>>,
+	index: Index,
+}
+
+impl Indexer {
+	pub fn new(client: Arc<FullClient<Factory>>, index: Index) -> Self {
+		Indexer { client, index }
+	}
+
+	/// Indexes the imported block `hash` and, if it is the new best block, the route to it.
+	pub fn on_import(&mut self, hash: Hash, is_new_best: bool) -> Result<(), String> {
+		if self.index.canonical(&hash)?.is_none() {
+			let block = self.read_block(hash)?;
+			self.index.insert(&block)?;
+		}
+		if !is_new_best {
+			return Ok(());
+		}
+
+		// Walk back to the first block that is canonical already. The index starts at the first
+		// best block that the node saw, so nothing below that is indexed.
+		let first = self.index.first_number()?.unwrap_or(0);
+		let mut route = Vec::new();
+		let mut current = hash;
+		let ancestor = loop {
+			let header = self.header(current)?;
+			let number = *header.number();
+			match self.index.canonical(&current)? {
+				Some(true) => break number,
+				Some(false) => {},
+				None => {
+					let block = self.read_block(current)?;
+					self.index.insert(&block)?;
+				},
+			}
+			route.push(current);
+			if number <= first {
+				break number.saturating_sub(1);
+			}
+			current = *header.parent_hash();
+		};
+
+		if route.len() > 1 {
+			info!(target: "hopr", "Index re-org to {:?}, enacting {} blocks above #{}", hash, route.len(), ancestor);
+		}
+		self.index.set_best(ancestor, &route)
+	}
+
+	fn header(&self, hash: Hash) -> Result<<Block as BlockT>::Header, String> {
+		self.client.header(&BlockId::hash(hash))
+			.map_err(|e| format!("{:?}", e))?
+			.ok_or_else(|| format!("Unknown block {:?}", hash))
+	}
+
+	/// Reads the hopr calls and events of block `hash`.
+	fn read_block(&self, hash: Hash) -> Result<IndexedBlock, String> {
+		let at = BlockId::hash(hash);
+		let header = self.header(hash)?;
+		let body = self.client.body(&at)
+			.map_err(|e| format!("{:?}", e))?
+			.ok_or_else(|| format!("Missing body of block {:?}", hash))?;
+
+		let mut calls = Vec::new();
+		for (extrinsic, opaque) in body.iter().enumerate() {
+			let xt = match UncheckedExtrinsic::decode(&mut &opaque.encode()[..]) {
+				Some(xt) => xt,
+				None => {
+					warn!(target: "hopr", "Undecodable extrinsic {} in block {:?}", extrinsic, hash);
+					continue;
+				},
+			};
+			if let Call::Hopr(call) = xt.function {
+				calls.push(IndexedCall {
+					extrinsic: extrinsic as u32,
+					signer: xt.signed.map(|(address, ..)| format!("{:?}", address)),
+					call,
+				});
+			}
+		}
+
+		Ok(IndexedBlock {
+			hash,
+			number: *header.number(),
+			parent_hash: *header.parent_hash(),
+			calls,
+			events: self.client.runtime_api().events(&at).map_err(|e| format!("{:?}", e))?,
+		})
+	}
+}
+
+/// Indexes every block that the node imports into the database at `path`.
+///
+/// SQLite connections can't be shared between threads, so the index is written from its own
+/// thread.
+pub fn start(service: &<Factory as ServiceFactory>::FullService, path: &Path) -> Result<(), String> {
+	let index = Index::open(path)?;
+	info!(target: "hopr", "Indexing hopr channels into {}", path.display());
+
+	let client = service.client();
+	let notifications = client.import_notification_stream();
+	let mut indexer = Indexer::new(client, index);
+
+	thread::spawn(move || {
+		for notification in notifications.wait() {
+			let notification = match notification {
+				Ok(notification) => notification,
+				Err(_) => break,
+			};
+			if let Err(e) = indexer.on_import(notification.hash, notification.is_new_best) {
+				warn!(target: "hopr", "Error indexing block {:?}: {}", notification.hash, e);
+			}
+		}
+	});
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn block(n: u8, parent: u8, number: u64, events: Vec<RawEvent<AccountId, Hash, Balance>>) -> IndexedBlock {
+		IndexedBlock {
+			hash: Hash::repeat_byte(n),
+			number,
+			parent_hash: Hash::repeat_byte(parent),
+			calls: Vec::new(),
+			events,
+		}
+	}
+
+	fn redeemed(amount: Balance) -> RawEvent<AccountId, Hash, Balance> {
+		RawEvent::RedeemedTicket(Hash::repeat_byte(0xcc), amount)
+	}
+
+	fn canonical(index: &Index, n: u8) -> Option<bool> {
+		index.canonical(&Hash::repeat_byte(n)).unwrap()
+	}
+
+	/// Sum of the redeemed amounts on the best chain.
+	fn redeemed_on_best_chain(index: &Index) -> u64 {
+		index.conn.query_row(
+			"SELECT COALESCE(SUM(CAST(amount AS INTEGER)), 0) FROM redemptions JOIN blocks ON blocks.hash = redemptions.block_hash WHERE blocks.canonical = 1",
+			params![],
+			|row| row.get::<_, i64>(0),
+		).unwrap() as u64
+	}
+
+	#[test]
+	fn canonical_flag_follows_reorg() {
+		let mut index = Index::open(Path::new(":memory:")).unwrap();
+		assert_eq!(index.first_number().unwrap(), None);
+
+		// Fork A with blocks 1 and 2, fork B with blocks 3, 4 and 5, both on top of block 0.
+		index.insert(&block(0, 0xff, 0, vec![])).unwrap();
+		index.insert(&block(1, 0, 1, vec![redeemed(5)])).unwrap();
+		index.insert(&block(2, 1, 2, vec![redeemed(7)])).unwrap();
+		index.set_best(0, &[Hash::repeat_byte(0), Hash::repeat_byte(1), Hash::repeat_byte(2)]).unwrap();
+
+		index.insert(&block(3, 0, 1, vec![redeemed(1)])).unwrap();
+		index.insert(&block(4, 3, 2, vec![])).unwrap();
+		assert_eq!(canonical(&index, 3), Some(false));
+		assert_eq!(redeemed_on_best_chain(&index), 12);
+
+		index.insert(&block(5, 4, 3, vec![redeemed(2)])).unwrap();
+		index.set_best(0, &[Hash::repeat_byte(3), Hash::repeat_byte(4), Hash::repeat_byte(5)]).unwrap();
+
+		assert_eq!(canonical(&index, 0), Some(true));
+		assert_eq!(canonical(&index, 1), Some(false));
+		assert_eq!(canonical(&index, 2), Some(false));
+		assert_eq!(canonical(&index, 3), Some(true));
+		assert_eq!(canonical(&index, 4), Some(true));
+		assert_eq!(canonical(&index, 5), Some(true));
+		assert_eq!(canonical(&index, 6), None);
+		assert_eq!(redeemed_on_best_chain(&index), 3);
+
+		// Fork A becomes the best chain again once it is longer.
+		index.insert(&block(6, 2, 3, vec![])).unwrap();
+		index.insert(&block(7, 6, 4, vec![])).unwrap();
+		index.set_best(0, &[Hash::repeat_byte(1), Hash::repeat_byte(2), Hash::repeat_byte(6), Hash::repeat_byte(7)]).unwrap();
+
+		assert_eq!(canonical(&index, 3), Some(false));
+		assert_eq!(canonical(&index, 5), Some(false));
+		assert_eq!(canonical(&index, 7), Some(true));
+		assert_eq!(redeemed_on_best_chain(&index), 12);
+		assert_eq!(index.first_number().unwrap(), Some(0));
+	}
+
+	#[test]
+	fn blocks_are_indexed_once() {
+		let mut index = Index::open(Path::new(":memory:")).unwrap();
+		index.insert(&block(1, 0, 1, vec![redeemed(5)])).unwrap();
+		index.insert(&block(1, 0, 1, vec![redeemed(5)])).unwrap();
+		index.set_best(0, &[Hash::repeat_byte(1)]).unwrap();
+
+		assert_eq!(redeemed_on_best_chain(&index), 5);
+	}
+}
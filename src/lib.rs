@@ -0,0 +1,15 @@
+//! Hopr node library. The `hopr-polkadot` binary runs its CLI, integration tests use the
+//! service and chain specs directly.
+
+#![warn(unused_extern_crates)]
+
+pub mod chain_spec;
+pub mod service;
+pub mod cli;
+pub mod indexer;
+pub mod light;
+pub mod metrics;
+mod params;
+pub mod redeemer;
+mod ticket;
+mod watchtower;
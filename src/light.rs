@@ -0,0 +1,124 @@
+//! Queries of hopr state on a light client.
+//!
+//! A light client only keeps headers. Storage entries are requested from full nodes together
+//! with a read proof, which the light backend checks against the state root of the header before
+//! the value is returned. Queries are answered at a trusted block, so that a light node can
+//! follow its channels without running a full node.
+
+use crate::service::Factory;
+use futures::Stream;
+use hopr_polkadot_runtime::{
+	hopr::{Channel, State},
+	opaque::Block,
+	AccountId, Balance, Hash, Hopr, Moment,
+};
+use log::{info, warn};
+use parity_codec::Decode;
+use primitives::storage::StorageKey;
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::BlakeTwo256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use substrate_service::{LightClient, ServiceFactory};
+
+/// Number of blocks below the best block that are trusted if the chain does not finalize them.
+///
+/// Aura alone does not finalize blocks, so the last finalized block may be the genesis block.
+pub const CONFIRMATIONS: u64 = 6;
+
+/// Reads channels and states of the hopr module through a light client.
+pub struct LightHopr {
+	client: Arc<LightClient<Factory>>,
+	confirmations: u64,
+}
+
+impl LightHopr {
+	pub fn new(client: Arc<LightClient<Factory>>, confirmations: u64) -> Self {
+		LightHopr { client, confirmations }
+	}
+
+	/// The block that queries are answered at: the last finalized block, or the block
+	/// `confirmations` below the best block if that is more recent.
+	pub fn trusted_block(&self) -> Result<BlockId<Block>, String> {
+		let info = self.client.info().map_err(|e| format!("{:?}", e))?.chain;
+		let confirmed = info.best_number.saturating_sub(self.confirmations);
+
+		Ok(if confirmed > info.finalized_number {
+			BlockId::number(confirmed)
+		} else {
+			BlockId::hash(info.finalized_hash)
+		})
+	}
+
+	/// The channel with the given id at `at`.
+	pub fn channel(&self, at: &BlockId<Block>, channel_id: &Hash) -> Result<Channel<Balance, Moment>, String> {
+		self.read(at, Hopr::channel_storage_key(channel_id)).map(Option::unwrap_or_default)
+	}
+
+	/// The state of `account` at `at`, or `None` if it has not called `init`.
+	pub fn state(&self, at: &BlockId<Block>, account: &AccountId) -> Result<Option<State<Hash, AccountId>>, String> {
+		self.read(at, Hopr::state_storage_key(account))
+	}
+
+	fn read<T: Decode>(&self, at: &BlockId<Block>, key: Vec<u8>) -> Result<Option<T>, String> {
+		let data = self.client.storage(at, &StorageKey(key))
+			.map_err(|e| format!("Error reading storage at {:?}: {:?}", at, e))?;
+
+		match data {
+			Some(data) => T::decode(&mut &data.0[..])
+				.map(Some)
+				.ok_or_else(|| format!("Invalid storage entry at {:?}", at)),
+			None => Ok(None),
+		}
+	}
+}
+
+/// Logs the state of `account` and of its channels with `counterparties` whenever they change.
+///
+/// Remote reads block until a full node answers them, so they run on their own thread instead
+/// of the runtime that drives the network.
+pub fn start(service: &<Factory as ServiceFactory>::LightService, account: AccountId, counterparties: Vec<AccountId>) {
+	info!(target: "hopr", "Following the channels of {:?} with {} counterparties", account, counterparties.len());
+
+	let client = service.client();
+	let notifications = client.import_notification_stream();
+	let hopr = LightHopr::new(client, CONFIRMATIONS);
+
+	thread::spawn(move || {
+		let mut secret = None;
+		let mut channels: HashMap<Hash, Channel<Balance, Moment>> = HashMap::new();
+
+		for _ in notifications.wait() {
+			let at = match hopr.trusted_block() {
+				Ok(at) => at,
+				Err(e) => {
+					warn!(target: "hopr", "{}", e);
+					continue;
+				},
+			};
+
+			match hopr.state(&at, &account) {
+				Ok(state) => {
+					let new_secret = state.map(|state| *state.secret());
+					if new_secret != secret {
+						info!(target: "hopr", "On-chain secret of {:?} at {:?}: {:?}", account, at, new_secret);
+						secret = new_secret;
+					}
+				},
+				Err(e) => warn!(target: "hopr", "{}", e),
+			}
+
+			for counterparty in &counterparties {
+				let channel_id = hopr_primitives::channel_id::<BlakeTwo256, _>(&account, counterparty);
+				match hopr.channel(&at, &channel_id) {
+					Ok(channel) => if channels.get(&channel_id) != Some(&channel) {
+						info!(target: "hopr", "Channel with {:?} at {:?}: {:?}", counterparty, at, channel);
+						channels.insert(channel_id, channel);
+					},
+					Err(e) => warn!(target: "hopr", "{}", e),
+				}
+			}
+		}
+	});
+}
@@ -3,9 +3,7 @@
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
-mod chain_spec;
-mod service;
-mod cli;
+use hopr_polkadot::cli;
 
 pub use substrate_cli::{VersionInfo, IntoExit, error};
 
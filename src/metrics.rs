@@ -0,0 +1,419 @@
+//! Prometheus metrics of the hopr module.
+//!
+//! Counters are taken from the hopr events of the blocks on the chain of the best block that are
+//! `CONFIRMATIONS` deep, so that they never decrease on a re-org. The events of the more recent
+//! blocks are kept by block hash until they are confirmed and are exposed as `*_unconfirmed`
+//! gauges of the current best chain. Gauges of the channels are computed from all channels that
+//! are not withdrawn yet at the best block, as listed by the runtime.
+
+use crate::light::CONFIRMATIONS;
+use crate::service::Factory;
+use futures::{Future, Stream};
+use hopr_polkadot_runtime::{hopr::{Channel, RawEvent}, AccountId, Balance, Hash, HoprApi, Moment};
+use hyper::{service::service_fn_ok, Body, Request, Response, Server, StatusCode};
+use log::{info, warn};
+use parking_lot::RwLock;
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::ProvideRuntimeApi;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use substrate_service::{ServiceFactory, TaskExecutor};
+
+/// Number of hopr events of one or more blocks.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Counts {
+	channels_funded: u64,
+	channels_opened: u64,
+	settlements_initiated: u64,
+	settlements_pushed_back: u64,
+	tickets_redeemed: u64,
+	redeemed_value: Balance,
+	withdrawals: u64,
+	announcements: u64,
+	payments_locked: u64,
+	payments_claimed: u64,
+	payments_refunded: u64,
+}
+
+impl Counts {
+	fn add(&mut self, other: &Counts) {
+		self.channels_funded += other.channels_funded;
+		self.channels_opened += other.channels_opened;
+		self.settlements_initiated += other.settlements_initiated;
+		self.settlements_pushed_back += other.settlements_pushed_back;
+		self.tickets_redeemed += other.tickets_redeemed;
+		self.redeemed_value += other.redeemed_value;
+		self.withdrawals += other.withdrawals;
+		self.announcements += other.announcements;
+		self.payments_locked += other.payments_locked;
+		self.payments_claimed += other.payments_claimed;
+		self.payments_refunded += other.payments_refunded;
+	}
+}
+
+/// An imported block that is not `CONFIRMATIONS` deep yet.
+struct RecentBlock {
+	parent_hash: Hash,
+	number: u64,
+	counts: Counts,
+}
+
+/// Hopr activity of the chain as seen by the node.
+#[derive(Default)]
+pub struct Metrics {
+	/// Hash and number of the best block.
+	best: Option<(Hash, u64)>,
+	/// Channels that are not withdrawn yet at the best block.
+	channels: Vec<(Hash, Channel<Balance, Moment>)>,
+	/// Events of the blocks of the best chain that are at least `CONFIRMATIONS` deep.
+	confirmed: Counts,
+	/// Events of the more recent blocks of all forks by block hash.
+	recent: HashMap<Hash, RecentBlock>,
+}
+
+impl Metrics {
+	/// Updates the metrics with the events of the imported block `hash`.
+	///
+	/// The events of every imported block are recorded, but only the blocks on the chain of the
+	/// best block are counted. If the block is the new best block, `channels` returns all channels
+	/// that are not withdrawn yet at that block.
+	pub fn on_block<F>(
+		&mut self,
+		hash: Hash,
+		parent_hash: Hash,
+		number: u64,
+		is_new_best: bool,
+		events: &[RawEvent<AccountId, Hash, Balance>],
+		channels: F,
+	) -> Result<(), String>
+		where F: FnOnce() -> Result<Vec<(Hash, Channel<Balance, Moment>)>, String>
+	{
+		let mut counts = Counts::default();
+		for event in events {
+			match event {
+				RawEvent::Funded(..) => counts.channels_funded += 1,
+				RawEvent::Opened(..) | RawEvent::OpenedFor(..) => counts.channels_opened += 1,
+				RawEvent::InitiatedSettlement(..) => counts.settlements_initiated += 1,
+				RawEvent::PushedBackSettlement(..) => counts.settlements_pushed_back += 1,
+				RawEvent::RedeemedTicket(_, amount) => {
+					counts.tickets_redeemed += 1;
+					counts.redeemed_value += amount;
+				},
+				RawEvent::Withdrawn(..) => counts.withdrawals += 1,
+				RawEvent::Announced(..) => counts.announcements += 1,
+				RawEvent::LockedPayment(..) => counts.payments_locked += 1,
+				RawEvent::ClaimedPayment(..) => counts.payments_claimed += 1,
+				RawEvent::RefundedPayment(..) => counts.payments_refunded += 1,
+			}
+		}
+		self.recent.insert(hash, RecentBlock { parent_hash, number, counts });
+
+		if !is_new_best {
+			return Ok(());
+		}
+		self.best = Some((hash, number));
+		self.channels = channels()?;
+
+		self.confirm();
+		Ok(())
+	}
+
+	/// Hashes of the recent blocks on the chain of the best block.
+	fn best_chain(&self) -> Vec<Hash> {
+		let mut chain = Vec::new();
+		let mut hash = match self.best {
+			Some((hash, _)) => hash,
+			None => return chain,
+		};
+		while let Some(block) = self.recent.get(&hash) {
+			chain.push(hash);
+			hash = block.parent_hash;
+		}
+		chain
+	}
+
+	/// Adds the events of the best chain that are `CONFIRMATIONS` deep to the confirmed events
+	/// and forgets all blocks at that depth.
+	fn confirm(&mut self) {
+		let confirmed_number = match self.best {
+			Some((_, number)) if number >= CONFIRMATIONS => number - CONFIRMATIONS,
+			_ => return,
+		};
+
+		let best_chain: HashSet<Hash> = self.best_chain().into_iter().collect();
+		let confirmed: Vec<Hash> = self.recent.iter()
+			.filter(|(_, block)| block.number <= confirmed_number)
+			.map(|(hash, _)| *hash)
+			.collect();
+		for hash in confirmed {
+			if let Some(block) = self.recent.remove(&hash) {
+				if best_chain.contains(&hash) {
+					self.confirmed.add(&block.counts);
+				}
+			}
+		}
+	}
+
+	/// Events of the blocks on the chain of the best block that are not confirmed yet.
+	fn unconfirmed(&self) -> Counts {
+		let mut unconfirmed = Counts::default();
+		for hash in self.best_chain() {
+			unconfirmed.add(&self.recent[&hash].counts);
+		}
+		unconfirmed
+	}
+
+	/// The metrics in the Prometheus text format.
+	pub fn render(&self) -> String {
+		let (confirmed, unconfirmed) = (&self.confirmed, self.unconfirmed());
+		let (best_block, tickets_redeemed_last_block) = match self.best {
+			Some((hash, number)) => (number, self.recent.get(&hash).map_or(0, |block| block.counts.tickets_redeemed)),
+			None => (0, 0),
+		};
+
+		let (mut funded, mut active, mut pending) = (0, 0, 0);
+		let mut locked: Balance = 0;
+		for (_, channel) in &self.channels {
+			let channel_balance = match channel {
+				Channel::Funded(channel_balance) => { funded += 1; channel_balance },
+				Channel::Active(channel_balance) => { active += 1; channel_balance },
+				Channel::PendingSettlement(channel_balance, _) => { pending += 1; channel_balance },
+				Channel::Uninitialized => continue,
+			};
+			locked += channel_balance.balance;
+		}
+
+		let mut out = String::new();
+		let mut metric = |name: &str, kind: &str, help: &str, values: &[(&str, String)]| {
+			let _ = writeln!(out, "# HELP {} {}", name, help);
+			let _ = writeln!(out, "# TYPE {} {}", name, kind);
+			for (labels, value) in values {
+				let _ = writeln!(out, "{}{} {}", name, labels, value);
+			}
+		};
+
+		metric("hopr_best_block", "gauge", "Number of the best block.", &[("", best_block.to_string())]);
+		metric("hopr_channels", "gauge", "Number of channels that are not withdrawn yet by state.", &[
+			("{state=\"funded\"}", funded.to_string()),
+			("{state=\"active\"}", active.to_string()),
+			("{state=\"pending_settlement\"}", pending.to_string()),
+		]);
+		metric("hopr_locked_value", "gauge", "Funds locked in channels.", &[("", locked.to_string())]);
+		metric("hopr_tickets_redeemed_last_block", "gauge", "Number of tickets redeemed in the best block.", &[("", tickets_redeemed_last_block.to_string())]);
+
+		// Every counter comes with a gauge of the events of the best chain that are not confirmed yet.
+		let mut counter = |name: &str, help: &str, values: &[(&str, String, String)]| {
+			metric(
+				&format!("{}_total", name),
+				"counter",
+				&format!("{} Counts the blocks that are {} deep.", help, CONFIRMATIONS),
+				&values.iter().map(|(labels, total, _)| (*labels, total.clone())).collect::<Vec<_>>(),
+			);
+			metric(
+				&format!("{}_unconfirmed", name),
+				"gauge",
+				&format!("{} Counts the more recent blocks of the best chain.", help),
+				&values.iter().map(|(labels, _, unconfirmed)| (*labels, unconfirmed.clone())).collect::<Vec<_>>(),
+			);
+		};
+		counter("hopr_channels_funded", "Number of channel fundings.", &[("", confirmed.channels_funded.to_string(), unconfirmed.channels_funded.to_string())]);
+		counter("hopr_channels_opened", "Number of opened channels.", &[("", confirmed.channels_opened.to_string(), unconfirmed.channels_opened.to_string())]);
+		counter("hopr_settlements_initiated", "Number of initiated settlements.", &[("", confirmed.settlements_initiated.to_string(), unconfirmed.settlements_initiated.to_string())]);
+		counter("hopr_settlements_pushed_back", "Number of settlements pushed back by redeemed tickets.", &[("", confirmed.settlements_pushed_back.to_string(), unconfirmed.settlements_pushed_back.to_string())]);
+		counter("hopr_tickets_redeemed", "Number of redeemed tickets.", &[("", confirmed.tickets_redeemed.to_string(), unconfirmed.tickets_redeemed.to_string())]);
+		counter("hopr_redeemed_value", "Value of all redeemed tickets.", &[("", confirmed.redeemed_value.to_string(), unconfirmed.redeemed_value.to_string())]);
+		counter("hopr_withdrawals", "Number of settled channels.", &[("", confirmed.withdrawals.to_string(), unconfirmed.withdrawals.to_string())]);
+		counter("hopr_announcements", "Number of node announcements.", &[("", confirmed.announcements.to_string(), unconfirmed.announcements.to_string())]);
+		counter("hopr_payments", "Number of conditional payments by outcome.", &[
+			("{outcome=\"locked\"}", confirmed.payments_locked.to_string(), unconfirmed.payments_locked.to_string()),
+			("{outcome=\"claimed\"}", confirmed.payments_claimed.to_string(), unconfirmed.payments_claimed.to_string()),
+			("{outcome=\"refunded\"}", confirmed.payments_refunded.to_string(), unconfirmed.payments_refunded.to_string()),
+		]);
+
+		out
+	}
+}
+
+/// Collects the metrics of every imported block and serves them on `addr` at `/metrics`.
+pub fn start(
+	service: &<Factory as ServiceFactory>::FullService,
+	addr: SocketAddr,
+	executor: &TaskExecutor,
+) -> Result<(), String> {
+	let client = service.client();
+
+	// The channel gauges are complete from the start, not only once the next block is imported.
+	let best = client.info().map_err(|e| format!("{:?}", e))?.chain.best_hash;
+	let channels = client.runtime_api().channels(&BlockId::hash(best)).map_err(|e| format!("{:?}", e))?;
+	let metrics = Arc::new(RwLock::new(Metrics { channels, ..Default::default() }));
+
+	let collected = metrics.clone();
+	let collect = client.import_notification_stream()
+		.for_each(move |notification| {
+			let at = BlockId::hash(notification.hash);
+			let api = client.runtime_api();
+			let res = api.events(&at).map_err(|e| format!("{:?}", e)).and_then(|events| {
+				collected.write().on_block(
+					notification.hash,
+					notification.header.parent_hash,
+					notification.header.number,
+					notification.is_new_best,
+					&events,
+					|| api.channels(&at).map_err(|e| format!("{:?}", e)),
+				)
+			});
+			if let Err(e) = res {
+				warn!(target: "hopr", "Error collecting metrics: {}", e);
+			}
+			Ok(())
+		});
+	executor.spawn(collect.select(service.on_exit()).then(|_| Ok(())));
+
+	let server = Server::try_bind(&addr)
+		.map_err(|e| format!("Error binding metrics endpoint to {}: {}", addr, e))?
+		.serve(move || {
+			let metrics = metrics.clone();
+			service_fn_ok(move |req: Request<Body>| match req.uri().path() {
+				"/metrics" => Response::builder()
+					.header("Content-Type", "text/plain; version=0.0.4")
+					.body(Body::from(metrics.read().render()))
+					.expect("static header is valid; qed"),
+				_ => Response::builder()
+					.status(StatusCode::NOT_FOUND)
+					.body(Body::empty())
+					.expect("static status is valid; qed"),
+			})
+		})
+		.map_err(|e| warn!(target: "hopr", "Metrics endpoint failed: {}", e));
+	executor.spawn(server.select(service.on_exit()).then(|_| Ok(())));
+
+	info!(target: "hopr", "Serving Prometheus metrics on http://{}/metrics", addr);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hopr_polkadot_runtime::hopr::ChannelBalance;
+
+	type Event = RawEvent<AccountId, Hash, Balance>;
+
+	fn hash(n: u8) -> Hash {
+		Hash::repeat_byte(n)
+	}
+
+	fn redeemed(amount: Balance) -> Event {
+		RawEvent::RedeemedTicket(hash(0xcc), amount)
+	}
+
+	fn active() -> Result<Vec<(Hash, Channel<Balance, Moment>)>, String> {
+		Ok(vec![(hash(0xcc), Channel::Active(ChannelBalance::new(30, 10)))])
+	}
+
+	/// Imports block `n` with parent `parent` as new best block.
+	fn import(metrics: &mut Metrics, n: u8, parent: u8, number: u64, events: &[Event]) {
+		metrics.on_block(hash(n), hash(parent), number, true, events, active).unwrap();
+	}
+
+	fn value(rendered: &str, metric: &str) -> String {
+		rendered.lines()
+			.find(|line| line.starts_with(metric) && line[metric.len()..].starts_with(' '))
+			.map(|line| line[metric.len() + 1..].to_string())
+			.unwrap_or_else(|| panic!("metric {} is missing", metric))
+	}
+
+	#[test]
+	fn counts_events_of_best_chain() {
+		let mut metrics = Metrics::default();
+		import(&mut metrics, 1, 0, 1, &[redeemed(5), RawEvent::InitiatedSettlement(hash(0xcc), 10)]);
+		import(&mut metrics, 2, 1, 2, &[redeemed(7)]);
+
+		let rendered = metrics.render();
+		assert_eq!(value(&rendered, "hopr_best_block"), "2");
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_unconfirmed"), "2");
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_last_block"), "1");
+		assert_eq!(value(&rendered, "hopr_redeemed_value_unconfirmed"), "12");
+		assert_eq!(value(&rendered, "hopr_settlements_initiated_unconfirmed"), "1");
+		// Neither block is confirmed yet.
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_total"), "0");
+		assert_eq!(value(&rendered, "hopr_channels{state=\"active\"}"), "1");
+		assert_eq!(value(&rendered, "hopr_locked_value"), "30");
+		assert!(rendered.contains("# TYPE hopr_tickets_redeemed_total counter"));
+		assert!(rendered.contains("# TYPE hopr_tickets_redeemed_unconfirmed gauge"));
+	}
+
+	#[test]
+	fn reorg_counts_only_new_best_chain() {
+		let mut metrics = Metrics::default();
+		import(&mut metrics, 1, 0, 1, &[redeemed(5)]);
+		// A fork of the same height is imported without becoming the best block.
+		metrics.on_block(hash(2), hash(0), 1, false, &[redeemed(7), redeemed(7)], active).unwrap();
+		assert_eq!(value(&metrics.render(), "hopr_tickets_redeemed_unconfirmed"), "1");
+
+		// The fork overtakes the chain of block 1.
+		import(&mut metrics, 3, 2, 2, &[redeemed(1)]);
+		let rendered = metrics.render();
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_unconfirmed"), "3");
+		assert_eq!(value(&rendered, "hopr_redeemed_value_unconfirmed"), "15");
+
+		// And back again.
+		import(&mut metrics, 4, 1, 2, &[]);
+		import(&mut metrics, 5, 4, 3, &[]);
+		let rendered = metrics.render();
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_unconfirmed"), "1");
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_last_block"), "0");
+		// The counters only move once blocks are confirmed, so a re-org never decreases them.
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_total"), "0");
+	}
+
+	#[test]
+	fn confirmed_blocks_are_counted_once() {
+		let mut metrics = Metrics::default();
+		import(&mut metrics, 1, 0, 1, &[redeemed(5)]);
+		metrics.on_block(hash(0xf0), hash(0), 1, false, &[redeemed(7)], active).unwrap();
+		let mut total = 0;
+		for n in 2..(CONFIRMATIONS as u8 + 10) {
+			import(&mut metrics, n, n - 1, u64::from(n), &[redeemed(1)]);
+			let counted: u64 = value(&metrics.render(), "hopr_tickets_redeemed_total").parse().unwrap();
+			assert!(counted >= total);
+			total = counted;
+		}
+
+		// Only the blocks that are not confirmed yet are kept, the fork is gone.
+		assert_eq!(metrics.recent.len(), CONFIRMATIONS as usize);
+		let rendered = metrics.render();
+		// Blocks 1 to 9 are confirmed, the fork was never counted.
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_total"), "9");
+		assert_eq!(value(&rendered, "hopr_redeemed_value_total"), "13");
+		assert_eq!(value(&rendered, "hopr_tickets_redeemed_unconfirmed"), CONFIRMATIONS.to_string());
+	}
+
+	#[test]
+	fn settled_channels_are_dropped() {
+		let mut metrics = Metrics::default();
+		import(&mut metrics, 1, 0, 1, &[RawEvent::Opened(hash(0xcc), 30, 10)]);
+		assert_eq!(value(&metrics.render(), "hopr_channels{state=\"active\"}"), "1");
+
+		metrics.on_block(hash(2), hash(1), 2, true, &[RawEvent::Withdrawn(hash(0xcc), 30, 10)], || Ok(Vec::new())).unwrap();
+		let rendered = metrics.render();
+		assert_eq!(value(&rendered, "hopr_channels{state=\"active\"}"), "0");
+		assert_eq!(value(&rendered, "hopr_locked_value"), "0");
+		assert_eq!(value(&rendered, "hopr_withdrawals_unconfirmed"), "1");
+	}
+
+	#[test]
+	fn gauges_cover_channels_without_events() {
+		let mut metrics = Metrics::default();
+		metrics.on_block(hash(1), hash(0), 1, true, &[], || Ok(vec![
+			(hash(0xaa), Channel::Funded(ChannelBalance::new(20, 20))),
+			(hash(0xcc), Channel::PendingSettlement(ChannelBalance::new(30, 10), 100)),
+		])).unwrap();
+
+		let rendered = metrics.render();
+		assert_eq!(value(&rendered, "hopr_channels{state=\"funded\"}"), "1");
+		assert_eq!(value(&rendered, "hopr_channels{state=\"pending_settlement\"}"), "1");
+		assert_eq!(value(&rendered, "hopr_locked_value"), "50");
+	}
+}
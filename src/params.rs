@@ -0,0 +1,349 @@
+//! Command line parameters that extend the default Substrate CLI.
+
+use crate::chain_spec::GenesisAccounts;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use substrate_cli::{impl_augment_clap, GetLogFilter};
+
+/// Subcommands that are specific to the hopr node.
+#[derive(Clone, Debug, StructOpt)]
+pub enum CustomSubcommands {
+	/// Write the chain specification of a built-in chain or a JSON chain spec to a file.
+	#[structopt(name = "export-spec")]
+	ExportSpec(ExportSpecCmd),
+	/// Create, sign and inspect lottery tickets offline.
+	#[structopt(name = "ticket")]
+	Ticket(TicketCmd),
+	/// Measure the execution time and storage accesses of the hopr calls.
+	#[cfg(feature = "runtime-benchmarks")]
+	#[structopt(name = "benchmark")]
+	Benchmark(BenchmarkCmd),
+}
+
+impl GetLogFilter for CustomSubcommands {
+	fn get_log_filter(&self) -> Option<String> {
+		None
+	}
+}
+
+/// The `export-spec` command used to write out chain specifications.
+#[derive(Clone, Debug, StructOpt)]
+pub struct ExportSpecCmd {
+	/// Chain to export, either `dev`, `local` or the path to a JSON chain spec.
+	#[structopt(long = "chain", value_name = "CHAIN_SPEC", default_value = "local")]
+	pub chain: String,
+
+	/// Export the genesis storage as raw key-value pairs instead of the genesis config.
+	#[structopt(long = "raw")]
+	pub raw: bool,
+
+	/// File to write the chain spec to. Defaults to stdout.
+	#[structopt(long = "output", short = "o", value_name = "PATH", parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Genesis accounts to use instead of the defaults of the chain.
+	#[structopt(flatten)]
+	pub genesis: GenesisParams,
+}
+
+/// The `benchmark` command used to derive weights and fees of the hopr calls.
+#[cfg(feature = "runtime-benchmarks")]
+#[derive(Clone, Debug, StructOpt)]
+pub struct BenchmarkCmd {
+	/// Call to benchmark, e.g. `redeem_ticket`. Defaults to all calls of the hopr module.
+	#[structopt(long = "extrinsic", value_name = "NAME")]
+	pub extrinsic: Option<String>,
+
+	/// Number of times every call is executed.
+	#[structopt(long = "repeat", value_name = "COUNT", default_value = "100")]
+	pub repeat: u32,
+}
+
+/// Parameters of the `run` command that are specific to the hopr node.
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct RunParams {
+	#[structopt(flatten)]
+	pub genesis: GenesisParams,
+
+	#[structopt(flatten)]
+	pub hopr: HoprParams,
+
+	#[structopt(flatten)]
+	pub metrics: MetricsParams,
+
+	/// SQLite database to write the history of hopr channels to. Only used by full nodes.
+	#[structopt(long = "index-db", value_name = "PATH", parse(from_os_str))]
+	pub index_db: Option<PathBuf>,
+}
+
+impl_augment_clap!(RunParams);
+
+/// Parameters of the Prometheus endpoint of a full node.
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct MetricsParams {
+	/// Serve metrics of the hopr module in the Prometheus format on this port at `/metrics`.
+	#[structopt(long = "prometheus-port", value_name = "PORT")]
+	pub prometheus_port: Option<u16>,
+
+	/// Listen for Prometheus requests on all interfaces instead of only on localhost.
+	#[structopt(long = "prometheus-external")]
+	pub prometheus_external: bool,
+}
+
+impl MetricsParams {
+	/// Address of the metrics endpoint, or `None` if it is disabled.
+	pub fn addr(&self) -> Option<std::net::SocketAddr> {
+		let ip = if self.prometheus_external { [0, 0, 0, 0] } else { [127, 0, 0, 1] };
+		self.prometheus_port.map(|port| (ip, port).into())
+	}
+}
+
+/// Parameters of the hopr account that the node acts for.
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct HoprParams {
+	/// Secret URI of the local hopr account. Enables the automatic redemption of winning tickets,
+	/// or on a light node the following of the channels of the account.
+	#[structopt(long = "hopr-key", value_name = "SURI")]
+	pub hopr_key: Option<String>,
+
+	/// Directory of the received tickets. Defaults to `hopr/tickets` in the directory of the chain.
+	#[structopt(long = "tickets-dir", value_name = "PATH", parse(from_os_str))]
+	pub tickets_dir: Option<PathBuf>,
+
+	/// Counterparty whose channel with the hopr account a light node follows, given as hex public
+	/// key or SS58 address. May be repeated.
+	#[structopt(long = "hopr-counterparty", value_name = "ACCOUNT")]
+	pub counterparties: Vec<String>,
+}
+
+/// Genesis accounts of the built-in chains.
+///
+/// Flags take precedence over the accounts read from `--genesis-accounts`, which in turn
+/// replace the defaults of the chain.
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct GenesisParams {
+	/// JSON file with the `authorities`, `endowedAccounts`, `sudoKey` and `endowment` to use.
+	#[structopt(long = "genesis-accounts", value_name = "PATH", parse(from_os_str))]
+	pub genesis_accounts: Option<PathBuf>,
+
+	/// Aura authority, given as secret URI like `//Alice`. May be repeated.
+	#[structopt(long = "authority", value_name = "SURI")]
+	pub authorities: Vec<String>,
+
+	/// Endowed account, given as secret URI or SS58 address. May be repeated.
+	#[structopt(long = "endowed-account", value_name = "ACCOUNT")]
+	pub endowed_accounts: Vec<String>,
+
+	/// Account that holds the sudo key, given as secret URI or SS58 address.
+	#[structopt(long = "sudo-key", value_name = "ACCOUNT")]
+	pub sudo_key: Option<String>,
+
+	/// Free balance of every endowed account.
+	#[structopt(long = "endowment", value_name = "AMOUNT")]
+	pub endowment: Option<u128>,
+}
+
+impl GenesisParams {
+	/// Whether none of the genesis flags were given.
+	pub fn is_empty(&self) -> bool {
+		self.genesis_accounts.is_none()
+			&& self.authorities.is_empty()
+			&& self.endowed_accounts.is_empty()
+			&& self.sudo_key.is_none()
+			&& self.endowment.is_none()
+	}
+
+	/// Overrides the fields of `accounts` that were given as flags.
+	pub fn apply(&self, accounts: &mut GenesisAccounts) {
+		if !self.authorities.is_empty() {
+			accounts.authorities = self.authorities.clone();
+		}
+		if !self.endowed_accounts.is_empty() {
+			accounts.endowed_accounts = self.endowed_accounts.clone();
+		}
+		if let Some(sudo_key) = &self.sudo_key {
+			accounts.sudo_key = sudo_key.clone();
+		}
+		if let Some(endowment) = self.endowment {
+			accounts.endowment = endowment;
+		}
+	}
+}
+
+/// Offline creation and inspection of lottery tickets.
+#[derive(Clone, Debug, StructOpt)]
+pub enum TicketCmd {
+	/// Create an unsigned ticket.
+	#[structopt(name = "create")]
+	Create(CreateTicketCmd),
+	/// Sign a ticket with the key of the issuer.
+	#[structopt(name = "sign")]
+	Sign(SignTicketCmd),
+	/// Verify the signature of a ticket.
+	#[structopt(name = "verify")]
+	Verify(VerifyTicketCmd),
+	/// Check whether a ticket is a win.
+	#[structopt(name = "check-win")]
+	CheckWin(CheckWinCmd),
+	/// Acknowledge a received packet with the key of the downstream node.
+	#[structopt(name = "acknowledge")]
+	Acknowledge(AcknowledgeCmd),
+	/// Store a received ticket, so that the node redeems it once it is a win.
+	#[structopt(name = "store")]
+	Store(StoreTicketCmd),
+}
+
+/// Encoding of tickets on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TicketFormat {
+	/// SCALE-encoded ticket as hex string.
+	Hex,
+	/// JSON object with one field per ticket field.
+	Json,
+}
+
+impl std::str::FromStr for TicketFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, String> {
+		match s {
+			"hex" => Ok(TicketFormat::Hex),
+			"json" => Ok(TicketFormat::Json),
+			_ => Err(format!("Unknown ticket format `{}`, expected `hex` or `json`.", s)),
+		}
+	}
+}
+
+/// The `ticket create` command.
+#[derive(Clone, Debug, StructOpt)]
+pub struct CreateTicketCmd {
+	/// First key half `s_a` as hex. The challenge is `H(H(s_a), H(s_b))`.
+	#[structopt(long = "s-a", value_name = "HEX", requires = "s_b", conflicts_with = "challenge")]
+	pub s_a: Option<String>,
+
+	/// Second key half `s_b` as hex.
+	#[structopt(long = "s-b", value_name = "HEX", requires = "s_a")]
+	pub s_b: Option<String>,
+
+	/// Challenge of the ticket as hex, if the key halves are not known.
+	#[structopt(long = "challenge", value_name = "HEX", required_unless = "s_a")]
+	pub challenge: Option<String>,
+
+	/// Downstream node that the packet is forwarded to, as hex public key or SS58 address. Only
+	/// its acknowledgement makes the ticket redeemable.
+	#[structopt(long = "next-hop", value_name = "ACCOUNT")]
+	pub next_hop: String,
+
+	/// Current on-chain secret of the recipient as hex.
+	#[structopt(long = "on-chain-secret", value_name = "HEX")]
+	pub on_chain_secret: String,
+
+	/// Amount that the ticket is worth if it wins.
+	#[structopt(long = "amount", value_name = "AMOUNT")]
+	pub amount: u128,
+
+	/// Winning probability as hex, a ticket wins if its hash is smaller.
+	#[structopt(long = "win-prob", value_name = "HEX")]
+	pub win_prob: String,
+
+	/// Last block that the ticket can be redeemed in.
+	#[structopt(long = "expiry", value_name = "BLOCK")]
+	pub expiry: u64,
+
+	/// Output format, either `hex` or `json`.
+	#[structopt(long = "format", value_name = "FORMAT", default_value = "hex")]
+	pub format: TicketFormat,
+}
+
+/// The `ticket sign` command.
+#[derive(Clone, Debug, StructOpt)]
+pub struct SignTicketCmd {
+	/// Ticket as hex or JSON.
+	#[structopt(long = "ticket", value_name = "TICKET")]
+	pub ticket: String,
+
+	/// Secret URI of the issuer, e.g. `//Alice`.
+	#[structopt(long = "suri", value_name = "SURI")]
+	pub suri: String,
+}
+
+/// The `ticket verify` command.
+#[derive(Clone, Debug, StructOpt)]
+pub struct VerifyTicketCmd {
+	/// Ticket as hex or JSON.
+	#[structopt(long = "ticket", value_name = "TICKET")]
+	pub ticket: String,
+
+	/// Signature of the ticket as hex.
+	#[structopt(long = "signature", value_name = "HEX")]
+	pub signature: String,
+
+	/// Public key of the issuer as hex or SS58 address.
+	#[structopt(long = "signer", value_name = "PUBKEY")]
+	pub signer: String,
+}
+
+/// The `ticket check-win` command.
+#[derive(Clone, Debug, StructOpt)]
+pub struct CheckWinCmd {
+	/// Ticket as hex or JSON.
+	#[structopt(long = "ticket", value_name = "TICKET")]
+	pub ticket: String,
+}
+
+/// The `ticket acknowledge` command.
+#[derive(Clone, Debug, StructOpt)]
+pub struct AcknowledgeCmd {
+	/// Challenge of the ticket that paid for the packet, as hex.
+	#[structopt(long = "challenge", value_name = "HEX")]
+	pub challenge: String,
+
+	/// Key half `s_b` of the downstream node as hex.
+	#[structopt(long = "s-b", value_name = "HEX")]
+	pub s_b: String,
+
+	/// Secret URI of the downstream node, e.g. `//Charlie`.
+	#[structopt(long = "suri", value_name = "SURI")]
+	pub suri: String,
+}
+
+/// The `ticket store` command.
+#[derive(Clone, Debug, StructOpt)]
+pub struct StoreTicketCmd {
+	/// Directory of the received tickets, as given to the node with `--tickets-dir`.
+	#[structopt(long = "tickets-dir", value_name = "PATH", parse(from_os_str))]
+	pub tickets_dir: PathBuf,
+
+	/// Ticket as hex or JSON.
+	#[structopt(long = "ticket", value_name = "TICKET")]
+	pub ticket: String,
+
+	/// Signature of the ticket as hex.
+	#[structopt(long = "signature", value_name = "HEX")]
+	pub signature: String,
+
+	/// Issuer of the ticket as hex public key or SS58 address.
+	#[structopt(long = "counterparty", value_name = "ACCOUNT")]
+	pub counterparty: String,
+
+	/// First key half `s_a` as hex.
+	#[structopt(long = "s-a", value_name = "HEX")]
+	pub s_a: String,
+
+	/// Second key half `s_b` as hex, as revealed by the acknowledgement of the downstream node.
+	#[structopt(long = "s-b", value_name = "HEX")]
+	pub s_b: String,
+
+	/// Downstream node that acknowledged the packet, as hex public key or SS58 address.
+	#[structopt(long = "downstream", value_name = "ACCOUNT")]
+	pub downstream: String,
+
+	/// Signature of the downstream node on the acknowledgement as hex.
+	#[structopt(long = "acknowledgement-signature", value_name = "HEX")]
+	pub acknowledgement_signature: String,
+
+	/// Pre-image of the on-chain secret that the ticket was issued for, as hex. It may lie several
+	/// hashes below that secret.
+	#[structopt(long = "pre-image", value_name = "HEX")]
+	pub pre_image: String,
+}
@@ -0,0 +1,556 @@
+//! Redeems winning tickets of the local hopr account.
+//!
+//! The offchain worker API of the runtime cannot read local storage nor sign transactions for
+//! an account, so redemption runs next to the service instead: on every new best block, the
+//! stored tickets are checked against the current on-chain secret of the account and the winning
+//! tickets are submitted to the transaction pool in one `redeem_tickets` call while their channels
+//! still accept them. Channels that the watchtower sees being settled are defended first.
+
+use crate::service::Factory;
+use crate::ticket::{StoredTicket, TicketStore};
+use crate::watchtower::Watchtower;
+use futures::{Future, Stream};
+use hopr_polkadot_runtime::{
+	hopr, opaque::Block, AccountId, AccountNonceApi, Balance, Call, Hash, HoprApi, Moment, Nonce,
+	UncheckedExtrinsic,
+};
+use hopr_primitives::Channel;
+use log::{debug, info, warn};
+use parity_codec::{Compact, Decode, Encode};
+use primitives::{blake2_256, sr25519, Pair};
+use runtime_primitives::generic::{BlockId, Era};
+use runtime_primitives::traits::{BlakeTwo256, Block as BlockT, Hash as HashT, ProvideRuntimeApi};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::Arc;
+use substrate_service::{FullClient, ServiceFactory, TaskExecutor};
+use transaction_pool::txpool::Pool as TransactionPool;
+
+/// Number of blocks after which a redemption that did not make it into a block is submitted again.
+const RESUBMIT_AFTER: u64 = 10;
+
+/// Same as `RESUBMIT_AFTER` for redemptions that defend a channel which is being settled.
+const RESUBMIT_URGENT_AFTER: u64 = 2;
+
+type FullPool = TransactionPool<<Factory as ServiceFactory>::FullTransactionPoolApi>;
+
+/// Signs `call` with `key` as extrinsic with the given index.
+pub fn sign_extrinsic(key: &sr25519::Pair, index: Nonce, call: Call, genesis_hash: Hash) -> UncheckedExtrinsic {
+	let era = Era::immortal();
+	let raw_payload = (Compact(index), call, era, genesis_hash);
+	let signature = raw_payload.using_encoded(|payload| if payload.len() > 256 {
+		key.sign(&blake2_256(payload)[..])
+	} else {
+		key.sign(payload)
+	});
+
+	UncheckedExtrinsic::new_signed(index, raw_payload.1, key.public().into(), signature, era)
+}
+
+/// A winning ticket that can be redeemed right now.
+struct Candidate {
+	ticket: StoredTicket,
+	channel_id: Hash,
+	/// End of the pending window if the channel is pending settlement.
+	deadline: Option<Moment>,
+	/// Number of hashes from the secret of the ticket up to the current on-chain secret.
+	secret_depth: u32,
+	/// Number of hashes from the pre-image of the ticket up to the current on-chain secret.
+	pre_image_depth: u32,
+}
+
+impl Candidate {
+	/// Whether both tickets need the same part of the hash chain, so that only one of them can be
+	/// redeemed, e.g. because they were issued for the same secret.
+	///
+	/// A ticket moves the on-chain secret down to its pre-image and the next ticket has to be
+	/// issued for that secret or one below it.
+	fn overlaps(&self, other: &Candidate) -> bool {
+		self.secret_depth < other.pre_image_depth && other.secret_depth < self.pre_image_depth
+	}
+
+	/// Tickets of channels that are pending settlement are urgent, the earliest deadline first.
+	/// Otherwise the ticket that pays most is preferred.
+	fn is_better_than(&self, other: &Candidate) -> bool {
+		match (self.deadline, other.deadline) {
+			(Some(a), Some(b)) if a != b => a < b,
+			(Some(_), None) => true,
+			(None, Some(_)) => false,
+			_ => self.ticket.amount > other.ticket.amount,
+		}
+	}
+}
+
+/// The candidates that a single `redeem_tickets` call redeems, in the order that it expects.
+///
+/// Better candidates are picked first, see `Candidate::is_better_than`, and candidates that
+/// overlap with a picked one are left for later.
+fn batch(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+	candidates.sort_by(|a, b| if a.is_better_than(b) {
+		Ordering::Less
+	} else if b.is_better_than(a) {
+		Ordering::Greater
+	} else {
+		Ordering::Equal
+	});
+
+	let mut batch: Vec<Candidate> = Vec::new();
+	for candidate in candidates {
+		if batch.len() == hopr::MAX_REDEEMED_TICKETS {
+			break;
+		}
+		if !batch.iter().any(|picked| picked.overlaps(&candidate)) {
+			batch.push(candidate);
+		}
+	}
+
+	// Every ticket leaves the pre-image that it reveals as on-chain secret for the next one.
+	batch.sort_by_key(|candidate| candidate.secret_depth);
+	batch
+}
+
+/// Number of hashes from `value` up to `secret`, if it lies at most `max_depth` hashes below it.
+fn depth(value: &Hash, secret: &Hash, max_depth: u32) -> Option<u32> {
+	let mut current = *value;
+	for depth in 0..=max_depth {
+		if current == *secret {
+			return Some(depth);
+		}
+		current = BlakeTwo256::hash(current.as_ref());
+	}
+	None
+}
+
+/// What to do with a stored ticket.
+#[derive(Debug, PartialEq)]
+enum Verdict {
+	/// Redeem the ticket in the given channel, before the deadline if the channel is pending
+	/// settlement.
+	Redeem(Hash, Option<Moment>),
+	/// Keep the ticket, it might become redeemable later.
+	Keep,
+	/// Remove the ticket, it can never be redeemed anymore.
+	Remove,
+}
+
+/// The state of the chain that decides which tickets can be redeemed.
+struct Context {
+	account: AccountId,
+	/// Number of the best block, redemptions go into the block after it.
+	number: u64,
+	/// Current on-chain secret of the account.
+	secret: Hash,
+	/// Previous on-chain secrets of the account, see `superseded`.
+	superseded: HashSet<Hash>,
+	now: Moment,
+	max_pre_image_depth: u32,
+}
+
+/// The on-chain secrets that `secret` replaced, as far back as a single `redeem_tickets` call can
+/// move the secret, i.e. `H(secret), H(H(secret)), ..`.
+///
+/// A redemption may reveal a pre-image several hashes below the on-chain secret, so all secrets in
+/// between are skipped at once, not only the previous one.
+fn superseded(secret: &Hash, max_pre_image_depth: u32) -> HashSet<Hash> {
+	let len = hopr::MAX_REDEEMED_TICKETS as u32 * max_pre_image_depth;
+	let mut superseded = HashSet::with_capacity(len as usize);
+	let mut value = *secret;
+	for _ in 0..len {
+		value = BlakeTwo256::hash(value.as_ref());
+		superseded.insert(value);
+	}
+	superseded
+}
+
+impl Context {
+	/// Checks whether `stored` wins and can be redeemed with the current on-chain secret.
+	///
+	/// `channel` looks up a channel by its id and is only called for tickets that could be
+	/// redeemed otherwise.
+	fn check<F>(&self, stored: &StoredTicket, channel: F) -> Result<Verdict, String>
+	where
+		F: FnOnce(Hash) -> Result<Channel<Balance, Moment>, String>,
+	{
+		let ticket = stored.ticket();
+
+		// Every redemption replaces the on-chain secret by a pre-image, so the tickets that were
+		// issued for the replaced secrets can no longer be redeemed.
+		let outdated = self.superseded.contains(ticket.on_chain_secret());
+		if !ticket.is_win::<BlakeTwo256>() || outdated || ticket.is_expired(&(self.number + 1)) {
+			return Ok(Verdict::Remove);
+		}
+
+		// The pre-image may lie several hashes below the on-chain secret, as long as the ticket was
+		// issued for a secret in between.
+		let redeemable = hopr_primitives::hash_chain_contains::<BlakeTwo256>(&stored.pre_image, &self.secret, ticket.on_chain_secret(), self.max_pre_image_depth)
+			.unwrap_or(false);
+		if !redeemable {
+			return Ok(Verdict::Keep);
+		}
+
+		let channel_id = hopr_primitives::channel_id::<BlakeTwo256, _>(&self.account, &stored.counterparty());
+		Ok(match channel(channel_id)? {
+			Channel::Active(_) => Verdict::Redeem(channel_id, None),
+			Channel::PendingSettlement(_, deadline) if self.now <= deadline => Verdict::Redeem(channel_id, Some(deadline)),
+			// The channel might still get activated.
+			Channel::Funded(_) => Verdict::Keep,
+			Channel::PendingSettlement(..) | Channel::Uninitialized => Verdict::Remove,
+		})
+	}
+}
+
+/// Redeems the winning tickets of one account.
+pub struct Redeemer {
+	client: Arc<FullClient<Factory>>,
+	pool: Arc<FullPool>,
+	key: sr25519::Pair,
+	store: TicketStore,
+	watchtower: Watchtower,
+	/// Ids of the last submitted tickets and the block they were submitted at.
+	last_submission: Option<(Vec<Hash>, u64)>,
+}
+
+impl Redeemer {
+	pub fn new(client: Arc<FullClient<Factory>>, pool: Arc<FullPool>, key: sr25519::Pair, store: TicketStore) -> Self {
+		Redeemer {
+			watchtower: Watchtower::new(key.public()),
+			client,
+			pool,
+			key,
+			store,
+			last_submission: None,
+		}
+	}
+
+	/// Checks the stored tickets against the state at the new best block `hash`.
+	pub fn on_new_best(&mut self, hash: Hash, number: u64) -> Result<(), String> {
+		let at = BlockId::hash(hash);
+		let account = self.key.public();
+
+		let secret = match self.client.runtime_api().on_chain_secret(&at, account.clone()).map_err(|e| format!("{:?}", e))? {
+			Some(secret) => secret,
+			None => {
+				debug!(target: "hopr", "Account {:?} has not called init() yet", account);
+				return Ok(());
+			},
+		};
+
+		let tickets = self.store.tickets().map_err(|e| format!("Error reading tickets: {}", e))?;
+		let events = self.client.runtime_api().events(&at).map_err(|e| format!("{:?}", e))?;
+		self.watchtower.on_events(&events, tickets.iter().map(StoredTicket::counterparty));
+
+		let candidates = self.candidates(&at, number, secret, tickets)?;
+		self.update_disputes(&at, &candidates)?;
+
+		let batch = batch(candidates);
+		if batch.is_empty() {
+			return Ok(());
+		}
+		let urgent = batch.iter().any(|candidate| self.watchtower.is_disputed(&candidate.channel_id));
+		self.submit(&at, number, &batch, urgent)
+	}
+
+	/// Lets the watchtower know about the state of the channels that are being settled.
+	fn update_disputes(&mut self, at: &BlockId<Block>, candidates: &[Candidate]) -> Result<(), String> {
+		let api = self.client.runtime_api();
+		let now = api.now(at).map_err(|e| format!("{:?}", e))?;
+
+		let disputes: Vec<Hash> = self.watchtower.disputes().cloned().collect();
+		for channel_id in disputes {
+			let channel = api.channel(at, channel_id).map_err(|e| format!("{:?}", e))?;
+			let has_evidence = candidates.iter().any(|candidate| candidate.channel_id == channel_id);
+			self.watchtower.update(&channel_id, &channel, now, has_evidence);
+		}
+
+		Ok(())
+	}
+
+	/// The stored tickets that win and can be redeemed with the current on-chain secret in the
+	/// block after `number`.
+	///
+	/// Tickets that can never be redeemed anymore are removed from the store.
+	fn candidates(&self, at: &BlockId<Block>, number: u64, secret: Hash, tickets: Vec<StoredTicket>) -> Result<Vec<Candidate>, String> {
+		let api = self.client.runtime_api();
+		let max_pre_image_depth = api.max_pre_image_depth(at).map_err(|e| format!("{:?}", e))?;
+		let context = Context {
+			account: self.key.public(),
+			number,
+			secret,
+			superseded: superseded(&secret, max_pre_image_depth),
+			now: api.now(at).map_err(|e| format!("{:?}", e))?,
+			max_pre_image_depth,
+		};
+
+		let mut candidates = Vec::new();
+		for stored in tickets {
+			match context.check(&stored, |channel_id| api.channel(at, channel_id).map_err(|e| format!("{:?}", e)))? {
+				Verdict::Redeem(channel_id, deadline) => {
+					// Both lie within the maximum depth, otherwise the ticket would not be redeemable.
+					let secret_depth = depth(&stored.on_chain_secret, &secret, max_pre_image_depth);
+					let pre_image_depth = depth(&stored.pre_image, &secret, max_pre_image_depth);
+					if let (Some(secret_depth), Some(pre_image_depth)) = (secret_depth, pre_image_depth) {
+						candidates.push(Candidate { ticket: stored, channel_id, deadline, secret_depth, pre_image_depth });
+					}
+				},
+				Verdict::Keep => {},
+				Verdict::Remove => self.remove(&stored),
+			}
+		}
+
+		Ok(candidates)
+	}
+
+	/// Submits a `redeem_tickets` transaction for `batch` unless that has happened recently.
+	///
+	/// Urgent redemptions defend a channel that is being settled and are resubmitted sooner.
+	fn submit(&mut self, at: &BlockId<Block>, number: u64, batch: &[Candidate], urgent: bool) -> Result<(), String> {
+		let ids: Vec<Hash> = batch.iter().map(|candidate| candidate.ticket.id()).collect();
+		let resubmit_after = if urgent { RESUBMIT_URGENT_AFTER } else { RESUBMIT_AFTER };
+		if let Some((last_ids, last_number)) = &self.last_submission {
+			if *last_ids == ids && number < last_number + resubmit_after {
+				return Ok(());
+			}
+		}
+
+		let account = self.key.public();
+		let index = self.client.runtime_api().account_nonce(at, account).map_err(|e| format!("{:?}", e))?;
+		let genesis_hash = self.client.info().map_err(|e| format!("{:?}", e))?.chain.genesis_hash;
+
+		let tickets = batch.iter().map(|candidate| {
+			let stored = &candidate.ticket;
+			hopr::TicketRedemption {
+				signature: stored.signature(),
+				counterparty: stored.counterparty(),
+				on_chain_secret: stored.on_chain_secret,
+				pre_image: stored.pre_image,
+				s_a: stored.s_a,
+				acknowledgement: stored.acknowledgement(),
+				acknowledgement_signature: stored.acknowledgement_signature(),
+				downstream: stored.downstream(),
+				amount: stored.amount,
+				win_prob: stored.win_prob,
+				expiry: stored.expiry,
+			}
+		}).collect();
+		let call = Call::Hopr(hopr::Call::redeem_tickets(tickets));
+		let xt = sign_extrinsic(&self.key, index, call, genesis_hash).encode();
+		let xt = <Block as BlockT>::Extrinsic::decode(&mut &xt[..]).ok_or("Extrinsic encoding is compatible; qed")?;
+
+		self.pool.submit_one(at, xt).map_err(|e| format!("Error submitting tickets {:?}: {:?}", ids, e))?;
+
+		let amount: Balance = batch.iter().map(|candidate| candidate.ticket.amount).sum();
+		info!(target: "hopr", "Submitted redemption of {} tickets worth {}", ids.len(), amount);
+		self.last_submission = Some((ids, number));
+		Ok(())
+	}
+
+	fn remove(&self, stored: &StoredTicket) {
+		if let Err(e) = self.store.remove(&stored.id()) {
+			warn!(target: "hopr", "Error removing ticket {:?}: {}", stored.id(), e);
+		}
+	}
+}
+
+/// Starts redeeming the winning tickets in `store` with `key`.
+pub fn start(
+	service: &<Factory as ServiceFactory>::FullService,
+	key: sr25519::Pair,
+	store: TicketStore,
+	executor: &TaskExecutor,
+) {
+	info!(target: "hopr", "Redeeming winning tickets of {:?}", key.public());
+
+	let mut redeemer = Redeemer::new(service.client(), service.transaction_pool(), key, store);
+	let task = service.client().import_notification_stream()
+		.filter(|notification| notification.is_new_best)
+		.for_each(move |notification| {
+			if let Err(e) = redeemer.on_new_best(notification.hash, notification.header.number) {
+				warn!(target: "hopr", "Error redeeming tickets: {}", e);
+			}
+			Ok(())
+		});
+
+	executor.spawn(task.select(service.on_exit()).then(|_| Ok(())));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hopr_primitives::ChannelBalance;
+	use primitives::H512;
+
+	fn account(seed: &str) -> AccountId {
+		sr25519::Pair::from_string(seed, None).unwrap().public()
+	}
+
+	/// A winning ticket from Bob for the on-chain secret `hash(pre_image)`.
+	fn stored(pre_image: Hash, expiry: u64) -> StoredTicket {
+		StoredTicket {
+			counterparty: Hash::from_slice(account("//Bob").as_ref()),
+			signature: H512::repeat_byte(1),
+			pre_image,
+			s_a: Hash::repeat_byte(2),
+			s_b: Hash::repeat_byte(3),
+			downstream: Hash::from_slice(account("//Charlie").as_ref()),
+			acknowledgement_signature: H512::repeat_byte(4),
+			on_chain_secret: BlakeTwo256::hash(pre_image.as_ref()),
+			amount: 10,
+			win_prob: Hash::repeat_byte(0xff),
+			expiry,
+		}
+	}
+
+	fn context(secret: Hash) -> Context {
+		Context {
+			account: account("//Alice"),
+			number: 5,
+			secret,
+			superseded: superseded(&secret, 1),
+			now: 100,
+			max_pre_image_depth: 1,
+		}
+	}
+
+	fn channel_id() -> Hash {
+		hopr_primitives::channel_id::<BlakeTwo256, _>(&account("//Alice"), &account("//Bob"))
+	}
+
+	fn active(_: Hash) -> Result<Channel<Balance, Moment>, String> {
+		Ok(Channel::Active(ChannelBalance::new(20, 10)))
+	}
+
+	fn unused(_: Hash) -> Result<Channel<Balance, Moment>, String> {
+		Err("channel must not be looked up".into())
+	}
+
+	#[test]
+	fn redeems_winning_ticket_of_active_channel() {
+		let stored = stored(Hash::repeat_byte(7), 10);
+		let context = context(stored.on_chain_secret);
+
+		assert_eq!(context.check(&stored, active), Ok(Verdict::Redeem(channel_id(), None)));
+	}
+
+	#[test]
+	fn redeems_before_deadline_of_pending_settlement() {
+		let stored = stored(Hash::repeat_byte(7), 10);
+		let context = context(stored.on_chain_secret);
+		let pending = |deadline| move |_| -> Result<Channel<Balance, Moment>, String> {
+			Ok(Channel::PendingSettlement(ChannelBalance::new(20, 10), deadline))
+		};
+
+		assert_eq!(context.check(&stored, pending(100)), Ok(Verdict::Redeem(channel_id(), Some(100))));
+		assert_eq!(context.check(&stored, pending(99)), Ok(Verdict::Remove));
+	}
+
+	#[test]
+	fn keeps_ticket_of_funded_channel() {
+		let stored = stored(Hash::repeat_byte(7), 10);
+		let context = context(stored.on_chain_secret);
+
+		assert_eq!(context.check(&stored, |_| Ok(Channel::Funded(ChannelBalance::new(20, 10)))), Ok(Verdict::Keep));
+		assert_eq!(context.check(&stored, |_| Ok(Channel::Uninitialized)), Ok(Verdict::Remove));
+	}
+
+	#[test]
+	fn removes_losing_expired_and_outdated_tickets() {
+		let winning = stored(Hash::repeat_byte(7), 10);
+		let context = context(winning.on_chain_secret);
+
+		let losing = StoredTicket { win_prob: Hash::zero(), ..winning.clone() };
+		assert_eq!(context.check(&losing, unused), Ok(Verdict::Remove));
+
+		// Redeemable up to and including the block after the best block.
+		let expiring = stored(Hash::repeat_byte(7), 6);
+		assert_eq!(context.check(&expiring, active), Ok(Verdict::Redeem(channel_id(), None)));
+		let expired = stored(Hash::repeat_byte(7), 5);
+		assert_eq!(context.check(&expired, unused), Ok(Verdict::Remove));
+
+		// The on-chain secret moved on to the pre-image of the one the ticket was issued for.
+		let context = Context {
+			secret: winning.pre_image,
+			superseded: superseded(&winning.pre_image, 1),
+			..context
+		};
+		assert_eq!(context.check(&winning, unused), Ok(Verdict::Remove));
+	}
+
+	#[test]
+	fn removes_tickets_of_skipped_secrets() {
+		// A redemption revealed a pre-image three hashes below the secret the ticket was issued for,
+		// skipping the secrets in between.
+		let pre_image = Hash::repeat_byte(7);
+		let first = BlakeTwo256::hash(pre_image.as_ref());
+		let skipped = stored(first, 10);
+
+		let jumped = context(pre_image);
+		assert_eq!(jumped.check(&skipped, unused), Ok(Verdict::Remove));
+		assert_eq!(jumped.check(&stored(pre_image, 10), unused), Ok(Verdict::Remove));
+		assert_eq!(jumped.check(&stored(Hash::repeat_byte(8), 10), unused), Ok(Verdict::Keep));
+	}
+
+	#[test]
+	fn follows_pre_image_up_to_max_depth() {
+		// The ticket was issued for `hash(pre_image)`, the on-chain secret is one hash above it.
+		let stored = stored(Hash::repeat_byte(7), 10);
+		let secret = BlakeTwo256::hash(stored.on_chain_secret.as_ref());
+
+		let shallow = context(secret);
+		assert_eq!(shallow.check(&stored, unused), Ok(Verdict::Keep));
+
+		let deep = Context { max_pre_image_depth: 2, ..context(secret) };
+		assert_eq!(deep.check(&stored, active), Ok(Verdict::Redeem(channel_id(), None)));
+
+		// Not a pre-image of the on-chain secret at all.
+		let unrelated = context(Hash::repeat_byte(9));
+		assert_eq!(unrelated.check(&stored, unused), Ok(Verdict::Keep));
+	}
+
+	/// A candidate worth `amount` for the secret `secret_depth` hashes below the on-chain secret
+	/// that reveals the pre-image `pre_image_depth` hashes below it.
+	fn candidate(amount: Balance, deadline: Option<Moment>, secret_depth: u32, pre_image_depth: u32) -> Candidate {
+		Candidate {
+			ticket: StoredTicket { amount, ..stored(Hash::repeat_byte(7), 10) },
+			channel_id: channel_id(),
+			deadline,
+			secret_depth,
+			pre_image_depth,
+		}
+	}
+
+	#[test]
+	fn prefers_earliest_deadline_then_amount() {
+		let ticket = |amount, deadline| candidate(amount, deadline, 0, 1);
+
+		assert!(ticket(1, Some(10)).is_better_than(&ticket(5, None)));
+		assert!(ticket(1, Some(10)).is_better_than(&ticket(5, Some(11))));
+		assert!(!ticket(5, Some(11)).is_better_than(&ticket(1, Some(10))));
+		assert!(ticket(5, None).is_better_than(&ticket(1, None)));
+	}
+
+	#[test]
+	fn batches_tickets_along_the_hash_chain() {
+		let batch = batch(vec![
+			candidate(5, None, 0, 1),
+			// Issued for the same secret as the ticket above, but pays more.
+			candidate(10, None, 0, 1),
+			candidate(1, None, 1, 2),
+			// Overlaps with the urgent ticket below.
+			candidate(3, None, 3, 4),
+			candidate(2, Some(100), 2, 4),
+		]);
+
+		let picked: Vec<_> = batch.iter().map(|candidate| (candidate.ticket.amount, candidate.secret_depth)).collect();
+		assert_eq!(picked, vec![(10, 0), (1, 1), (2, 2)]);
+	}
+
+	#[test]
+	fn depth_below_secret() {
+		let pre_image = Hash::repeat_byte(7);
+		let secret = BlakeTwo256::hash(BlakeTwo256::hash(pre_image.as_ref()).as_ref());
+
+		assert_eq!(depth(&secret, &secret, 0), Some(0));
+		assert_eq!(depth(&pre_image, &secret, 2), Some(2));
+		assert_eq!(depth(&pre_image, &secret, 1), None);
+	}
+}
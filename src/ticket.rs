@@ -0,0 +1,479 @@
+//! Offline creation, signing and inspection of lottery tickets.
+//!
+//! Tickets are encoded exactly like `redeem_ticket` in the runtime encodes them before checking
+//! the signature, so tickets that pass `verify` and `check-win` can be redeemed on-chain.
+
+use crate::params::{
+	AcknowledgeCmd, CheckWinCmd, CreateTicketCmd, SignTicketCmd, StoreTicketCmd, TicketCmd, TicketFormat,
+	VerifyTicketCmd,
+};
+use hopr_polkadot_runtime::{AccountId, Balance, BlockNumber, Hash, MAX_PRE_IMAGE_DEPTH_LIMIT};
+use hopr_primitives::{self as hopr, Acknowledgement, LotteryTicket};
+use log::warn;
+use parity_codec::{Decode, Encode};
+use primitives::{crypto::Ss58Codec, sr25519, Pair, H512};
+use runtime_primitives::traits::{BlakeTwo256, Hash as HashT};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::{Path, PathBuf}};
+use substrate_cli::error;
+
+/// A ticket as it is checked by the runtime.
+pub type Ticket = LotteryTicket<Hash, Balance, BlockNumber>;
+
+/// A received ticket together with everything that is needed to redeem it.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StoredTicket {
+	/// Public key of the issuer.
+	pub counterparty: Hash,
+	/// Signature of the issuer.
+	pub signature: H512,
+	/// Pre-image of the on-chain secret of the recipient.
+	pub pre_image: Hash,
+	/// First key half.
+	pub s_a: Hash,
+	/// Second key half, as revealed by the acknowledgement of the downstream node.
+	pub s_b: Hash,
+	/// Public key of the downstream node.
+	pub downstream: Hash,
+	/// Signature of the downstream node on the acknowledgement.
+	pub acknowledgement_signature: H512,
+	/// The on-chain secret the ticket was issued for.
+	pub on_chain_secret: Hash,
+	/// Amount that the ticket is worth if it wins.
+	pub amount: Balance,
+	/// Winning probability of the ticket.
+	pub win_prob: Hash,
+	/// Last block that the ticket can be redeemed in.
+	pub expiry: BlockNumber,
+}
+
+impl StoredTicket {
+	/// The ticket as it is checked by the runtime.
+	pub fn ticket(&self) -> Ticket {
+		Ticket::new(
+			hopr::challenge::<BlakeTwo256>(self.s_a.as_ref(), self.s_b.as_ref()),
+			hopr::next_hop::<BlakeTwo256>(self.downstream.as_ref()),
+			self.on_chain_secret,
+			self.amount,
+			self.win_prob,
+			self.expiry,
+		)
+	}
+
+	/// The acknowledgement of the downstream node, as it is checked by the runtime.
+	pub fn acknowledgement(&self) -> Acknowledgement<Hash> {
+		Acknowledgement::new(*self.ticket().challenge(), self.s_b)
+	}
+
+	pub fn counterparty(&self) -> AccountId {
+		AccountId::from_slice(self.counterparty.as_ref())
+	}
+
+	pub fn downstream(&self) -> AccountId {
+		AccountId::from_slice(self.downstream.as_ref())
+	}
+
+	pub fn signature(&self) -> sr25519::Signature {
+		sr25519::Signature::from_slice(self.signature.as_ref())
+	}
+
+	pub fn acknowledgement_signature(&self) -> sr25519::Signature {
+		sr25519::Signature::from_slice(self.acknowledgement_signature.as_ref())
+	}
+
+	/// The runtime uses the hash of the signature as nonce, so it identifies the ticket.
+	pub fn id(&self) -> Hash {
+		BlakeTwo256::hash(self.signature.as_ref())
+	}
+}
+
+/// Directory of received tickets with one JSON file per ticket.
+///
+/// Tickets are written by `ticket store` or by any other HOPR component that uses the same
+/// format and read by the node to redeem them.
+pub struct TicketStore {
+	dir: PathBuf,
+}
+
+impl TicketStore {
+	/// Opens the store in `dir` and creates the directory if necessary.
+	pub fn open(dir: &Path) -> io::Result<Self> {
+		fs::create_dir_all(dir)?;
+		Ok(TicketStore { dir: dir.to_path_buf() })
+	}
+
+	/// Stores `ticket` and returns its id.
+	pub fn insert(&self, ticket: &StoredTicket) -> io::Result<Hash> {
+		let id = ticket.id();
+		let json = serde_json::to_vec_pretty(ticket)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		fs::write(self.path(&id), json)?;
+		Ok(id)
+	}
+
+	/// All stored tickets. Files that cannot be parsed are skipped.
+	pub fn tickets(&self) -> io::Result<Vec<StoredTicket>> {
+		let mut tickets = Vec::new();
+		for entry in fs::read_dir(&self.dir)? {
+			let path = entry?.path();
+			if path.extension().map_or(true, |ext| ext != "json") {
+				continue;
+			}
+
+			match fs::read(&path).map(|json| serde_json::from_slice::<StoredTicket>(&json)) {
+				Ok(Ok(ticket)) => tickets.push(ticket),
+				Ok(Err(e)) => warn!("Skipping invalid ticket {}: {}", path.display(), e),
+				Err(e) => warn!("Skipping unreadable ticket {}: {}", path.display(), e),
+			}
+		}
+		Ok(tickets)
+	}
+
+	/// Removes the ticket with the given id.
+	pub fn remove(&self, id: &Hash) -> io::Result<()> {
+		match fs::remove_file(self.path(id)) {
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+			res => res,
+		}
+	}
+
+	fn path(&self, id: &Hash) -> PathBuf {
+		self.dir.join(format!("{}.json", hex::encode(id)))
+	}
+}
+
+/// Runs one of the `ticket` subcommands.
+pub fn run(cmd: TicketCmd) -> error::Result<()> {
+	match cmd {
+		TicketCmd::Create(cmd) => create(cmd),
+		TicketCmd::Sign(cmd) => sign(cmd),
+		TicketCmd::Verify(cmd) => verify(cmd),
+		TicketCmd::CheckWin(cmd) => check_win(cmd),
+		TicketCmd::Acknowledge(cmd) => acknowledge(cmd),
+		TicketCmd::Store(cmd) => store(cmd),
+	}
+}
+
+fn create(cmd: CreateTicketCmd) -> error::Result<()> {
+	let challenge = match (cmd.s_a, cmd.s_b, cmd.challenge) {
+		(Some(s_a), Some(s_b), _) => hopr::challenge::<BlakeTwo256>(
+			parse_hash(&s_a)?.as_ref(),
+			parse_hash(&s_b)?.as_ref(),
+		),
+		(_, _, Some(challenge)) => parse_hash(&challenge)?,
+		_ => return Err("Either both key halves or the challenge must be given.".into()),
+	};
+
+	let ticket = Ticket::new(
+		challenge,
+		hopr::next_hop::<BlakeTwo256>(parse_public(&cmd.next_hop)?.as_ref()),
+		parse_hash(&cmd.on_chain_secret)?,
+		cmd.amount,
+		parse_hash(&cmd.win_prob)?,
+		cmd.expiry,
+	);
+
+	println!("{}", encode_ticket(&ticket, cmd.format)?);
+	Ok(())
+}
+
+fn sign(cmd: SignTicketCmd) -> error::Result<()> {
+	let ticket = parse_ticket(&cmd.ticket)?;
+	let pair = sr25519::Pair::from_string(&cmd.suri, None)
+		.map_err(|e| format!("Invalid secret URI: {:?}", e))?;
+
+	println!("0x{}", hex::encode(sign_ticket(&ticket, &pair).as_ref()));
+
+	Ok(())
+}
+
+fn verify(cmd: VerifyTicketCmd) -> error::Result<()> {
+	let ticket = parse_ticket(&cmd.ticket)?;
+	let signer = parse_public(&cmd.signer)?;
+	let signature = parse_signature(&cmd.signature)?;
+
+	if !verify_ticket(&ticket, &signature, &signer) {
+		return Err("Invalid signature.".into());
+	}
+
+	println!("Signature is valid.");
+	Ok(())
+}
+
+fn check_win(cmd: CheckWinCmd) -> error::Result<()> {
+	let ticket = parse_ticket(&cmd.ticket)?;
+
+	println!("Ticket hash: {:?}", BlakeTwo256::hash_of(&ticket));
+	println!("Win: {}", ticket.is_win::<BlakeTwo256>());
+
+	Ok(())
+}
+
+fn acknowledge(cmd: AcknowledgeCmd) -> error::Result<()> {
+	let acknowledgement = Acknowledgement::new(parse_hash(&cmd.challenge)?, parse_hash(&cmd.s_b)?);
+	let pair = sr25519::Pair::from_string(&cmd.suri, None)
+		.map_err(|e| format!("Invalid secret URI: {:?}", e))?;
+
+	let signature = pair.sign(&hopr::acknowledgement_message(&acknowledgement));
+	println!("0x{}", hex::encode(signature.as_ref()));
+
+	Ok(())
+}
+
+fn store(cmd: StoreTicketCmd) -> error::Result<()> {
+	let ticket = parse_ticket(&cmd.ticket)?;
+	let signature = parse_signature(&cmd.signature)?;
+	let acknowledgement_signature = parse_signature(&cmd.acknowledgement_signature)?;
+
+	let stored = StoredTicket {
+		counterparty: Hash::from_slice(parse_public(&cmd.counterparty)?.as_ref()),
+		signature,
+		pre_image: parse_hash(&cmd.pre_image)?,
+		s_a: parse_hash(&cmd.s_a)?,
+		s_b: parse_hash(&cmd.s_b)?,
+		downstream: Hash::from_slice(parse_public(&cmd.downstream)?.as_ref()),
+		acknowledgement_signature,
+		on_chain_secret: *ticket.on_chain_secret(),
+		amount: *ticket.amount(),
+		win_prob: *ticket.win_prob(),
+		expiry: *ticket.expiry(),
+	};
+
+	if stored.ticket() != ticket {
+		return Err("Key halves or downstream node do not match the ticket.".into());
+	}
+	if !verify_ticket(&ticket, &stored.signature, &stored.counterparty()) {
+		return Err("Invalid signature of the counterparty.".into());
+	}
+	// The node keeps tickets whose pre-image lies deeper than the depth that the chain currently
+	// allows, but no chain accepts pre-images below the limit.
+	if hopr::hash_chain_contains::<BlakeTwo256>(&stored.pre_image, &stored.on_chain_secret, &stored.on_chain_secret, MAX_PRE_IMAGE_DEPTH_LIMIT).is_none() {
+		return Err("Given value is not a pre-image of the on-chain secret of the ticket.".into());
+	}
+	let acknowledgement_message = hopr::acknowledgement_message(&stored.acknowledgement());
+	if !sr25519::Pair::verify(&stored.acknowledgement_signature(), &acknowledgement_message, &stored.downstream()) {
+		return Err("Invalid acknowledgement signature of the downstream node.".into());
+	}
+
+	let id = TicketStore::open(&cmd.tickets_dir)?.insert(&stored)?;
+	println!("Stored ticket {:?}", id);
+
+	Ok(())
+}
+
+/// Encodes a ticket in the given format, the inverse of `parse_ticket`.
+fn encode_ticket(ticket: &Ticket, format: TicketFormat) -> Result<String, String> {
+	match format {
+		TicketFormat::Hex => Ok(format!("0x{}", hex::encode(ticket.encode()))),
+		TicketFormat::Json => serde_json::to_string_pretty(ticket)
+			.map_err(|e| format!("Error serializing ticket: {}", e)),
+	}
+}
+
+fn sign_ticket(ticket: &Ticket, pair: &sr25519::Pair) -> H512 {
+	H512::from_slice(pair.sign(ticket.encode().as_slice()).as_ref())
+}
+
+fn verify_ticket(ticket: &Ticket, signature: &H512, signer: &AccountId) -> bool {
+	let signature = sr25519::Signature::from_slice(signature.as_ref());
+	sr25519::Pair::verify(&signature, ticket.encode().as_slice(), signer)
+}
+
+/// Parses a ticket given either as JSON object or as SCALE-encoded hex string.
+fn parse_ticket(s: &str) -> Result<Ticket, String> {
+	let s = s.trim();
+	if s.starts_with('{') {
+		return serde_json::from_str(s).map_err(|e| format!("Invalid ticket: {}", e));
+	}
+
+	let bytes = decode_hex(s)?;
+	let input = &mut &bytes[..];
+	match Ticket::decode(input) {
+		Some(ticket) if input.is_empty() => Ok(ticket),
+		_ => Err("Invalid ticket encoding.".into()),
+	}
+}
+
+/// Parses a public key given either as hex string or as SS58 address.
+pub(crate) fn parse_public(s: &str) -> Result<AccountId, String> {
+	if let Ok(public) = AccountId::from_ss58check(s) {
+		return Ok(public);
+	}
+
+	let bytes = decode_hex(s)?;
+	if bytes.len() != 32 {
+		return Err(format!("Public key must be 32 bytes long, got {} bytes.", bytes.len()));
+	}
+	Ok(AccountId::from_slice(&bytes))
+}
+
+fn parse_signature(s: &str) -> Result<H512, String> {
+	let bytes = decode_hex(s)?;
+	if bytes.len() != 64 {
+		return Err(format!("Signature must be 64 bytes long, got {} bytes.", bytes.len()));
+	}
+	Ok(H512::from_slice(&bytes))
+}
+
+fn parse_hash(s: &str) -> Result<Hash, String> {
+	let bytes = decode_hex(s)?;
+	if bytes.len() != 32 {
+		return Err(format!("Expected 32 bytes, got {} bytes in `{}`.", bytes.len(), s));
+	}
+	Ok(Hash::from_slice(&bytes))
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+	let s = s.trim();
+	let s = if s.starts_with("0x") { &s[2..] } else { s };
+	hex::decode(s).map_err(|e| format!("Invalid hex string `{}`: {}", s, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ticket(win_prob: Hash) -> Ticket {
+		Ticket::new(
+			hopr::challenge::<BlakeTwo256>(&[1u8; 32], &[2u8; 32]),
+			hopr::next_hop::<BlakeTwo256>(&[4u8; 32]),
+			Hash::repeat_byte(3),
+			10,
+			win_prob,
+			100,
+		)
+	}
+
+	fn alice() -> sr25519::Pair {
+		sr25519::Pair::from_string("//Alice", None).unwrap()
+	}
+
+	#[test]
+	fn hex_round_trip() {
+		let ticket = ticket(Hash::repeat_byte(0xff));
+		let encoded = encode_ticket(&ticket, TicketFormat::Hex).unwrap();
+
+		assert!(encoded.starts_with("0x"));
+		assert_eq!(parse_ticket(&encoded), Ok(ticket.clone()));
+		assert_eq!(parse_ticket(&encoded[2..]), Ok(ticket));
+	}
+
+	#[test]
+	fn json_round_trip() {
+		let ticket = ticket(Hash::repeat_byte(0xff));
+		let encoded = encode_ticket(&ticket, TicketFormat::Json).unwrap();
+
+		assert!(encoded.contains("\"onChainSecret\""));
+		assert_eq!(parse_ticket(&encoded), Ok(ticket));
+	}
+
+	#[test]
+	fn sign_and_verify() {
+		let ticket = ticket(Hash::repeat_byte(0xff));
+		let signature = sign_ticket(&ticket, &alice());
+		let parsed = parse_signature(&format!("0x{}", hex::encode(signature))).unwrap();
+
+		assert!(verify_ticket(&ticket, &parsed, &alice().public()));
+
+		let bob = sr25519::Pair::from_string("//Bob", None).unwrap();
+		assert!(!verify_ticket(&ticket, &parsed, &bob.public()));
+
+		let other = Ticket::new(*ticket.challenge(), *ticket.next_hop(), *ticket.on_chain_secret(), 11, *ticket.win_prob(), 100);
+		assert!(!verify_ticket(&other, &parsed, &alice().public()));
+	}
+
+	#[test]
+	fn check_win_of_parsed_ticket() {
+		let always = ticket(Hash::repeat_byte(0xff));
+		let never = ticket(Hash::zero());
+
+		let parsed = parse_ticket(&encode_ticket(&always, TicketFormat::Hex).unwrap()).unwrap();
+		assert!(parsed.is_win::<BlakeTwo256>());
+		let parsed = parse_ticket(&encode_ticket(&never, TicketFormat::Json).unwrap()).unwrap();
+		assert!(!parsed.is_win::<BlakeTwo256>());
+	}
+
+	#[test]
+	fn parse_public_accepts_ss58_and_hex() {
+		let public = alice().public();
+
+		assert_eq!(parse_public(&public.to_ss58check()), Ok(public.clone()));
+		assert_eq!(parse_public(&format!("0x{}", hex::encode(&public))), Ok(public));
+	}
+
+	fn stored(amount: Balance) -> StoredTicket {
+		StoredTicket {
+			counterparty: Hash::from_slice(alice().public().as_ref()),
+			signature: H512::repeat_byte(amount as u8),
+			pre_image: Hash::repeat_byte(1),
+			s_a: Hash::repeat_byte(2),
+			s_b: Hash::repeat_byte(3),
+			downstream: Hash::repeat_byte(4),
+			acknowledgement_signature: H512::repeat_byte(5),
+			on_chain_secret: Hash::repeat_byte(6),
+			amount,
+			win_prob: Hash::repeat_byte(0xff),
+			expiry: 100,
+		}
+	}
+
+	fn amounts(store: &TicketStore) -> Vec<Balance> {
+		let mut amounts: Vec<_> = store.tickets().unwrap().iter().map(|ticket| ticket.amount).collect();
+		amounts.sort();
+		amounts
+	}
+
+	#[test]
+	fn store_insert_and_remove() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = TicketStore::open(&dir.path().join("tickets")).unwrap();
+		assert!(store.tickets().unwrap().is_empty());
+
+		let id = store.insert(&stored(10)).unwrap();
+		assert_eq!(id, stored(10).id());
+		store.insert(&stored(20)).unwrap();
+		// Inserting the same ticket again overwrites it.
+		store.insert(&stored(20)).unwrap();
+		assert_eq!(amounts(&store), vec![10, 20]);
+
+		store.remove(&id).unwrap();
+		assert_eq!(amounts(&store), vec![20]);
+		// Removing a ticket that is not stored is fine.
+		store.remove(&id).unwrap();
+
+		// A reopened store sees the same tickets.
+		let store = TicketStore::open(&dir.path().join("tickets")).unwrap();
+		assert_eq!(amounts(&store), vec![20]);
+	}
+
+	#[test]
+	fn store_skips_invalid_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = TicketStore::open(dir.path()).unwrap();
+		store.insert(&stored(10)).unwrap();
+
+		fs::write(dir.path().join("broken.json"), "{").unwrap();
+		fs::write(dir.path().join("notes.txt"), "not a ticket").unwrap();
+
+		assert_eq!(amounts(&store), vec![10]);
+	}
+
+	#[test]
+	fn rejects_invalid_input() {
+		let encoded = encode_ticket(&ticket(Hash::zero()), TicketFormat::Hex).unwrap();
+
+		assert!(parse_ticket(&format!("{}00", encoded)).is_err());
+		assert!(parse_ticket(&encoded[..encoded.len() - 2]).is_err());
+		assert!(parse_ticket("0xzz").is_err());
+		assert!(parse_ticket("{\"challenge\": \"0x00\"}").is_err());
+
+		let json = encode_ticket(&ticket(Hash::zero()), TicketFormat::Json).unwrap();
+		assert!(parse_ticket(&json.replacen('{', "{\"unknown\": 1,", 1)).is_err());
+
+		assert!(parse_hash(&format!("0x{}", hex::encode(&[0u8; 31][..]))).is_err());
+		assert!(parse_signature(&format!("0x{}", hex::encode(&[0u8; 32][..]))).is_err());
+		assert!(parse_public(&format!("0x{}", hex::encode(&[0u8; 33][..]))).is_err());
+		assert!(parse_public("not a key").is_err());
+	}
+}
@@ -0,0 +1,162 @@
+//! Defends the channels of the local hopr account while they are pending settlement.
+//!
+//! Once the pending window of a channel ends, `withdraw` pays out the channel balance as it is
+//! stored on-chain, so every winning ticket that has not been redeemed by then is lost. The
+//! watchtower looks for settlements of our channels in the hopr events of every new best block,
+//! so that the redeemer submits the stored evidence right away and keeps doing so until the
+//! pending window ends.
+//!
+//! The only evidence that the runtime accepts while a channel is pending settlement are winning
+//! tickets, which also push back the end of the pending window. Recovery states carry no version,
+//! so there is no newer state that could be submitted against a stale `initiate_recovery`.
+
+use hopr_polkadot_runtime::{hopr::RawEvent, AccountId, Balance, Hash, Moment};
+use hopr_primitives::Channel;
+use log::{info, warn};
+use runtime_primitives::traits::BlakeTwo256;
+use std::collections::HashMap;
+
+/// An event of the hopr module as returned by the runtime.
+pub type HoprEvent = RawEvent<AccountId, Hash, Balance>;
+
+/// Watches the settlement of the channels of one account.
+pub struct Watchtower {
+	account: AccountId,
+	/// Channels of the account that are being settled, with their counterparty.
+	disputes: HashMap<Hash, AccountId>,
+}
+
+impl Watchtower {
+	pub fn new(account: AccountId) -> Self {
+		Watchtower {
+			account,
+			disputes: HashMap::new(),
+		}
+	}
+
+	/// Looks for settlements of channels between the account and one of `counterparties`.
+	pub fn on_events<I: IntoIterator<Item = AccountId>>(&mut self, events: &[HoprEvent], counterparties: I) {
+		let channels: HashMap<Hash, AccountId> = counterparties.into_iter()
+			.map(|counterparty| (hopr_primitives::channel_id::<BlakeTwo256, _>(&self.account, &counterparty), counterparty))
+			.collect();
+
+		for event in events {
+			if let RawEvent::InitiatedSettlement(channel_id, _) = event {
+				if let Some(counterparty) = channels.get(channel_id) {
+					warn!(
+						target: "hopr",
+						"Settlement of channel {:?} with {:?} was initiated, submitting stored tickets",
+						channel_id,
+						counterparty,
+					);
+					self.disputes.insert(*channel_id, counterparty.clone());
+				}
+			}
+		}
+	}
+
+	/// Whether the channel is being settled and has to be defended.
+	pub fn is_disputed(&self, channel_id: &Hash) -> bool {
+		self.disputes.contains_key(channel_id)
+	}
+
+	/// The channels that are being settled.
+	pub fn disputes(&self) -> impl Iterator<Item = &Hash> {
+		self.disputes.keys()
+	}
+
+	/// Updates a disputed channel with its current state and the time of the new best block.
+	///
+	/// `has_evidence` tells whether there is a stored ticket left that could be redeemed.
+	pub fn update(&mut self, channel_id: &Hash, channel: &Channel<Balance, Moment>, now: Moment, has_evidence: bool) {
+		let counterparty = match self.disputes.get(channel_id) {
+			Some(counterparty) => counterparty.clone(),
+			None => return,
+		};
+
+		match channel {
+			Channel::PendingSettlement(_, deadline) if now <= *deadline => if !has_evidence {
+				info!(target: "hopr", "No tickets left to defend channel {:?} with {:?}", channel_id, counterparty);
+			},
+			Channel::PendingSettlement(channel_balance, _) => {
+				info!(
+					target: "hopr",
+					"Pending window of channel {:?} with {:?} ended with balance_a {}",
+					channel_id,
+					counterparty,
+					channel_balance.balance_a,
+				);
+				self.disputes.remove(channel_id);
+			},
+			_ => {
+				info!(target: "hopr", "Channel {:?} with {:?} is no longer being settled", channel_id, counterparty);
+				self.disputes.remove(channel_id);
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hopr_primitives::ChannelBalance;
+	use primitives::{sr25519, Pair};
+
+	fn account(seed: &str) -> AccountId {
+		sr25519::Pair::from_string(seed, None).unwrap().public()
+	}
+
+	fn channel_id(a: &str, b: &str) -> Hash {
+		hopr_primitives::channel_id::<BlakeTwo256, _>(&account(a), &account(b))
+	}
+
+	#[test]
+	fn watches_settlements_of_own_channels() {
+		let mut watchtower = Watchtower::new(account("//Alice"));
+		let events = vec![
+			RawEvent::InitiatedSettlement(channel_id("//Alice", "//Bob"), 10),
+			RawEvent::InitiatedSettlement(channel_id("//Alice", "//Charlie"), 10),
+			RawEvent::InitiatedSettlement(channel_id("//Bob", "//Charlie"), 10),
+			RawEvent::PushedBackSettlement(channel_id("//Alice", "//Dave"), 10),
+		];
+
+		watchtower.on_events(&events, vec![account("//Bob"), account("//Dave")]);
+
+		assert!(watchtower.is_disputed(&channel_id("//Alice", "//Bob")));
+		// No tickets from Charlie, so there is nothing to defend.
+		assert!(!watchtower.is_disputed(&channel_id("//Alice", "//Charlie")));
+		assert!(!watchtower.is_disputed(&channel_id("//Bob", "//Charlie")));
+		assert!(!watchtower.is_disputed(&channel_id("//Alice", "//Dave")));
+		assert_eq!(watchtower.disputes().count(), 1);
+	}
+
+	#[test]
+	fn keeps_dispute_until_pending_window_ends() {
+		let mut watchtower = Watchtower::new(account("//Alice"));
+		let channel_id = channel_id("//Alice", "//Bob");
+		let channel_balance = ChannelBalance::new(20, 10);
+		watchtower.on_events(&[RawEvent::InitiatedSettlement(channel_id, 10)], vec![account("//Bob")]);
+
+		let pending = Channel::PendingSettlement(channel_balance.clone(), 100);
+		watchtower.update(&channel_id, &pending, 100, true);
+		watchtower.update(&channel_id, &pending, 100, false);
+		assert!(watchtower.is_disputed(&channel_id));
+
+		watchtower.update(&channel_id, &pending, 101, true);
+		assert!(!watchtower.is_disputed(&channel_id));
+	}
+
+	#[test]
+	fn drops_dispute_once_channel_is_no_longer_settled() {
+		let mut watchtower = Watchtower::new(account("//Alice"));
+		let channel_id = channel_id("//Alice", "//Bob");
+		watchtower.on_events(&[RawEvent::InitiatedSettlement(channel_id, 10)], vec![account("//Bob")]);
+
+		watchtower.update(&channel_id, &Channel::Uninitialized, 50, true);
+		assert!(!watchtower.is_disputed(&channel_id));
+
+		// Updates of channels that are not disputed are ignored.
+		watchtower.update(&channel_id, &Channel::PendingSettlement(ChannelBalance::new(20, 10), 100), 50, true);
+		assert!(!watchtower.is_disputed(&channel_id));
+	}
+}
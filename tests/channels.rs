@@ -0,0 +1,83 @@
+//! Integration tests that drive hopr channels through running nodes.
+//!
+//! They take a few block times each and are ignored by default, run them with
+//! `cargo test -p hopr-polkadot -- --ignored`.
+
+mod common;
+
+use common::{key, wait_until, TestNet};
+use hopr_polkadot::indexer;
+use hopr_polkadot_runtime::{hopr, hopr::{Channel, ChannelBalance}, Call};
+use primitives::{Pair, H256};
+use runtime_primitives::traits::{BlakeTwo256, Hash as HashT};
+
+const FUNDS: u128 = 1_000;
+
+/// Registers Alice and Bob and opens a channel that both of them fund with `FUNDS`.
+fn open_channel(net: &TestNet) -> H256 {
+	let (alice, bob) = (key("Alice"), key("Bob"));
+	let channel_id = hopr_primitives::channel_id::<BlakeTwo256, _>(&alice.public(), &bob.public());
+
+	for pair in &[&alice, &bob] {
+		let secret = BlakeTwo256::hash(BlakeTwo256::hash(pair.public().as_ref()).as_ref());
+		net.nodes[0].submit(pair, Call::Hopr(hopr::Call::init(H256::from_slice(pair.public().as_ref()), secret)));
+	}
+	net.wait_for_all("Alice and Bob to be registered", |node| {
+		node.on_chain_secret(alice.public()).is_some() && node.on_chain_secret(bob.public()).is_some()
+	});
+
+	let channel_balance = ChannelBalance {
+		balance: 2 * FUNDS,
+		balance_a: FUNDS,
+	};
+	let signature = bob.sign(&hopr_primitives::funding_message(&channel_balance));
+	net.nodes[0].submit(&alice, Call::Hopr(hopr::Call::create_funded(bob.public(), signature, FUNDS)));
+	net.wait_for_all("the channel to be opened", |node| node.channel(channel_id) == Channel::Active(channel_balance.clone()));
+
+	channel_id
+}
+
+#[test]
+#[ignore]
+fn channel_is_opened_on_all_nodes() {
+	let net = TestNet::new(1, 30400);
+
+	open_channel(&net);
+
+	assert!(net.nodes[1].best_number() > 0);
+	assert_eq!(net.nodes[0].best_hash(), net.nodes[1].best_hash());
+}
+
+#[test]
+#[ignore]
+fn settlement_submitted_to_full_node_is_included() {
+	let net = TestNet::new(1, 30410);
+	let channel_id = open_channel(&net);
+
+	// The full node does not author blocks, so the transaction has to reach the authority.
+	net.nodes[1].submit(&key("Bob"), Call::Hopr(hopr::Call::initiate_settlement(key("Alice").public())));
+	net.wait_for_all("the settlement to start", |node| match node.channel(channel_id) {
+		Channel::PendingSettlement(channel_balance, _) => channel_balance.balance == 2 * FUNDS,
+		_ => false,
+	});
+}
+
+#[test]
+#[ignore]
+fn opened_channel_is_indexed() {
+	let net = TestNet::new(1, 30420);
+	let dir = tempfile::TempDir::new().expect("Error creating temporary directory");
+	let path = dir.path().join("index.sqlite");
+	indexer::start(&net.nodes[1].service, &path).expect("Error starting indexer");
+
+	let channel_id = format!("0x{}", hex::encode(open_channel(&net)));
+
+	let conn = rusqlite::Connection::open(&path).expect("Error opening index");
+	let balance: String = wait_until("the channel to be indexed", || conn.query_row(
+		"SELECT channels.balance FROM channels JOIN blocks ON blocks.hash = channels.block_hash \
+			WHERE blocks.canonical = 1 AND channels.channel_id = ?1",
+		&[&channel_id],
+		|row| row.get(0),
+	).ok());
+	assert_eq!(balance, (2 * FUNDS).to_string());
+}
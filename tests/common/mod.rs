@@ -0,0 +1,148 @@
+//! Runs hopr full nodes in-process on the `dev` chain.
+//!
+//! The first node authors blocks with the dev authority `//Alice`, the others connect to it and
+//! import its blocks. Every node keeps its database in a temporary directory.
+
+use futures::Future;
+use hopr_polkadot::{chain_spec::Alternative, redeemer::sign_extrinsic, service::Factory};
+use hopr_polkadot_runtime::{
+	hopr::Channel, opaque::Block, AccountId, AccountNonceApi, Balance, Call, Hash, HoprApi, Moment,
+};
+use parity_codec::{Decode, Encode};
+use primitives::{sr25519, Pair};
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{Block as BlockT, ProvideRuntimeApi};
+use std::thread;
+use std::time::{Duration, Instant};
+use substrate_service::{Configuration, FactoryFullConfiguration, Roles, ServiceFactory};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+/// Time after which a node is expected to see the outcome of a transaction.
+///
+/// Blocks of the dev chain are authored every 10 seconds.
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn key(name: &str) -> sr25519::Pair {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed")
+}
+
+/// A running full node.
+pub struct TestNode {
+	pub service: <Factory as ServiceFactory>::FullService,
+	_dir: TempDir,
+}
+
+impl TestNode {
+	pub fn best_hash(&self) -> Hash {
+		self.service.client().info().expect("Error reading chain info").chain.best_hash
+	}
+
+	pub fn best_number(&self) -> u64 {
+		self.service.client().info().expect("Error reading chain info").chain.best_number
+	}
+
+	/// Signs `call` with `key` and submits it to the transaction pool of the node.
+	pub fn submit(&self, key: &sr25519::Pair, call: Call) {
+		let client = self.service.client();
+		let at = BlockId::hash(self.best_hash());
+		let index = client.runtime_api().account_nonce(&at, key.public()).expect("Error reading nonce");
+		let genesis_hash = client.info().expect("Error reading chain info").chain.genesis_hash;
+
+		let xt = sign_extrinsic(key, index, call, genesis_hash).encode();
+		let xt = <Block as BlockT>::Extrinsic::decode(&mut &xt[..]).expect("Extrinsic encoding is compatible; qed");
+		self.service.transaction_pool().submit_one(&at, xt).expect("Error submitting extrinsic");
+	}
+
+	/// The channel with the given id at the best block of the node.
+	pub fn channel(&self, channel_id: Hash) -> Channel<Balance, Moment> {
+		let at = BlockId::hash(self.best_hash());
+		self.service.client().runtime_api().channel(&at, channel_id).expect("Error reading channel")
+	}
+
+	/// The on-chain secret of `account` at the best block of the node.
+	pub fn on_chain_secret(&self, account: AccountId) -> Option<Hash> {
+		let at = BlockId::hash(self.best_hash());
+		self.service.client().runtime_api().on_chain_secret(&at, account).expect("Error reading on-chain secret")
+	}
+}
+
+/// Nodes of one test, running on their own tokio runtime.
+pub struct TestNet {
+	pub nodes: Vec<TestNode>,
+	runtime: Option<Runtime>,
+}
+
+impl TestNet {
+	/// Starts an authority and `full_nodes` further nodes that connect to it.
+	///
+	/// Nodes listen on consecutive ports from `base_port`, so that tests running in parallel have
+	/// to use distinct ranges.
+	pub fn new(full_nodes: usize, base_port: u16) -> Self {
+		let runtime = Runtime::new().expect("Error creating tokio runtime");
+		let mut net = TestNet {
+			nodes: Vec::new(),
+			runtime: Some(runtime),
+		};
+
+		net.start_node(Some("//Alice"), base_port, vec![]);
+		let boot_node = wait_until("the authority to listen", || net.nodes[0].service.network().node_id());
+
+		for port in (base_port + 1..).take(full_nodes) {
+			net.start_node(None, port, vec![boot_node.clone()]);
+		}
+
+		net
+	}
+
+	fn start_node(&mut self, key: Option<&str>, port: u16, boot_nodes: Vec<String>) {
+		let dir = TempDir::new().expect("Error creating temporary directory");
+		let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+
+		let spec = Alternative::Development.load().expect("dev chain spec is valid; qed");
+		let mut config: FactoryFullConfiguration<Factory> = Configuration::default_with_spec(spec);
+		config.name = format!("hopr-test-{}", port);
+		config.roles = if key.is_some() { Roles::AUTHORITY } else { Roles::FULL };
+		config.keys = key.into_iter().map(String::from).collect();
+		config.database_path = path("db");
+		config.keystore_path = path("keystore");
+		config.network.net_config_path = Some(path("network"));
+		config.network.listen_addresses = vec![
+			format!("/ip4/127.0.0.1/tcp/{}", port).parse().expect("valid multiaddr; qed"),
+		];
+		config.network.boot_nodes = boot_nodes;
+
+		let executor = self.runtime.as_ref().expect("runtime lives as long as the net; qed").executor();
+		let service = Factory::new_full(config, executor).expect("Error starting node");
+		self.nodes.push(TestNode { service, _dir: dir });
+	}
+
+	/// Waits until `f` holds on every node.
+	pub fn wait_for_all<F: Fn(&TestNode) -> bool>(&self, what: &str, f: F) {
+		wait_until(what, || if self.nodes.iter().all(|node| f(node)) { Some(()) } else { None })
+	}
+}
+
+impl Drop for TestNet {
+	fn drop(&mut self) {
+		// Services have to shut down before the runtime that drives them.
+		self.nodes.clear();
+		if let Some(runtime) = self.runtime.take() {
+			let _ = runtime.shutdown_now().wait();
+		}
+	}
+}
+
+/// Polls `f` until it returns a value and panics after `TIMEOUT`.
+pub fn wait_until<T, F: FnMut() -> Option<T>>(what: &str, mut f: F) -> T {
+	let start = Instant::now();
+	loop {
+		if let Some(value) = f() {
+			return value;
+		}
+		if start.elapsed() > TIMEOUT {
+			panic!("Timeout waiting for {}", what);
+		}
+		thread::sleep(Duration::from_millis(500));
+	}
+}